@@ -0,0 +1,334 @@
+//! Contract checks a deployment can run against its own instance.
+//!
+//! The checks cover the response models, error codes, result limits and the
+//! enforcement of authentication, which depend on the configuration of the
+//! deployment rather than on the code alone.
+//!
+//! ```no_run
+//! # async fn run() {
+//! use search_client::conformance::{Conformance, ConformanceOptions};
+//!
+//! let report = Conformance::new("https://search.example.com", "token")
+//!     .run(&ConformanceOptions::default())
+//!     .await;
+//! assert!(report.passed(), "{}", report);
+//! # }
+//! ```
+
+use crate::{SearchResult, Status};
+
+use std::fmt;
+
+use reqwest::{header::ACCEPT, RequestBuilder, StatusCode};
+use search_models::HealthStatus;
+use serde::de::DeserializeOwned;
+
+/// Query expected to be valid on every deployment
+const DEFAULT_QUERY: &str = "ammo";
+
+/// Longest query accepted by the search endpoint
+const MAX_QUERY_LEN: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct ConformanceOptions {
+    /// Query the search checks are run with
+    pub query: String,
+    /// Whether the token has the admin scope, admin endpoints must be denied otherwise
+    pub admin: bool,
+}
+
+impl Default for ConformanceOptions {
+    fn default() -> Self {
+        Self {
+            query: DEFAULT_QUERY.to_string(),
+            admin: false,
+        }
+    }
+}
+
+/// Outcome of a single check
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: &'static str,
+    /// Reason the check failed
+    pub failure: Option<String>,
+}
+
+impl CheckResult {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConformanceReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl ConformanceReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(CheckResult::passed)
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks.iter().filter(|c| !c.passed())
+    }
+
+    fn record(&mut self, name: &'static str, result: Result<(), String>) {
+        self.checks.push(CheckResult {
+            name,
+            failure: result.err(),
+        });
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self.failed().count();
+        write!(
+            f,
+            "{} of {} checks passed",
+            self.checks.len() - failed,
+            self.checks.len()
+        )?;
+        for check in self.failed() {
+            write!(
+                f,
+                "\n  {}: {}",
+                check.name,
+                check.failure.as_deref().unwrap_or_default()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs the checks against a deployment with the given token
+#[derive(Debug, Clone)]
+pub struct Conformance {
+    http: reqwest::Client,
+    base_url: String,
+    token: String,
+}
+
+impl Conformance {
+    pub fn new<S: Into<String>, T: Into<String>>(base_url: S, token: T) -> Self {
+        Self::with_http_client(reqwest::Client::new(), base_url, token)
+    }
+
+    pub fn with_http_client<S: Into<String>, T: Into<String>>(
+        http: reqwest::Client,
+        base_url: S,
+        token: T,
+    ) -> Self {
+        Self {
+            http,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token: token.into(),
+        }
+    }
+
+    /// Runs all checks, failed checks don't stop the following ones
+    pub async fn run(&self, opts: &ConformanceOptions) -> ConformanceReport {
+        let mut report = ConformanceReport::default();
+
+        report.record("health", self.health().await);
+        report.record("search_result", self.search_result(opts).await);
+        report.record("search_limit", self.search_limit(opts).await);
+        report.record("search_term_too_short", self.term_too_short().await);
+        report.record("search_term_too_long", self.term_too_long().await);
+        report.record("search_invalid_type", self.invalid_type(opts).await);
+        report.record("search_unknown_refinement", self.unknown_refinement().await);
+        report.record("auth_missing_token", self.missing_token(opts).await);
+        report.record("auth_invalid_token", self.invalid_token(opts).await);
+        report.record("auth_admin_scope", self.admin_scope(opts).await);
+        report.record("not_found", self.not_found().await);
+
+        report
+    }
+
+    async fn health(&self) -> Result<(), String> {
+        let req = self.http.get(self.url("/health")).bearer_auth(&self.token);
+        let health: HealthStatus = expect_json(req, StatusCode::OK).await?;
+
+        if health.indexes.is_empty() {
+            return Err("no indexes are reported".to_string());
+        }
+
+        Ok(())
+    }
+
+    async fn search_result(&self, opts: &ConformanceOptions) -> Result<(), String> {
+        let req = self.search(&[("query", opts.query.as_str())]);
+        let result: SearchResult = expect_json(req, StatusCode::OK).await?;
+
+        if result.count != result.data.len() {
+            return Err(format!(
+                "count is {}, but {} documents are returned",
+                result.count,
+                result.data.len()
+            ));
+        }
+        if result.meta.query.is_empty() {
+            return Err("the query is missing in the meta data".to_string());
+        }
+
+        Ok(())
+    }
+
+    async fn search_limit(&self, opts: &ConformanceOptions) -> Result<(), String> {
+        for limit in [1, 5] {
+            let limit_param = limit.to_string();
+            let req = self.search(&[("query", opts.query.as_str()), ("limit", &limit_param)]);
+            let result: SearchResult = expect_json(req, StatusCode::OK).await?;
+
+            if result.data.len() > limit {
+                return Err(format!(
+                    "{} documents are returned for limit {}",
+                    result.data.len(),
+                    limit
+                ));
+            }
+            if result.meta.options.limit != limit {
+                return Err(format!(
+                    "limit {} is applied as {}",
+                    limit, result.meta.options.limit
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn term_too_short(&self) -> Result<(), String> {
+        let req = self.search(&[("query", "a")]);
+        expect_error(req, StatusCode::BAD_REQUEST).await
+    }
+
+    async fn term_too_long(&self) -> Result<(), String> {
+        let query = "a".repeat(MAX_QUERY_LEN + 1);
+        let req = self.search(&[("query", query.as_str())]);
+        expect_error(req, StatusCode::BAD_REQUEST).await
+    }
+
+    async fn invalid_type(&self, opts: &ConformanceOptions) -> Result<(), String> {
+        let req = self.search(&[("query", opts.query.as_str()), ("type", "invalid")]);
+        expect_error(req, StatusCode::BAD_REQUEST).await
+    }
+
+    async fn unknown_refinement(&self) -> Result<(), String> {
+        let req = self.search(&[("refine", "unknown")]);
+        expect_error(req, StatusCode::NOT_FOUND).await
+    }
+
+    async fn missing_token(&self, opts: &ConformanceOptions) -> Result<(), String> {
+        let req = self
+            .http
+            .get(self.url("/search"))
+            .query(&[("query", opts.query.as_str())]);
+        expect_error(req, StatusCode::UNAUTHORIZED).await
+    }
+
+    async fn invalid_token(&self, opts: &ConformanceOptions) -> Result<(), String> {
+        let req = self
+            .http
+            .get(self.url("/search"))
+            .query(&[("query", opts.query.as_str())])
+            .bearer_auth("invalid");
+        expect_error(req, StatusCode::UNAUTHORIZED).await
+    }
+
+    async fn admin_scope(&self, opts: &ConformanceOptions) -> Result<(), String> {
+        let req = self
+            .http
+            .get(self.url("/admin/stats"))
+            .bearer_auth(&self.token);
+        if opts.admin {
+            expect_status(req, StatusCode::OK).await
+        } else {
+            expect_error(req, StatusCode::FORBIDDEN).await
+        }
+    }
+
+    async fn not_found(&self) -> Result<(), String> {
+        let req = self
+            .http
+            .get(self.url("/conformance/unknown"))
+            .bearer_auth(&self.token);
+        expect_status(req, StatusCode::NOT_FOUND).await
+    }
+
+    fn search(&self, params: &[(&str, &str)]) -> RequestBuilder {
+        self.http
+            .get(self.url("/search"))
+            .query(params)
+            .bearer_auth(&self.token)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+}
+
+async fn expect_json<T>(req: RequestBuilder, status: StatusCode) -> Result<T, String>
+where
+    T: DeserializeOwned,
+{
+    let res = send(req).await?;
+    if res.status() != status {
+        return Err(unexpected_status(status, res).await);
+    }
+
+    res.json()
+        .await
+        .map_err(|e| format!("invalid response body: {}", e))
+}
+
+async fn expect_status(req: RequestBuilder, status: StatusCode) -> Result<(), String> {
+    let res = send(req).await?;
+    if res.status() != status {
+        return Err(unexpected_status(status, res).await);
+    }
+
+    Ok(())
+}
+
+/// Checks that the request fails with the status and an error body of the same code
+async fn expect_error(req: RequestBuilder, status: StatusCode) -> Result<(), String> {
+    let res = send(req).await?;
+    if res.status() != status {
+        return Err(unexpected_status(status, res).await);
+    }
+
+    let body = res
+        .json::<Status>()
+        .await
+        .map_err(|e| format!("invalid error body: {}", e))?;
+    if body.code != status {
+        return Err(format!(
+            "error body has code {}, but the response {}",
+            body.code, status
+        ));
+    }
+
+    Ok(())
+}
+
+async fn send(req: RequestBuilder) -> Result<reqwest::Response, String> {
+    req.header(ACCEPT, "application/json")
+        .send()
+        .await
+        .map_err(|e| format!("request failed: {}", e))
+}
+
+async fn unexpected_status(expected: StatusCode, res: reqwest::Response) -> String {
+    let status = res.status();
+    let message = res
+        .json::<Status>()
+        .await
+        .map(|s| format!(": {}", s.message))
+        .unwrap_or_default();
+
+    format!("expected status {}, got {}{}", expected, status, message)
+}
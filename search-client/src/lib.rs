@@ -0,0 +1,228 @@
+#[cfg(feature = "conformance")]
+pub mod conformance;
+mod model;
+
+use reqwest::{header::ACCEPT, RequestBuilder};
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+pub use model::{DocPrice, Document, SearchResult};
+pub use search_models::{
+    AppliedOptions, CreateTokenRequest, DocType, HealthStatus, IndexStatus, Scope, SearchMeta,
+    ServiceStatus, Services, Status, TokenResponse,
+};
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("HTTP error: {0}")]
+    HttpError(#[from] reqwest::Error),
+    #[error("API error ({}): {}", _0.code, _0.message)]
+    ApiError(Status),
+    #[error("No token set")]
+    MissingToken,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Parameters of a search request
+#[derive(Debug, Clone, Default)]
+pub struct SearchQuery {
+    query: String,
+    types: Vec<DocType>,
+    kinds: Vec<String>,
+    limit: Option<usize>,
+    lang: Option<String>,
+    conjunction: bool,
+    autocorrect: bool,
+    lenient: bool,
+    fuzzy: Option<u8>,
+    refine: Option<String>,
+}
+
+impl SearchQuery {
+    pub fn new<S: Into<String>>(query: S) -> Self {
+        Self {
+            query: query.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Filters a previous result by its fingerprint instead of searching again
+    pub fn refine<S: Into<String>>(fingerprint: S) -> Self {
+        Self {
+            refine: Some(fingerprint.into()),
+            ..Default::default()
+        }
+    }
+
+    pub fn doc_type(mut self, r#type: DocType) -> Self {
+        self.types.push(r#type);
+        self
+    }
+
+    pub fn kind<S: Into<String>>(mut self, kind: S) -> Self {
+        self.kinds.push(kind.into());
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn lang<S: Into<String>>(mut self, lang: S) -> Self {
+        self.lang = Some(lang.into());
+        self
+    }
+
+    pub fn conjunction(mut self, enabled: bool) -> Self {
+        self.conjunction = enabled;
+        self
+    }
+
+    pub fn autocorrect(mut self, enabled: bool) -> Self {
+        self.autocorrect = enabled;
+        self
+    }
+
+    /// Searches invalid query syntax as plain terms instead of failing
+    pub fn lenient(mut self, enabled: bool) -> Self {
+        self.lenient = enabled;
+        self
+    }
+
+    /// Matches the words of names within the edit distance, at most 2
+    pub fn fuzzy(mut self, distance: u8) -> Self {
+        self.fuzzy = Some(distance);
+        self
+    }
+
+    fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = Vec::new();
+
+        if !self.query.is_empty() {
+            params.push(("query", self.query.clone()));
+        }
+        if let Some(r) = &self.refine {
+            params.push(("refine", r.clone()));
+        }
+        for t in &self.types {
+            params.push(("type", t.to_string()));
+        }
+        for k in &self.kinds {
+            params.push(("kind", k.clone()));
+        }
+        if let Some(l) = self.limit {
+            params.push(("limit", l.to_string()));
+        }
+        if let Some(l) = &self.lang {
+            params.push(("lang", l.clone()));
+        }
+        if self.conjunction {
+            params.push(("conjunction", "true".to_string()));
+        }
+        if self.autocorrect {
+            params.push(("autocorrect", "true".to_string()));
+        }
+        if self.lenient {
+            params.push(("lenient", "true".to_string()));
+        }
+        if let Some(d) = self.fuzzy {
+            params.push(("fuzzy", d.to_string()));
+        }
+
+        params
+    }
+}
+
+/// Typed client of the search API
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+}
+
+impl Client {
+    pub fn new<S: Into<String>>(base_url: S) -> Self {
+        Self::with_http_client(reqwest::Client::new(), base_url)
+    }
+
+    pub fn with_http_client<S: Into<String>>(http: reqwest::Client, base_url: S) -> Self {
+        Self {
+            http,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token: None,
+        }
+    }
+
+    pub fn with_token<S: Into<String>>(mut self, token: S) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn set_token<S: Into<String>>(&mut self, token: S) {
+        self.token = Some(token.into());
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    pub async fn search(&self, query: &SearchQuery) -> Result<SearchResult> {
+        let req = self.http.get(self.url("/search")).query(&query.params());
+
+        self.send(req).await
+    }
+
+    /// Renews the current token and uses the renewed one for further requests
+    pub async fn renew_token(&mut self) -> Result<TokenResponse> {
+        let req = self.http.get(self.url("/token"));
+        let res: TokenResponse = self.send(req).await?;
+        self.token = Some(res.token.clone());
+
+        Ok(res)
+    }
+
+    pub async fn create_token(&self, request: &CreateTokenRequest) -> Result<TokenResponse> {
+        let req = self.http.post(self.url("/token")).json(request);
+
+        self.send(req).await
+    }
+
+    pub async fn health(&self) -> Result<HealthStatus> {
+        let req = self.http.get(self.url("/health"));
+
+        self.send(req).await
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    async fn send<T>(&self, req: RequestBuilder) -> Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        let token = self.token.as_ref().ok_or(Error::MissingToken)?;
+
+        let res = req
+            .bearer_auth(token)
+            .header(ACCEPT, "application/json")
+            .send()
+            .await?;
+
+        if !res.status().is_success() {
+            let status = res.status();
+            return match res.json::<Status>().await {
+                Ok(s) => Err(Error::ApiError(s)),
+                Err(_) => Err(Error::ApiError(Status::new(
+                    status,
+                    status.canonical_reason().unwrap_or_default(),
+                ))),
+            };
+        }
+
+        Ok(res.json().await?)
+    }
+}
@@ -0,0 +1,36 @@
+use search_models::DocType;
+use serde::Deserialize;
+
+/// Result of a search
+pub type SearchResult = search_models::SearchResult<Document>;
+
+/// Result document, the description and payloads are missing for tokens
+/// without the search scope
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Document {
+    pub id: String,
+    pub name: String,
+    pub short_name: Option<String>,
+    pub description: Option<String>,
+    pub kind: Option<String>,
+    pub r#type: DocType,
+    #[serde(default)]
+    pub locations: Vec<String>,
+    pub parent: Option<String>,
+    pub penetration: Option<f64>,
+    pub damage: Option<f64>,
+    pub price: Option<DocPrice>,
+    /// Fields the query matched in
+    #[serde(default)]
+    pub matched: Vec<String>,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocPrice {
+    pub amount: f64,
+    pub currency: String,
+    pub normalized: Option<f64>,
+}
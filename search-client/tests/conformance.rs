@@ -0,0 +1,39 @@
+//! Runs the conformance checks against a deployment.
+//!
+//! Skipped unless `SEARCH_CONFORMANCE_URL` and `SEARCH_CONFORMANCE_TOKEN` are
+//! set, e.g.
+//! `SEARCH_CONFORMANCE_URL=https://search.example.com SEARCH_CONFORMANCE_TOKEN=...
+//! cargo test -p search-client --features conformance --test conformance`.
+//! Set `SEARCH_CONFORMANCE_ADMIN=true` if the token has the admin scope.
+
+#![cfg(feature = "conformance")]
+
+use std::env;
+
+use search_client::conformance::{Conformance, ConformanceOptions};
+
+#[tokio::test]
+async fn deployment_conforms() {
+    let (url, token) = match (
+        env::var("SEARCH_CONFORMANCE_URL"),
+        env::var("SEARCH_CONFORMANCE_TOKEN"),
+    ) {
+        (Ok(url), Ok(token)) => (url, token),
+        _ => {
+            eprintln!("SEARCH_CONFORMANCE_URL or SEARCH_CONFORMANCE_TOKEN not set, skipping");
+            return;
+        }
+    };
+
+    let mut opts = ConformanceOptions {
+        admin: env::var("SEARCH_CONFORMANCE_ADMIN").is_ok_and(|v| v == "true"),
+        ..Default::default()
+    };
+    if let Ok(query) = env::var("SEARCH_CONFORMANCE_QUERY") {
+        opts.query = query;
+    }
+
+    let report = Conformance::new(url, token).run(&opts).await;
+
+    assert!(report.passed(), "{}", report);
+}
@@ -0,0 +1,135 @@
+use crate::index::DocType;
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::Hasher,
+};
+
+use serde::Serialize;
+use tantivy::{schema::Field, Document};
+
+/// Maximum number of IDs listed per change
+const MAX_DIFF_IDS: usize = 100;
+
+/// Content hashes of the indexed documents by ID
+pub(crate) type Fingerprints = HashMap<String, (DocType, u64)>;
+
+/// Returns the hash of the field values of the document.
+///
+/// The modification time is left out, so documents whose upstream timestamp
+/// is bumped without any change of their content keep their fingerprint.
+pub(crate) fn fingerprint(doc: &Document, modified: Field) -> u64 {
+    let values = doc
+        .field_values()
+        .iter()
+        .filter(|v| v.field() != modified)
+        .collect::<Vec<_>>();
+
+    // Values aren't hashable because of floats, their debug output is
+    let mut hasher = DefaultHasher::new();
+    hasher.write(format!("{:?}", values).as_bytes());
+    hasher.finish()
+}
+
+/// IDs of changed documents, only the first ones are listed
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangedIds {
+    pub count: usize,
+    pub ids: Vec<String>,
+}
+
+impl ChangedIds {
+    fn push(&mut self, id: &str) {
+        self.count += 1;
+        if self.ids.len() < MAX_DIFF_IDS {
+            self.ids.push(id.to_owned());
+        }
+    }
+}
+
+/// Changes of the documents of a type
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeDiff {
+    pub r#type: DocType,
+    /// Documents of the type in the previous generation
+    pub previous: usize,
+    pub added: ChangedIds,
+    pub removed: ChangedIds,
+    pub modified: ChangedIds,
+}
+
+impl TypeDiff {
+    fn new(r#type: DocType) -> Self {
+        Self {
+            r#type,
+            previous: 0,
+            added: ChangedIds::default(),
+            removed: ChangedIds::default(),
+            modified: ChangedIds::default(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.count == 0 && self.removed.count == 0 && self.modified.count == 0
+    }
+}
+
+/// Changes of the documents between two generations of an index
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationDiff {
+    pub generation: u64,
+    pub types: Vec<TypeDiff>,
+}
+
+impl GenerationDiff {
+    pub(crate) fn between(
+        previous: &Fingerprints,
+        current: &Fingerprints,
+        generation: u64,
+    ) -> Self {
+        let mut types = DocType::ALL.map(TypeDiff::new);
+
+        for (id, (t, hash)) in current {
+            let diff = type_diff(&mut types, t);
+            match previous.get(id) {
+                None => diff.added.push(id),
+                Some((p, _)) if p != t => {
+                    diff.added.push(id);
+                    type_diff(&mut types, p).removed.push(id);
+                }
+                Some((_, h)) if h != hash => diff.modified.push(id),
+                Some(_) => {}
+            }
+        }
+
+        for (id, (t, _)) in previous {
+            let diff = type_diff(&mut types, t);
+            diff.previous += 1;
+            if !current.contains_key(id) {
+                diff.removed.push(id);
+            }
+        }
+
+        for diff in types.iter_mut() {
+            diff.added.ids.sort_unstable();
+            diff.removed.ids.sort_unstable();
+            diff.modified.ids.sort_unstable();
+        }
+
+        Self {
+            generation,
+            types: types.into(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.iter().all(TypeDiff::is_empty)
+    }
+}
+
+fn type_diff<'a>(types: &'a mut [TypeDiff], t: &DocType) -> &'a mut TypeDiff {
+    types.iter_mut().find(|d| d.r#type == *t).unwrap()
+}
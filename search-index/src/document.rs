@@ -0,0 +1,185 @@
+use crate::{
+    index::DocType,
+    schema::IndexField,
+    supplement::{Preset, Supplements},
+};
+
+use std::collections::HashMap;
+
+use chrono::{DateTime as ChronoDateTime, Utc};
+use tantivy::{
+    schema::{Field, Schema},
+    tokenizer::Language,
+    DateTime, Document,
+};
+use tarkov_database_rs::model::item::common::Item;
+
+/// Data shared by all documents of a batch
+pub(crate) struct DocContext<'a> {
+    schema: &'a Schema,
+    lang: Language,
+    supplements: &'a Supplements,
+    /// Short and full names by item ID
+    names: HashMap<&'a str, [&'a str; 2]>,
+    /// Weapon names each mod is compatible with
+    compatible: HashMap<&'a str, Vec<&'a str>>,
+}
+
+impl<'a> DocContext<'a> {
+    pub(crate) fn new(
+        schema: &'a Schema,
+        lang: Language,
+        items: &'a [Item],
+        supplements: &'a Supplements,
+    ) -> Self {
+        let names = items
+            .iter()
+            .map(|i| (i.id.as_str(), [i.short_name.as_str(), i.name.as_str()]))
+            .collect::<HashMap<_, _>>();
+
+        let mut compatible: HashMap<&str, Vec<&str>> = HashMap::new();
+        for preset in supplements.presets.iter() {
+            let weapon = match names.get(preset.weapon.as_str()) {
+                Some(n) => n,
+                None => continue,
+            };
+            for m in preset.mods.iter() {
+                let entry = compatible.entry(m.as_str()).or_default();
+                for name in weapon {
+                    if !entry.contains(name) {
+                        entry.push(*name);
+                    }
+                }
+            }
+        }
+
+        Self {
+            schema,
+            lang,
+            supplements,
+            names,
+            compatible,
+        }
+    }
+
+    pub(crate) fn field(&self, field: IndexField) -> Field {
+        self.schema.get_field(field.name()).unwrap()
+    }
+}
+
+/// Source model mapped to an index document
+pub(crate) trait IntoIndexDocument {
+    fn doc_id(&self) -> &str;
+
+    fn doc_type(&self) -> DocType;
+
+    /// Upstream modification time, if the source has one
+    fn doc_modified(&self) -> Option<ChronoDateTime<Utc>>;
+
+    /// Names used to validate the document
+    fn doc_names(&self) -> Vec<&str>;
+
+    fn to_document(&self, ctx: &DocContext) -> Document;
+}
+
+impl IntoIndexDocument for Item {
+    fn doc_id(&self) -> &str {
+        &self.id
+    }
+
+    fn doc_type(&self) -> DocType {
+        DocType::Item
+    }
+
+    fn doc_modified(&self) -> Option<ChronoDateTime<Utc>> {
+        Some(self.modified)
+    }
+
+    fn doc_names(&self) -> Vec<&str> {
+        vec![&self.short_name, &self.name]
+    }
+
+    fn to_document(&self, ctx: &DocContext) -> Document {
+        let name_prefix_field = ctx.field(IndexField::NamePrefix);
+
+        let mut doc = Document::default();
+        doc.add_text(ctx.field(IndexField::ID), &self.id);
+        doc.add_text(ctx.field(IndexField::Name), &self.name);
+        doc.add_text(ctx.field(IndexField::NameTerms), &self.name);
+        doc.add_text(ctx.field(IndexField::ShortName), &self.short_name);
+        doc.add_text(name_prefix_field, &self.short_name);
+        doc.add_text(name_prefix_field, &self.name);
+        doc.add_text(
+            ctx.field(IndexField::Description(ctx.lang)),
+            &self.description,
+        );
+        doc.add_text(ctx.field(IndexField::Kind), &self.kind);
+        doc.add_text(ctx.field(IndexField::Type), DocType::Item.to_string());
+        doc.add_date(
+            ctx.field(IndexField::Modified),
+            DateTime::from_timestamp_secs(self.modified.timestamp()),
+        );
+
+        let supplements = ctx.supplements;
+        let location_field = ctx.field(IndexField::Locations);
+        for location in supplements.locations.get(&self.id).into_iter().flatten() {
+            doc.add_text(location_field, location);
+        }
+        let compatible_field = ctx.field(IndexField::Compatible);
+        for name in ctx.compatible.get(self.id.as_str()).into_iter().flatten() {
+            doc.add_text(compatible_field, name);
+        }
+        if let Some(b) = supplements.ballistics.get(&self.id) {
+            doc.add_f64(ctx.field(IndexField::Penetration), b.penetration);
+            doc.add_f64(ctx.field(IndexField::Damage), b.damage);
+        }
+        if let Some(p) = supplements.prices.get(&self.id) {
+            doc.add_f64(ctx.field(IndexField::PriceOriginal), p.amount);
+            doc.add_text(ctx.field(IndexField::Currency), &p.currency);
+            if let Some(normalized) = supplements.normalize_price(p) {
+                doc.add_f64(ctx.field(IndexField::Price), normalized);
+            }
+        }
+
+        doc
+    }
+}
+
+impl IntoIndexDocument for Preset {
+    fn doc_id(&self) -> &str {
+        &self.id
+    }
+
+    fn doc_type(&self) -> DocType {
+        DocType::Preset
+    }
+
+    fn doc_modified(&self) -> Option<ChronoDateTime<Utc>> {
+        None
+    }
+
+    fn doc_names(&self) -> Vec<&str> {
+        vec![&self.name]
+    }
+
+    fn to_document(&self, ctx: &DocContext) -> Document {
+        let mut doc = Document::default();
+        doc.add_text(ctx.field(IndexField::ID), &self.id);
+        doc.add_text(ctx.field(IndexField::Name), &self.name);
+        doc.add_text(ctx.field(IndexField::NameTerms), &self.name);
+        doc.add_text(ctx.field(IndexField::NamePrefix), &self.name);
+        doc.add_text(
+            ctx.field(IndexField::Description(ctx.lang)),
+            &self.description,
+        );
+        doc.add_text(ctx.field(IndexField::Type), DocType::Preset.to_string());
+        doc.add_text(ctx.field(IndexField::Parent), &self.weapon);
+
+        let compatible_field = ctx.field(IndexField::Compatible);
+        for name in ctx.names.get(self.weapon.as_str()).into_iter().flatten() {
+            doc.add_text(compatible_field, name);
+        }
+
+        doc
+    }
+}
@@ -1,21 +1,82 @@
 use crate::{
+    diff::{fingerprint, Fingerprints, GenerationDiff},
+    document::{DocContext, IntoIndexDocument},
+    pattern::PatternQuery,
     schema::{IndexField, IndexSchema},
+    spelling::{closest_candidates, Vocabulary},
+    supplement::Supplements,
     tokenizer::{NgramOptions, Tokenizer},
     Error, Result,
 };
 
-use std::{fmt, str::FromStr};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt, fs,
+    ops::Bound,
+    path::Path,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
 
+use chrono::{DateTime, TimeZone, Utc};
+pub use search_models::DocType;
 use serde::{Deserialize, Serialize};
 use tantivy::{
-    collector::TopDocs, query::QueryParser, schema::Schema, tokenizer::Language, Document,
-    Index as TantivyIndex, IndexReader, ReloadPolicy,
+    collector::{Count, TopDocs},
+    directory::MmapDirectory,
+    query::{
+        BooleanQuery, BoostQuery, EnableScoring, Explanation, FuzzyTermQuery, Occur, Query,
+        QueryParser, RangeQuery, TermQuery, Weight,
+    },
+    schema::{Field, IndexRecordOption, Schema},
+    tokenizer::{Language, TokenStream},
+    DocAddress, DocSet, Index as TantivyIndex, IndexReader, IndexWriter, ReloadPolicy, Searcher,
+    TantivyError, Term,
 };
 use tarkov_database_rs::model::item::common::Item;
 
-const WRITE_BUFFER: usize = 50_000_000;
+/// Memory budget of an index writer without a configured limit
+pub const WRITE_BUFFER: usize = 50_000_000;
 
-#[derive(Debug, Serialize)]
+pub(crate) const MAX_PREFIX_LEN: usize = 20;
+
+/// Maximum number of kinds a typed query is expanded to
+const MAX_KIND_CLAUSES: usize = 16;
+
+/// Number of suggestions for an unknown kind
+const KIND_SUGGESTIONS: usize = 3;
+
+/// Maximum number of wildcard and fuzzy operators in a query
+const MAX_WILDCARDS: usize = 4;
+
+/// Largest edit distance of fuzzy name terms supported by tantivy
+pub const MAX_FUZZY_DISTANCE: u8 = 2;
+
+/// Maximum number of words matched fuzzily, each one scans the term dictionary
+const MAX_FUZZY_TERMS: usize = 8;
+
+/// Maximum number of terms of a parsed query, ngram fields expand each word to many terms
+const MAX_QUERY_TERMS: usize = 1024;
+
+/// Smallest memory budget of an index writer accepted by tantivy
+pub const MIN_WRITE_BUFFER: usize = 15_000_000;
+
+/// Meta file of a tantivy index directory
+const META_FILE: &str = "meta.json";
+
+/// Files tantivy keeps next to the segment files
+const INDEX_FILES: [&str; 4] = [
+    META_FILE,
+    ".managed.json",
+    ".tantivy-meta.lock",
+    ".tantivy-writer.lock",
+];
+
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct IndexDoc {
     id: String,
@@ -26,53 +87,565 @@ pub struct IndexDoc {
     #[serde(skip_serializing_if = "Option::is_none")]
     kind: Option<String>,
     r#type: DocType,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    locations: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    penetration: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    damage: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    price: Option<DocPrice>,
+    /// Upstream modification time, only known for items
+    #[serde(skip_serializing_if = "Option::is_none")]
+    modified: Option<DateTime<Utc>>,
+    /// Fields the query matched in
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    matched: Vec<&'static str>,
+    score: f32,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
-pub enum DocType {
-    Item,
-    Location,
-    Module,
+pub struct DocPrice {
+    amount: f64,
+    currency: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    normalized: Option<f64>,
 }
 
-impl FromStr for DocType {
-    type Err = crate::Error;
+/// Reduced projection of an [`IndexDoc`] without descriptions and payloads
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocSummary {
+    id: String,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    short_name: Option<String>,
+    r#type: DocType,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    matched: Vec<&'static str>,
+    score: f32,
+}
 
-    fn from_str(s: &str) -> Result<Self> {
-        let t = match s {
-            "item" => DocType::Item,
-            "location" => DocType::Location,
-            "module" => DocType::Module,
-            _ => return Err(Error::ParseError("unknown doc type".to_string())),
-        };
+impl From<IndexDoc> for DocSummary {
+    fn from(doc: IndexDoc) -> Self {
+        Self {
+            id: doc.id,
+            name: doc.name,
+            short_name: doc.short_name,
+            r#type: doc.r#type,
+            matched: doc.matched,
+            score: doc.score,
+        }
+    }
+}
+
+impl IndexDoc {
+    pub fn score(&self) -> f32 {
+        self.score
+    }
+
+    pub fn doc_type(&self) -> &DocType {
+        &self.r#type
+    }
+
+    /// Returns true if the document is of one of the types and kinds and
+    /// matches all numeric filters. Empty types or kinds match all documents.
+    pub fn matches(&self, types: &[DocType], kinds: &[&str], filters: &[NumericFilter]) -> bool {
+        if !types.is_empty() && !types.contains(&self.r#type) {
+            return false;
+        }
+
+        if !kinds.is_empty()
+            && !self
+                .kind
+                .as_ref()
+                .is_some_and(|k| kinds.iter().any(|v| v.eq_ignore_ascii_case(k)))
+        {
+            return false;
+        }
+
+        filters.iter().all(|f| {
+            let value = match f.field {
+                NumericField::Penetration => self.penetration,
+                NumericField::Damage => self.damage,
+                NumericField::Price => self.price.as_ref().and_then(|p| p.normalized),
+                NumericField::Modified => self.modified.map(|m| m.timestamp() as f64),
+            };
 
-        Ok(t)
+            value.is_some_and(|v| f.contains(v))
+        })
     }
 }
 
-impl fmt::Display for DocType {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            DocType::Item => write!(f, "item"),
-            DocType::Location => write!(f, "location"),
-            DocType::Module => write!(f, "module"),
+/// Search text restricted to a doc type and item kinds.
+///
+/// The restrictions are composed as term queries instead of query syntax, so
+/// kinds with special characters don't change the meaning of the query.
+#[derive(Debug, Clone)]
+pub struct QueryBuilder {
+    text: String,
+    r#type: Option<DocType>,
+    kinds: Vec<String>,
+}
+
+impl QueryBuilder {
+    pub fn new<S: Into<String>>(text: S) -> Self {
+        Self {
+            text: text.into(),
+            r#type: None,
+            kinds: Vec::new(),
         }
     }
+
+    pub fn with_type(mut self, r#type: DocType) -> Self {
+        self.r#type = Some(r#type);
+        self
+    }
+
+    /// Restricts items to any of the kinds, ignored for other types
+    pub fn with_kinds<I, S>(mut self, kinds: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.kinds = kinds.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn doc_type(&self) -> Option<&DocType> {
+        self.r#type.as_ref()
+    }
+
+    fn item_kinds(&self) -> &[String] {
+        match self.r#type {
+            Some(DocType::Item) => &self.kinds,
+            _ => &[],
+        }
+    }
+
+    /// Returns the parsed search text restricted to the type and kinds
+    fn build(&self, index: &Index, text: Box<dyn Query>) -> Result<Box<dyn Query>> {
+        let Some(r#type) = &self.r#type else {
+            return Ok(text);
+        };
+
+        let type_field = index.schema.get_field(IndexField::Type.name()).unwrap();
+        let mut clauses = vec![(
+            Occur::Must,
+            term_query(Term::from_field_text(type_field, &r#type.to_string())),
+        )];
+
+        let kinds = self.item_kinds();
+        if !kinds.is_empty() {
+            let kind_field = index.schema.get_field(IndexField::Kind.name()).unwrap();
+            let kinds = kinds
+                .iter()
+                .map(|kind| {
+                    // Kinds are tokenized like the indexed ones, all tokens must match
+                    let terms = index
+                        .analyze(kind_field, kind)?
+                        .into_iter()
+                        .map(|t| {
+                            (
+                                Occur::Must,
+                                term_query(Term::from_field_text(kind_field, &t)),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    let query: Box<dyn Query> = Box::new(BooleanQuery::new(terms));
+                    Ok((Occur::Should, query))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            clauses.push((Occur::Must, Box::new(BooleanQuery::new(kinds))));
+        }
+
+        clauses.push((Occur::Must, text));
+
+        Ok(Box::new(BooleanQuery::new(clauses)))
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct QueryOptions {
     pub limit: usize,
     pub conjunction: bool,
+    pub boosts: FieldBoosts,
+    pub filters: Vec<NumericFilter>,
+    /// Searches the query as plain terms if its syntax is invalid
+    pub lenient: bool,
+    /// Score boosts of documents by their kind
+    pub kind_boosts: Vec<(String, f32)>,
+    /// Score factors of documents by their type
+    pub type_boosts: Vec<(DocType, f32)>,
+    /// Matches the words of the name within the edit distance instead of by
+    /// ngrams
+    pub fuzzy: Option<u8>,
+    pub name_analyzer: NameAnalyzer,
+}
+
+/// How the query is matched against the name, ignored by fuzzy queries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NameAnalyzer {
+    /// Ngrams of the words, matches parts of words
+    #[default]
+    Ngram,
+    /// Whole words
+    Words,
+}
+
+impl NameAnalyzer {
+    fn index_field(&self) -> IndexField {
+        match self {
+            NameAnalyzer::Ngram => IndexField::Name,
+            NameAnalyzer::Words => IndexField::NameTerms,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumericField {
+    Penetration,
+    Damage,
+    /// Price normalized to roubles
+    Price,
+    /// Modification time in seconds since the Unix epoch
+    Modified,
+}
+
+impl NumericField {
+    fn index_field(&self) -> IndexField {
+        match self {
+            NumericField::Penetration => IndexField::Penetration,
+            NumericField::Damage => IndexField::Damage,
+            NumericField::Price => IndexField::Price,
+            NumericField::Modified => IndexField::Modified,
+        }
+    }
+}
+
+/// Range filter on a numeric field
+#[derive(Debug, Clone)]
+pub struct NumericFilter {
+    pub field: NumericField,
+    pub lower: Bound<f64>,
+    pub upper: Bound<f64>,
+}
+
+impl NumericFilter {
+    pub fn contains(&self, value: f64) -> bool {
+        let lower = match self.lower {
+            Bound::Included(l) => value >= l,
+            Bound::Excluded(l) => value > l,
+            Bound::Unbounded => true,
+        };
+        let upper = match self.upper {
+            Bound::Included(u) => value <= u,
+            Bound::Excluded(u) => value < u,
+            Bound::Unbounded => true,
+        };
+
+        lower && upper
+    }
+
+    fn to_query(&self) -> Box<dyn Query> {
+        let field = self.field.index_field().to_string();
+
+        if self.field == NumericField::Modified {
+            let date = |b: Bound<f64>| match b {
+                Bound::Included(v) => {
+                    Bound::Included(tantivy::DateTime::from_timestamp_secs(v.floor() as i64))
+                }
+                Bound::Excluded(v) => {
+                    Bound::Excluded(tantivy::DateTime::from_timestamp_secs(v.floor() as i64))
+                }
+                Bound::Unbounded => Bound::Unbounded,
+            };
+            return Box::new(RangeQuery::new_date_bounds(
+                field,
+                date(self.lower),
+                date(self.upper),
+            ));
+        }
+
+        Box::new(RangeQuery::new_f64_bounds(field, self.lower, self.upper))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldBoosts {
+    pub name: f32,
+    pub description: f32,
+    pub compatible: f32,
+}
+
+impl Default for FieldBoosts {
+    fn default() -> Self {
+        Self {
+            name: 2.0,
+            description: 1.0,
+            compatible: 0.5,
+        }
+    }
+}
+
+/// Gram lengths of the ngram tokenizer, fixed when the index is created
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NgramRange {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Default for NgramRange {
+    fn default() -> Self {
+        Self { min: 3, max: 4 }
+    }
+}
+
+/// Durations of the individual query phases
+#[derive(Debug, Clone, Default)]
+pub struct QueryTimings {
+    pub parse: Duration,
+    pub search: Duration,
+    pub fetch: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct QueryOutput {
+    pub docs: Vec<IndexDoc>,
+    pub timings: QueryTimings,
+}
+
+impl QueryOutput {
+    /// Merges the outputs of concurrently executed queries by score.
+    ///
+    /// The phase timings are the maximum of all outputs.
+    pub fn merge<I>(outputs: I, limit: usize) -> Self
+    where
+        I: IntoIterator<Item = QueryOutput>,
+    {
+        let mut docs = Vec::new();
+        let mut timings = QueryTimings::default();
+
+        for output in outputs {
+            docs.extend(output.docs);
+            timings.parse = timings.parse.max(output.timings.parse);
+            timings.search = timings.search.max(output.timings.search);
+            timings.fetch = timings.fetch.max(output.timings.fetch);
+        }
+
+        docs.sort_by(|a, b| b.score.total_cmp(&a.score));
+        docs.truncate(limit);
+
+        Self { docs, timings }
+    }
+}
+
+/// Tokens a query is analyzed into for a field
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldTokens {
+    field: &'static str,
+    tokens: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExplainedDoc {
+    #[serde(flatten)]
+    doc: IndexDoc,
+    explanation: Explanation,
+}
+
+/// Analysis, parsed query and explained results of a query
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Explained {
+    analysis: Vec<FieldTokens>,
+    query: String,
+    results: Vec<ExplainedDoc>,
+}
+
+/// Maximum number of document errors kept per batch
+const MAX_DOC_ERRORS: usize = 50;
+
+/// Document skipped while writing the index
+#[derive(Debug, Clone)]
+pub struct DocError {
+    pub id: String,
+    pub reason: String,
+}
+
+impl fmt::Display for DocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.id, self.reason)
+    }
+}
+
+/// Outcome of writing a batch of documents
+#[derive(Debug, Clone, Default)]
+pub struct BatchSummary {
+    pub indexed: usize,
+    pub skipped: usize,
+    /// Documents left as they are, because their content didn't change
+    pub unchanged: usize,
+    /// Errors of the first skipped documents
+    pub errors: Vec<DocError>,
+}
+
+impl BatchSummary {
+    fn skip(&mut self, id: &str, reason: &str) {
+        self.skipped += 1;
+
+        if self.errors.len() < MAX_DOC_ERRORS {
+            self.errors.push(DocError {
+                id: id.to_owned(),
+                reason: reason.to_owned(),
+            });
+        }
+    }
+}
+
+/// Returns the reason a document can't be indexed
+fn invalid_doc<'a, T>(doc: &'a T, seen: &mut HashSet<&'a str>) -> Option<&'static str>
+where
+    T: IntoIndexDocument,
+{
+    let id = doc.doc_id();
+    if id.is_empty() {
+        return Some("missing ID");
+    }
+    if doc.doc_names().iter().all(|n| n.trim().is_empty()) {
+        return Some("missing name");
+    }
+    if !seen.insert(id) {
+        return Some("duplicate ID");
+    }
+
+    None
+}
+
+/// Documents of the last commit
+#[derive(Debug, Clone, Default)]
+struct Committed {
+    fingerprints: Fingerprints,
+    /// Latest upstream modification time of the documents
+    modified: Option<DateTime<Utc>>,
+}
+
+/// Adds the valid documents to the writer and records the skipped ones.
+///
+/// Documents with the same fingerprint as the previously committed ones are
+/// left in the index, changed ones are replaced. Committed documents modified
+/// upstream before the modification time of `previous` aren't even compared.
+fn add_documents<'a, T>(
+    writer: &IndexWriter,
+    docs: &'a [T],
+    ctx: &DocContext,
+    previous: &Committed,
+    batch: &mut BatchSummary,
+    seen: &mut HashSet<&'a str>,
+    fingerprints: &mut Fingerprints,
+) -> Result<()>
+where
+    T: IntoIndexDocument,
+{
+    for doc in docs.iter() {
+        if let Some(reason) = invalid_doc(doc, seen) {
+            batch.skip(doc.doc_id(), reason);
+            continue;
+        }
+
+        let id = doc.doc_id();
+
+        if let (Some(entry), Some(modified), Some(last)) = (
+            previous.fingerprints.get(id),
+            doc.doc_modified(),
+            previous.modified,
+        ) {
+            // Timestamps have a resolution of seconds, so equal ones are compared
+            if modified < last {
+                batch.unchanged += 1;
+                fingerprints.insert(id.to_owned(), *entry);
+                continue;
+            }
+        }
+
+        let document = doc.to_document(ctx);
+        let entry = (
+            doc.doc_type(),
+            fingerprint(&document, ctx.field(IndexField::Modified)),
+        );
+
+        match previous.fingerprints.get(id) {
+            Some(p) if *p == entry => {
+                batch.unchanged += 1;
+            }
+            Some(_) => {
+                writer.delete_term(Term::from_field_text(ctx.field(IndexField::ID), id));
+                writer.add_document(document)?;
+                batch.indexed += 1;
+            }
+            None => {
+                writer.add_document(document)?;
+                batch.indexed += 1;
+            }
+        }
+
+        fingerprints.insert(id.to_owned(), entry);
+    }
+
+    Ok(())
+}
+
+/// Statistics of the commits made by an index writer
+#[derive(Debug, Clone, Default)]
+pub struct CommitStats {
+    pub commits: u64,
+    pub last_opstamp: u64,
+    pub last_documents: usize,
+    pub last_batch: BatchSummary,
+    /// Documents removed by the last commit
+    pub last_deleted: u64,
+    pub last_duration: Duration,
+    /// Searchable segments after the last commit
+    pub segments: usize,
+    /// Changes of the documents by the last commit
+    pub last_diff: GenerationDiff,
+}
+
+/// Result of merging the segments of an index
+#[derive(Debug, Clone, Default)]
+pub struct OptimizeStats {
+    pub segments_before: usize,
+    pub segments_after: usize,
+    pub duration: Duration,
 }
 
 #[derive(Clone)]
 pub struct Index {
     index: TantivyIndex,
     reader: IndexReader,
+    writer: Arc<Mutex<IndexWriter>>,
+    commit_stats: Arc<Mutex<CommitStats>>,
+    /// Incremented with every commit of the documents, starts at the opstamp
+    /// of the last commit of the index or the current time in milliseconds
+    generation: Arc<AtomicU64>,
+    vocabulary: Arc<RwLock<Vocabulary>>,
+    /// Kinds of the indexed items
+    kinds: Arc<RwLock<BTreeSet<String>>>,
+    /// Content hashes of the committed documents
+    committed: Arc<Mutex<Committed>>,
     schema: Schema,
     lang: Language,
+    ngram: NgramRange,
 }
 
 impl Index {
@@ -81,67 +654,342 @@ impl Index {
     }
 
     pub fn with_lang(lang: Language) -> Result<Self> {
-        let schema = IndexSchema::with_lang(lang).build();
+        Self::with_write_buffer(lang, WRITE_BUFFER)
+    }
+
+    /// Creates an index with the given writer memory budget, at least [`MIN_WRITE_BUFFER`].
+    ///
+    /// Segments are stored in an mmap-backed temporary directory, so the budget
+    /// bounds the memory used while building the index.
+    pub fn with_write_buffer(lang: Language, write_buffer: usize) -> Result<Self> {
+        Self::with_ngram_range(lang, write_buffer, NgramRange::default())
+    }
 
+    /// Creates an index with the given writer memory budget and ngram lengths
+    pub fn with_ngram_range(
+        lang: Language,
+        write_buffer: usize,
+        ngram: NgramRange,
+    ) -> Result<Self> {
+        let schema = IndexSchema::with_lang(lang).build();
         let index = TantivyIndex::create_from_tempdir(schema.clone())?;
+
+        Self::build(index, schema, lang, write_buffer, ngram)
+    }
+
+    /// Opens the index stored in the directory, or creates it if there is none.
+    ///
+    /// The existing segments are searchable right away. An index with a
+    /// different schema, e.g. of another language or version, is replaced by an
+    /// empty one. Only the files of the index are removed, other files in the
+    /// directory are kept.
+    pub fn open_or_create<P: AsRef<Path>>(
+        path: P,
+        lang: Language,
+        write_buffer: usize,
+        ngram: NgramRange,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let schema = IndexSchema::with_lang(lang).build();
+
+        fs::create_dir_all(path).map_err(TantivyError::from)?;
+        let dir = MmapDirectory::open(path).map_err(TantivyError::from)?;
+        let index = match TantivyIndex::open_or_create(dir, schema.clone()) {
+            Ok(i) => i,
+            Err(TantivyError::SchemaError(e)) => {
+                if !path.join(META_FILE).is_file() {
+                    return Err(TantivyError::SchemaError(e).into());
+                }
+                remove_index_files(path)?;
+                TantivyIndex::create_in_dir(path, schema.clone())?
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Self::build(index, schema, lang, write_buffer, ngram)
+    }
+
+    /// Creates an empty index with the settings of this one, kept in memory.
+    ///
+    /// Used to try out writes without touching the served documents.
+    pub fn empty_in_memory(&self) -> Result<Self> {
+        let index = TantivyIndex::create_in_ram(self.schema.clone());
+
+        Self::build(
+            index,
+            self.schema.clone(),
+            self.lang,
+            MIN_WRITE_BUFFER,
+            self.ngram,
+        )
+    }
+
+    /// Creates an empty index with the settings of this one, stored in an
+    /// mmap-backed temporary directory.
+    ///
+    /// Like [`Index::empty_in_memory`], but for when memory is short.
+    pub fn empty_on_disk(&self) -> Result<Self> {
+        let index = TantivyIndex::create_from_tempdir(self.schema.clone())?;
+
+        Self::build(
+            index,
+            self.schema.clone(),
+            self.lang,
+            MIN_WRITE_BUFFER,
+            self.ngram,
+        )
+    }
+
+    fn build(
+        index: TantivyIndex,
+        schema: Schema,
+        lang: Language,
+        write_buffer: usize,
+        ngram_range: NgramRange,
+    ) -> Result<Self> {
+        // Readers are reloaded explicitly after a commit, so queries never see the
+        // intermediate state of a rebuild
         let reader = index
             .reader_builder()
-            .reload_policy(ReloadPolicy::OnCommit)
+            .reload_policy(ReloadPolicy::Manual)
             .try_into()?;
 
         let custom = Tokenizer::Custom(lang);
         custom.register_for(&index)?;
 
-        let ngram = Tokenizer::Ngram(NgramOptions::default().set_language(lang));
+        let ngram = Tokenizer::Ngram(
+            NgramOptions::new(ngram_range.min, ngram_range.max, false).set_language(lang),
+        );
         ngram.register_for(&index)?;
 
+        let edge_ngram = Tokenizer::Ngram(NgramOptions::edge().set_language(lang));
+        edge_ngram.register_for(&index)?;
+
+        Tokenizer::Keyword.register_for(&index)?;
+
+        let writer = index.writer(write_buffer.max(MIN_WRITE_BUFFER))?;
+
+        // Generations continue from the last commit of a stored index, so they
+        // aren't repeated after a restart. Temporary and replaced indexes start
+        // over at opstamp zero, for them the clock keeps generations increasing.
+        let opstamp = index.load_metas()?.opstamp;
+        let generation = opstamp.max(Utc::now().timestamp_millis().max(0) as u64);
+
         Ok(Self {
             index,
             reader,
+            writer: Arc::new(Mutex::new(writer)),
+            commit_stats: Arc::default(),
+            generation: Arc::new(AtomicU64::new(generation)),
+            vocabulary: Arc::default(),
+            kinds: Arc::default(),
+            committed: Arc::default(),
             schema,
             lang,
+            ngram: ngram_range,
         })
     }
 
-    pub fn write_index(&self, data: Vec<Item>) -> Result<()> {
-        let mut writer = self.index.writer(WRITE_BUFFER)?;
-        let schema = &self.schema;
-
-        // TODO: Make it more intelligent
-        writer.delete_all_documents()?;
-
-        for item in data.into_iter() {
-            let mut doc = Document::default();
-            doc.add_text(schema.get_field(IndexField::ID.name()).unwrap(), &item.id);
-            doc.add_text(
-                schema.get_field(IndexField::Name.name()).unwrap(),
-                item.short_name,
-            );
-            doc.add_text(
-                schema.get_field(IndexField::Name.name()).unwrap(),
-                item.name,
-            );
-            doc.add_text(
-                schema
-                    .get_field(IndexField::Description(self.lang).name())
-                    .unwrap(),
-                item.description,
-            );
-            doc.add_text(
-                schema.get_field(IndexField::Kind.name()).unwrap(),
-                item.kind,
-            );
-            doc.add_text(
-                schema.get_field(IndexField::Type.name()).unwrap(),
-                DocType::Item.to_string(),
-            );
-
-            writer.add_document(doc)?;
-        }
-
-        writer.commit()?;
+    pub fn language(&self) -> Language {
+        self.lang
+    }
 
-        Ok(())
+    pub fn commit_stats(&self) -> CommitStats {
+        self.commit_stats.lock().unwrap().clone()
+    }
+
+    /// Returns the generation of the committed documents, which increases
+    /// with every commit and across restarts
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of searchable documents
+    pub fn num_docs(&self) -> u64 {
+        self.reader.searcher().num_docs()
+    }
+
+    /// Returns the number of searchable documents of each type
+    pub fn num_docs_by_type(&self) -> Result<Vec<(DocType, u64)>> {
+        let searcher = self.reader.searcher();
+        let field = self.schema.get_field(IndexField::Type.name()).unwrap();
+
+        DocType::ALL
+            .into_iter()
+            .map(|t| {
+                let term = Term::from_field_text(field, &t.to_string());
+                let query = TermQuery::new(term, IndexRecordOption::Basic);
+                let count = searcher.search(&query, &Count)?;
+                Ok((t, count as u64))
+            })
+            .collect()
+    }
+
+    /// Writes the items and presets, only changed documents are replaced
+    pub fn write_index(&self, data: &[Item], supplements: &Supplements) -> Result<CommitStats> {
+        self.write(data, supplements, false)
+    }
+
+    /// Writes the items and presets like [`Index::write_index`], but items
+    /// modified upstream before the last commit are kept without comparing
+    /// their content.
+    ///
+    /// Changes of the supplements of those items are only applied by the next
+    /// full write.
+    pub fn upsert_items(&self, data: &[Item], supplements: &Supplements) -> Result<CommitStats> {
+        self.write(data, supplements, true)
+    }
+
+    fn write(&self, data: &[Item], supplements: &Supplements, upsert: bool) -> Result<CommitStats> {
+        let mut writer = self.writer.lock().unwrap();
+        let start = Instant::now();
+
+        let (batch, fingerprints, deleted) =
+            match self.add_items(&writer, data, supplements, upsert) {
+                Ok(v) => v,
+                Err(e) => {
+                    writer.rollback()?;
+                    return Err(e);
+                }
+            };
+
+        let opstamp = match writer.commit() {
+            Ok(o) => o,
+            Err(e) => {
+                writer.rollback()?;
+                return Err(e.into());
+            }
+        };
+
+        self.reader.reload()?;
+
+        let mut vocabulary = Vocabulary::default();
+        for item in data.iter() {
+            vocabulary.add(&item.name);
+            vocabulary.add(&item.short_name);
+        }
+        for preset in supplements.presets.iter() {
+            vocabulary.add(&preset.name);
+        }
+        *self.vocabulary.write().unwrap() = vocabulary;
+        *self.kinds.write().unwrap() = data
+            .iter()
+            .map(|i| i.kind.to_string().to_lowercase())
+            .collect();
+
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+
+        let mut committed = self.committed.lock().unwrap();
+        let diff = GenerationDiff::between(&committed.fingerprints, &fingerprints, generation);
+        committed.fingerprints = fingerprints;
+        committed.modified = data.iter().map(|i| i.modified).max();
+
+        let mut stats = self.commit_stats.lock().unwrap();
+        stats.commits += 1;
+        stats.last_opstamp = opstamp;
+        stats.last_documents = batch.indexed;
+        stats.last_batch = batch;
+        stats.last_deleted = deleted;
+        stats.last_duration = start.elapsed();
+        stats.segments = self.index.searchable_segment_ids()?.len();
+        stats.last_diff = diff;
+
+        Ok(stats.clone())
+    }
+
+    /// Merges the smallest segments until at most `target_segments` are left.
+    ///
+    /// The merge blocks index writes until it's finished.
+    pub fn optimize(&self, target_segments: usize) -> Result<OptimizeStats> {
+        let mut writer = self.writer.lock().unwrap();
+        let start = Instant::now();
+
+        let mut metas = self.index.searchable_segment_metas()?;
+        let segments_before = metas.len();
+        let target = target_segments.max(1);
+
+        if segments_before > target {
+            metas.sort_by_key(|m| m.num_docs());
+            let ids = metas
+                .iter()
+                .take(segments_before - target + 1)
+                .map(|m| m.id())
+                .collect::<Vec<_>>();
+
+            writer.merge(&ids).wait()?;
+            let _ = writer.garbage_collect_files().wait();
+            self.reader.reload()?;
+        }
+
+        let segments_after = self.index.searchable_segment_ids()?.len();
+        self.commit_stats.lock().unwrap().segments = segments_after;
+
+        Ok(OptimizeStats {
+            segments_before,
+            segments_after,
+            duration: start.elapsed(),
+        })
+    }
+
+    fn add_items(
+        &self,
+        writer: &IndexWriter,
+        data: &[Item],
+        supplements: &Supplements,
+        upsert: bool,
+    ) -> Result<(BatchSummary, Fingerprints, u64)> {
+        let ctx = DocContext::new(&self.schema, self.lang, data, supplements);
+        let mut batch = BatchSummary::default();
+        let mut seen = HashSet::with_capacity(data.len() + supplements.presets.len());
+        let mut fingerprints = Fingerprints::with_capacity(seen.capacity());
+
+        let mut previous = self.committed.lock().unwrap().clone();
+        if !upsert {
+            previous.modified = None;
+        }
+        let mut deleted = 0;
+
+        // Without fingerprints the indexed documents are unknown
+        if previous.fingerprints.is_empty() {
+            deleted = self.reader.searcher().num_docs();
+            writer.delete_all_documents()?;
+        }
+
+        add_documents(
+            writer,
+            data,
+            &ctx,
+            &previous,
+            &mut batch,
+            &mut seen,
+            &mut fingerprints,
+        )?;
+        add_documents(
+            writer,
+            &supplements.presets,
+            &ctx,
+            &previous,
+            &mut batch,
+            &mut seen,
+            &mut fingerprints,
+        )?;
+
+        let id_field = ctx.field(IndexField::ID);
+        for id in previous.fingerprints.keys() {
+            if !fingerprints.contains_key(id) {
+                writer.delete_term(Term::from_field_text(id_field, id));
+                deleted += 1;
+            }
+        }
+
+        let replaced = fingerprints
+            .keys()
+            .filter(|id| previous.fingerprints.contains_key(*id))
+            .count()
+            - batch.unchanged;
+        deleted += replaced as u64;
+
+        Ok((batch, fingerprints, deleted))
     }
 
     pub fn check_health(&self) -> Result<()> {
@@ -156,110 +1004,694 @@ impl Index {
         Ok(())
     }
 
-    // Replace with query builder?
-    pub fn search_by_type(
+    /// Runs the composed query, the restrictions are kept if the search text
+    /// falls back to plain terms
+    pub fn search(&self, builder: &QueryBuilder, opts: QueryOptions) -> Result<QueryOutput> {
+        let kinds = builder.item_kinds();
+        if kinds.len() > MAX_KIND_CLAUSES {
+            return Err(Error::QueryTooComplex(format!(
+                "more than {} kinds",
+                MAX_KIND_CLAUSES
+            )));
+        }
+        self.check_kinds(kinds)?;
+
+        let lenient = opts.lenient;
+        let type_boost = builder.doc_type().and_then(|t| {
+            opts.type_boosts
+                .iter()
+                .find(|(boosted, _)| boosted == t)
+                .map(|(_, b)| *b)
+        });
+
+        let result = match self.query_built(builder, opts.clone()) {
+            Err(Error::BadQuery(e)) if lenient => {
+                let terms = plain_terms(builder.text());
+                if terms.is_empty() {
+                    return Err(Error::BadQuery(e));
+                }
+
+                let mut builder = builder.clone();
+                builder.text = terms;
+                self.query_built(&builder, opts)
+            }
+            result => result,
+        };
+
+        // Scores are scaled instead of adding a clause, so the type boost only
+        // changes the order of merged results of several types
+        match (result, type_boost) {
+            (Ok(mut output), Some(boost)) => {
+                output.docs.iter_mut().for_each(|d| d.score *= boost);
+                Ok(output)
+            }
+            (result, _) => result,
+        }
+    }
+
+    pub fn query_top(&self, query: &str, opts: QueryOptions) -> Result<QueryOutput> {
+        self.query_built(&QueryBuilder::new(query), opts)
+    }
+
+    fn query_built(&self, builder: &QueryBuilder, opts: QueryOptions) -> Result<QueryOutput> {
+        let mut timings = QueryTimings::default();
+
+        let collector = TopDocs::with_limit(opts.limit);
+
+        let start = Instant::now();
+        let query = self.parse_query(builder, &opts)?;
+        timings.parse = start.elapsed();
+
+        let start = Instant::now();
+        let searcher = self.reader.searcher();
+        let docs = searcher.search(&query, &collector)?;
+        timings.search = start.elapsed();
+
+        if docs.is_empty() {
+            return Ok(QueryOutput {
+                docs: Vec::new(),
+                timings,
+            });
+        }
+
+        let start = Instant::now();
+        let matchers = self.field_matchers(builder.text(), &opts, &searcher);
+        let mut result: Vec<IndexDoc> = Vec::with_capacity(docs.len());
+        for (score, addr) in docs.into_iter() {
+            let mut doc = self.fetch_doc(&searcher, addr, score)?;
+            doc.matched = matched_fields(&matchers, &searcher, addr)?;
+            result.push(doc);
+        }
+
+        timings.fetch = start.elapsed();
+
+        Ok(QueryOutput {
+            docs: result,
+            timings,
+        })
+    }
+
+    /// Runs the query and returns the analyzed tokens, the parsed query and
+    /// the score explanation of each result
+    pub fn explain(
         &self,
         query: &str,
-        r#type: DocType,
-        kind: Option<&[&str]>,
+        r#type: Option<DocType>,
         opts: QueryOptions,
-    ) -> Result<Vec<IndexDoc>> {
-        let mut q = format!("type:{}", r#type);
-
-        if r#type == DocType::Item {
-            if let Some(k) = kind {
-                let len = k.len();
-                let k = k
-                    .iter()
-                    .enumerate()
-                    .map(|(i, v)| {
-                        if i == len - 1 {
-                            format!("kind:{}", v)
-                        } else {
-                            format!("kind:{} OR ", v)
-                        }
-                    })
-                    .collect::<Vec<_>>()
-                    .concat();
-                q = format!("{} AND ({})", q, k);
-            }
+    ) -> Result<Explained> {
+        let name_field = self.schema.get_field(IndexField::Name.name()).unwrap();
+        let desc_field = self
+            .schema
+            .get_field(IndexField::Description(self.lang).name())
+            .unwrap();
+
+        let analysis = vec![
+            FieldTokens {
+                field: IndexField::Name.name(),
+                tokens: self.analyze(name_field, query)?,
+            },
+            FieldTokens {
+                field: IndexField::Description(self.lang).name(),
+                tokens: self.analyze(desc_field, query)?,
+            },
+        ];
+
+        let mut builder = QueryBuilder::new(query);
+        if let Some(t) = r#type {
+            builder = builder.with_type(t);
         }
+        let parsed = self.parse_query(&builder, &opts)?;
+
+        let searcher = self.reader.searcher();
+        let docs = searcher.search(&parsed, &TopDocs::with_limit(opts.limit))?;
 
-        self.query_top(&format!("{} AND ({})", q, query), opts)
+        let results = docs
+            .into_iter()
+            .map(|(score, addr)| {
+                Ok(ExplainedDoc {
+                    explanation: parsed.explain(&searcher, addr)?,
+                    doc: self.fetch_doc(&searcher, addr, score)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Explained {
+            analysis,
+            query: format!("{:#?}", parsed),
+            results,
+        })
     }
 
-    pub fn query_top(&self, query: &str, opts: QueryOptions) -> Result<Vec<IndexDoc>> {
-        let id_field = self.schema.get_field(IndexField::ID.name()).unwrap();
-        let name_field = self.schema.get_field(IndexField::Name.name()).unwrap();
+    fn analyze(&self, field: Field, text: &str) -> Result<Vec<String>> {
+        let mut analyzer = self.index.tokenizer_for_field(field)?;
+        let mut stream = analyzer.token_stream(text);
+
+        let mut tokens = Vec::new();
+        stream.process(&mut |t| tokens.push(t.text.clone()));
+
+        Ok(tokens)
+    }
+
+    /// Returns the query restricted to each field a match is reported for
+    fn field_matchers(
+        &self,
+        query: &str,
+        opts: &QueryOptions,
+        searcher: &Searcher,
+    ) -> Vec<(&'static str, Box<dyn Weight>)> {
+        let fields = [
+            IndexField::Name,
+            IndexField::ShortName,
+            IndexField::Description(self.lang),
+            IndexField::Kind,
+            IndexField::Compatible,
+        ];
+
+        fields
+            .into_iter()
+            .filter_map(|f| {
+                let query = match (&f, opts.fuzzy) {
+                    (IndexField::Name, Some(distance)) => {
+                        self.fuzzy_name_query(query, distance, opts).ok()?
+                    }
+                    (IndexField::Name, None) => {
+                        let field = opts.name_analyzer.index_field();
+                        let field = self.schema.get_field(field.name()).ok()?;
+                        let mut parser = QueryParser::for_index(&self.index, vec![field]);
+                        if opts.conjunction {
+                            parser.set_conjunction_by_default();
+                        }
+
+                        parser.parse_query(query).ok()?
+                    }
+                    _ => {
+                        let field = self.schema.get_field(f.name()).ok()?;
+                        let mut parser = QueryParser::for_index(&self.index, vec![field]);
+                        if opts.conjunction {
+                            parser.set_conjunction_by_default();
+                        }
+
+                        parser.parse_query(query).ok()?
+                    }
+                };
+
+                let weight = query
+                    .weight(EnableScoring::disabled_from_searcher(searcher))
+                    .ok()?;
+
+                Some((f.name(), weight))
+            })
+            .collect()
+    }
+
+    fn parse_query(&self, builder: &QueryBuilder, opts: &QueryOptions) -> Result<Box<dyn Query>> {
+        let name_field = self
+            .schema
+            .get_field(opts.name_analyzer.index_field().name())
+            .unwrap();
+        let short_name_field = self.schema.get_field(IndexField::ShortName.name()).unwrap();
         let desc_field = self
             .schema
             .get_field(IndexField::Description(self.lang).name())
             .unwrap();
-        let kind_field = self.schema.get_field(IndexField::Kind.name()).unwrap();
-        let type_field = self.schema.get_field(IndexField::Type.name()).unwrap();
+        let compatible_field = self
+            .schema
+            .get_field(IndexField::Compatible.name())
+            .unwrap();
 
-        let collector = TopDocs::with_limit(opts.limit);
+        // Fuzzy matching replaces the ngrams of the name
+        let mut fields = vec![short_name_field, desc_field, compatible_field];
+        if opts.fuzzy.is_none() {
+            fields.push(name_field);
+        }
 
-        let mut parser = QueryParser::for_index(&self.index, vec![name_field, desc_field]);
-        parser.set_field_boost(name_field, 2.0);
+        let mut parser = QueryParser::for_index(&self.index, fields);
+        parser.set_field_boost(name_field, opts.boosts.name);
+        parser.set_field_boost(short_name_field, opts.boosts.name);
+        parser.set_field_boost(desc_field, opts.boosts.description);
+        parser.set_field_boost(compatible_field, opts.boosts.compatible);
 
         if opts.conjunction {
             parser.set_conjunction_by_default();
         }
 
-        let query = parser.parse_query(query)?;
+        let wildcards = builder
+            .text()
+            .chars()
+            .filter(|c| matches!(c, '*' | '?' | '~'))
+            .count();
+        if wildcards > MAX_WILDCARDS {
+            return Err(Error::QueryTooComplex(format!(
+                "more than {} wildcard or fuzzy operators",
+                MAX_WILDCARDS
+            )));
+        }
+
+        let query = parser.parse_query(builder.text())?;
+
+        let mut terms = 0;
+        query.query_terms(&mut |_, _| terms += 1);
+        if terms > MAX_QUERY_TERMS {
+            return Err(Error::QueryTooComplex(format!(
+                "more than {} terms",
+                MAX_QUERY_TERMS
+            )));
+        }
+
+        let query: Box<dyn Query> = match opts.fuzzy {
+            Some(distance) => {
+                let fuzzy = self.fuzzy_name_query(builder.text(), distance, opts)?;
+                Box::new(BooleanQuery::new(vec![
+                    (Occur::Should, query),
+                    (Occur::Should, fuzzy),
+                ]))
+            }
+            None => query,
+        };
+
+        let mut query = builder.build(self, query)?;
+
+        if !opts.filters.is_empty() {
+            let mut clauses = vec![(Occur::Must, query)];
+            clauses.extend(opts.filters.iter().map(|f| (Occur::Must, f.to_query())));
+            query = Box::new(BooleanQuery::new(clauses));
+        }
+
+        if !opts.kind_boosts.is_empty() {
+            let kind_field = self.schema.get_field(IndexField::Kind.name()).unwrap();
+
+            let mut clauses = vec![(Occur::Must, query)];
+            clauses.extend(opts.kind_boosts.iter().map(|(kind, boost)| {
+                let term = Term::from_field_text(kind_field, &kind.to_lowercase());
+                let query: Box<dyn Query> = Box::new(BoostQuery::new(
+                    Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+                    *boost,
+                ));
+                (Occur::Should, query)
+            }));
+            query = Box::new(BooleanQuery::new(clauses));
+        }
+
+        Ok(query)
+    }
+
+    /// Returns a query matching the words of the text in the names within the
+    /// edit distance
+    fn fuzzy_name_query(
+        &self,
+        text: &str,
+        distance: u8,
+        opts: &QueryOptions,
+    ) -> Result<Box<dyn Query>> {
+        if distance > MAX_FUZZY_DISTANCE {
+            return Err(Error::QueryTooComplex(format!(
+                "fuzzy distance above {}",
+                MAX_FUZZY_DISTANCE
+            )));
+        }
+
+        let field = self.schema.get_field(IndexField::NameTerms.name()).unwrap();
+        let words = self.analyze(field, &plain_terms(text))?;
+        if words.len() > MAX_FUZZY_TERMS {
+            return Err(Error::QueryTooComplex(format!(
+                "more than {} fuzzy terms",
+                MAX_FUZZY_TERMS
+            )));
+        }
+
+        let occur = if opts.conjunction {
+            Occur::Must
+        } else {
+            Occur::Should
+        };
+        let clauses = words
+            .into_iter()
+            .map(|w| {
+                let term = Term::from_field_text(field, &w);
+                let query: Box<dyn Query> = Box::new(FuzzyTermQuery::new(term, distance, true));
+                (occur, query)
+            })
+            .collect();
+
+        Ok(Box::new(BoostQuery::new(
+            Box::new(BooleanQuery::new(clauses)),
+            opts.boosts.name,
+        )))
+    }
+
+    /// Returns documents with a name starting with the prefix
+    pub fn complete(&self, prefix: &str, limit: usize) -> Result<Vec<IndexDoc>> {
+        let field = self
+            .schema
+            .get_field(IndexField::NamePrefix.name())
+            .unwrap();
+
+        // Prefixes are only indexed up to the maximum ngram length
+        let prefix = prefix
+            .trim()
+            .to_lowercase()
+            .chars()
+            .take(MAX_PREFIX_LEN)
+            .collect::<String>();
+        let term = Term::from_field_text(field, &prefix);
+        let query = TermQuery::new(term, IndexRecordOption::WithFreqs);
 
         let searcher = self.reader.searcher();
-        let docs = searcher.search(&query, &collector)?;
+        let docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
 
-        if docs.is_empty() {
-            return Ok(Vec::new());
+        docs.into_iter()
+            .map(|(score, addr)| self.fetch_doc(&searcher, addr, score))
+            .collect()
+    }
+
+    /// Returns the kinds of the indexed items
+    pub fn kinds(&self) -> Vec<String> {
+        self.kinds.read().unwrap().iter().cloned().collect()
+    }
+
+    /// Fails with suggestions of the closest known kinds if a kind isn't
+    /// indexed. All kinds are accepted as long as the index is empty.
+    fn check_kinds(&self, kinds: &[String]) -> Result<()> {
+        let known = self.kinds.read().unwrap();
+        if known.is_empty() {
+            return Ok(());
         }
 
-        let mut result: Vec<IndexDoc> = Vec::with_capacity(docs.len());
-        for (_, addr) in docs.into_iter() {
-            let doc = searcher.doc(addr)?;
-            let mut names = doc.get_all(name_field);
-            let mut item = IndexDoc {
-                id: doc
-                    .get_first(id_field)
-                    .unwrap()
-                    .as_text()
-                    .unwrap()
-                    .to_string(),
-                short_name: None,
-                name: String::new(),
-                description: doc
-                    .get_first(desc_field)
-                    .unwrap()
-                    .as_text()
-                    .unwrap_or_default()
-                    .to_string(),
-                kind: None,
-                r#type: DocType::from_str(
-                    doc.get_first(type_field)
-                        .unwrap()
-                        .as_text()
-                        .unwrap_or_default(),
+        match kinds.iter().find(|k| !known.contains(&k.to_lowercase())) {
+            Some(kind) => Err(Error::UnknownKind {
+                kind: kind.to_string(),
+                suggestions: closest_candidates(
+                    kind,
+                    known.iter().map(String::as_str),
+                    KIND_SUGGESTIONS,
                 )
-                .unwrap(),
-            };
+                .into_iter()
+                .map(str::to_owned)
+                .collect(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the query with unknown words replaced by their closest indexed word,
+    /// or `None` if there is nothing to correct
+    pub fn suggest(&self, query: &str) -> Option<String> {
+        self.vocabulary.read().unwrap().correct(query)
+    }
+
+    /// Returns documents with terms matching the pattern.
+    ///
+    /// Fails if the pattern matches more than `max_terms` terms, counted in
+    /// every segment.
+    pub fn search_pattern(
+        &self,
+        pattern: &PatternQuery,
+        limit: usize,
+        max_terms: usize,
+    ) -> Result<QueryOutput> {
+        let mut timings = QueryTimings::default();
+        let field = self
+            .schema
+            .get_field(pattern.field.index_field().name())
+            .unwrap();
 
-            if item.r#type == DocType::Item {
-                item.short_name = Some(names.next().unwrap().as_text().unwrap().to_string());
+        let start = Instant::now();
+        let searcher = self.reader.searcher();
+
+        // The automaton only visits the terms matching the pattern, each one
+        // found in a segment counts against the limit
+        let mut terms = BTreeSet::new();
+        let mut evaluated = 0;
+        for segment in searcher.segment_readers() {
+            let inverted_index = segment.inverted_index(field)?;
+            let mut stream = inverted_index
+                .terms()
+                .search(pattern.regex.as_ref())
+                .into_stream()?;
+            while stream.advance() {
+                evaluated += 1;
+                if evaluated > max_terms {
+                    return Err(Error::QueryTooComplex(format!(
+                        "pattern matches more than {} terms",
+                        max_terms
+                    )));
+                }
+                if let Ok(term) = std::str::from_utf8(stream.key()) {
+                    terms.insert(term.to_owned());
+                }
             }
+        }
 
-            item.name.push_str(names.next().unwrap().as_text().unwrap());
+        let clauses = terms
+            .iter()
+            .map(|t| {
+                let term = Term::from_field_text(field, t);
+                let query: Box<dyn Query> =
+                    Box::new(TermQuery::new(term, IndexRecordOption::Basic));
+                (Occur::Should, query)
+            })
+            .collect::<Vec<_>>();
+        timings.parse = start.elapsed();
 
-            item.kind = doc
-                .get_first(kind_field)
-                .unwrap()
-                .as_text()
-                .map(|s| s.to_string());
+        if clauses.is_empty() {
+            return Ok(QueryOutput {
+                docs: Vec::new(),
+                timings,
+            });
+        }
 
-            result.push(item);
+        let start = Instant::now();
+        let query = BooleanQuery::new(clauses);
+        let docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
+        timings.search = start.elapsed();
+
+        let start = Instant::now();
+        let docs = docs
+            .into_iter()
+            .map(|(score, addr)| self.fetch_doc(&searcher, addr, score))
+            .collect::<Result<Vec<_>>>()?;
+        timings.fetch = start.elapsed();
+
+        Ok(QueryOutput { docs, timings })
+    }
+
+    /// Returns documents sharing a location or the kind with the given document
+    pub fn related(&self, id: &str, limit: usize) -> Result<Vec<IndexDoc>> {
+        let id_field = self.schema.get_field(IndexField::ID.name()).unwrap();
+        let kind_field = self.schema.get_field(IndexField::Kind.name()).unwrap();
+        let location_field = self.schema.get_field(IndexField::Locations.name()).unwrap();
+
+        let searcher = self.reader.searcher();
+
+        let id_term = Term::from_field_text(id_field, id);
+        let id_query = TermQuery::new(id_term.clone(), IndexRecordOption::Basic);
+        let (_, addr) = searcher
+            .search(&id_query, &TopDocs::with_limit(1))?
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::DocumentNotFound(id.to_string()))?;
+        let source = self.fetch_doc(&searcher, addr, 0.0)?;
+
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = source
+            .locations
+            .iter()
+            .map(|l| {
+                let term = Term::from_field_text(location_field, &l.to_lowercase());
+                let query: Box<dyn Query> =
+                    Box::new(TermQuery::new(term, IndexRecordOption::Basic));
+                (Occur::Should, query)
+            })
+            .collect();
+
+        if let Some(kind) = &source.kind {
+            let term = Term::from_field_text(kind_field, &kind.to_lowercase());
+            clauses.push((
+                Occur::Should,
+                Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+            ));
+        }
+
+        if clauses.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        clauses.push((
+            Occur::MustNot,
+            Box::new(TermQuery::new(id_term, IndexRecordOption::Basic)),
+        ));
+
+        let query = BooleanQuery::new(clauses);
+        let docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
+
+        docs.into_iter()
+            .map(|(score, addr)| self.fetch_doc(&searcher, addr, score))
+            .collect()
+    }
+
+    fn fetch_doc(&self, searcher: &Searcher, addr: DocAddress, score: f32) -> Result<IndexDoc> {
+        let id_field = self.schema.get_field(IndexField::ID.name()).unwrap();
+        let name_field = self.schema.get_field(IndexField::Name.name()).unwrap();
+        let short_name_field = self.schema.get_field(IndexField::ShortName.name()).unwrap();
+        let desc_field = self
+            .schema
+            .get_field(IndexField::Description(self.lang).name())
+            .unwrap();
+        let kind_field = self.schema.get_field(IndexField::Kind.name()).unwrap();
+        let type_field = self.schema.get_field(IndexField::Type.name()).unwrap();
+        let location_field = self.schema.get_field(IndexField::Locations.name()).unwrap();
+        let parent_field = self.schema.get_field(IndexField::Parent.name()).unwrap();
+        let penetration_field = self
+            .schema
+            .get_field(IndexField::Penetration.name())
+            .unwrap();
+        let damage_field = self.schema.get_field(IndexField::Damage.name()).unwrap();
+        let price_field = self.schema.get_field(IndexField::Price.name()).unwrap();
+        let price_original_field = self
+            .schema
+            .get_field(IndexField::PriceOriginal.name())
+            .unwrap();
+        let currency_field = self.schema.get_field(IndexField::Currency.name()).unwrap();
+        let modified_field = self.schema.get_field(IndexField::Modified.name()).unwrap();
+
+        let doc = searcher.doc(addr)?;
+
+        // The ID and type are stored with every document, the index is corrupt
+        // if one is missing
+        let required = |field: Field, name: &str| {
+            doc.get_first(field)
+                .and_then(|v| v.as_text())
+                .ok_or_else(|| {
+                    Error::UnhealthyIndex(format!("stored document without {} field", name))
+                })
+        };
+        let id = required(id_field, IndexField::ID.name())?;
+        let doc_type = required(type_field, IndexField::Type.name())?;
+        let doc_type = DocType::from_str(doc_type).map_err(|_| {
+            Error::UnhealthyIndex(format!(
+                "stored document {} has unknown type \"{}\"",
+                id, doc_type
+            ))
+        })?;
+
+        let mut item = IndexDoc {
+            id: id.to_string(),
+            short_name: doc
+                .get_first(short_name_field)
+                .and_then(|v| v.as_text())
+                .map(|v| v.to_string()),
+            name: doc
+                .get_first(name_field)
+                .and_then(|v| v.as_text())
+                .unwrap_or_default()
+                .to_string(),
+            description: doc
+                .get_first(desc_field)
+                .and_then(|v| v.as_text())
+                .unwrap_or_default()
+                .to_string(),
+            kind: None,
+            r#type: doc_type,
+            locations: doc
+                .get_all(location_field)
+                .filter_map(|v| v.as_text())
+                .map(|v| v.to_string())
+                .collect(),
+            parent: doc
+                .get_first(parent_field)
+                .and_then(|v| v.as_text())
+                .map(|v| v.to_string()),
+            penetration: doc.get_first(penetration_field).and_then(|v| v.as_f64()),
+            damage: doc.get_first(damage_field).and_then(|v| v.as_f64()),
+            price: doc
+                .get_first(price_original_field)
+                .and_then(|v| v.as_f64())
+                .map(|amount| DocPrice {
+                    amount,
+                    currency: doc
+                        .get_first(currency_field)
+                        .and_then(|v| v.as_text())
+                        .unwrap_or_default()
+                        .to_string(),
+                    normalized: doc.get_first(price_field).and_then(|v| v.as_f64()),
+                }),
+            modified: doc
+                .get_first(modified_field)
+                .and_then(|v| v.as_date())
+                .and_then(|d| Utc.timestamp_opt(d.into_timestamp_secs(), 0).single()),
+            matched: Vec::new(),
+            score,
+        };
+
+        item.kind = doc
+            .get_first(kind_field)
+            .and_then(|v| v.as_text())
+            .map(|s| s.to_string());
+
+        Ok(item)
+    }
+}
+
+/// Returns the names of the fields whose matcher matches the document
+fn matched_fields(
+    matchers: &[(&'static str, Box<dyn Weight>)],
+    searcher: &Searcher,
+    addr: DocAddress,
+) -> Result<Vec<&'static str>> {
+    let reader = searcher.segment_reader(addr.segment_ord);
+
+    let mut matched = Vec::new();
+    for (name, weight) in matchers.iter() {
+        let mut scorer = weight.scorer(reader, 1.0)?;
+        if scorer.seek(addr.doc_id) == addr.doc_id {
+            matched.push(*name);
         }
+    }
+
+    Ok(matched)
+}
+
+/// Removes the query syntax, so the query only consists of plain terms
+fn plain_terms(query: &str) -> String {
+    const SYNTAX: &[char] = &[
+        '+', '-', '^', '`', ':', '{', '}', '"', '[', ']', '(', ')', '<', '>', '~', '!', '\\', '*',
+        '\'', '?',
+    ];
+
+    query
+        .split(|c: char| c.is_whitespace() || SYNTAX.contains(&c))
+        .filter(|t| !t.is_empty())
+        .map(|t| match t {
+            "AND" | "OR" | "NOT" | "IN" => t.to_lowercase(),
+            _ => t.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Removes the meta and segment files of the index in the directory
+fn remove_index_files(path: &Path) -> Result<()> {
+    for entry in fs::read_dir(path).map_err(TantivyError::from)? {
+        let entry = entry.map_err(TantivyError::from)?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
 
-        Ok(result)
+        if INDEX_FILES.contains(&name) || is_segment_file(name) {
+            fs::remove_file(entry.path()).map_err(TantivyError::from)?;
+        }
     }
+
+    Ok(())
+}
+
+/// Returns true if the file name starts with a segment ID, like the files of
+/// a segment and its deletes
+fn is_segment_file(name: &str) -> bool {
+    name.split_once('.')
+        .is_some_and(|(id, _)| id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+fn term_query(term: Term) -> Box<dyn Query> {
+    Box::new(TermQuery::new(term, IndexRecordOption::Basic))
 }
@@ -0,0 +1,70 @@
+use tantivy::tokenizer::Language;
+use whatlang::Lang;
+
+const LANGUAGE_CODES: [(Language, &str); 18] = [
+    (Language::Arabic, "ar"),
+    (Language::Danish, "da"),
+    (Language::Dutch, "nl"),
+    (Language::English, "en"),
+    (Language::Finnish, "fi"),
+    (Language::French, "fr"),
+    (Language::German, "de"),
+    (Language::Greek, "el"),
+    (Language::Hungarian, "hu"),
+    (Language::Italian, "it"),
+    (Language::Norwegian, "no"),
+    (Language::Portuguese, "pt"),
+    (Language::Romanian, "ro"),
+    (Language::Russian, "ru"),
+    (Language::Spanish, "es"),
+    (Language::Swedish, "sv"),
+    (Language::Tamil, "ta"),
+    (Language::Turkish, "tr"),
+];
+
+/// Returns the language for an ISO 639-1 code
+pub fn language_from_code(code: &str) -> Option<Language> {
+    LANGUAGE_CODES
+        .iter()
+        .find(|(_, c)| c.eq_ignore_ascii_case(code))
+        .map(|(l, _)| *l)
+}
+
+/// Returns the ISO 639-1 code of a language
+pub fn language_code(lang: Language) -> &'static str {
+    LANGUAGE_CODES
+        .iter()
+        .find(|(l, _)| *l == lang)
+        .map(|(_, c)| *c)
+        .unwrap()
+}
+
+/// Detects the language of a text, returns `None` if the detection is unreliable
+/// or the language isn't supported
+pub fn detect_language(text: &str) -> Option<Language> {
+    let info = whatlang::detect(text).filter(|i| i.is_reliable())?;
+
+    let lang = match info.lang() {
+        Lang::Ara => Language::Arabic,
+        Lang::Dan => Language::Danish,
+        Lang::Nld => Language::Dutch,
+        Lang::Eng => Language::English,
+        Lang::Fin => Language::Finnish,
+        Lang::Fra => Language::French,
+        Lang::Deu => Language::German,
+        Lang::Ell => Language::Greek,
+        Lang::Hun => Language::Hungarian,
+        Lang::Ita => Language::Italian,
+        Lang::Nob => Language::Norwegian,
+        Lang::Por => Language::Portuguese,
+        Lang::Ron => Language::Romanian,
+        Lang::Rus => Language::Russian,
+        Lang::Spa => Language::Spanish,
+        Lang::Swe => Language::Swedish,
+        Lang::Tam => Language::Tamil,
+        Lang::Tur => Language::Turkish,
+        _ => return None,
+    };
+
+    Some(lang)
+}
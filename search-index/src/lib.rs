@@ -3,11 +3,28 @@ use std::result;
 use tantivy::{query::QueryParserError, TantivyError};
 use thiserror::Error;
 
+mod diff;
+mod document;
 mod index;
+mod language;
+mod pattern;
 mod schema;
+mod spelling;
+mod supplement;
 mod tokenizer;
 
-pub use index::{DocType, Index, IndexDoc, QueryOptions};
+pub use diff::{ChangedIds, GenerationDiff, TypeDiff};
+pub use index::{
+    BatchSummary, CommitStats, DocError, DocPrice, DocSummary, DocType, Explained, ExplainedDoc,
+    FieldBoosts, FieldTokens, Index, IndexDoc, NameAnalyzer, NgramRange, NumericField,
+    NumericFilter, OptimizeStats, QueryBuilder, QueryOptions, QueryOutput, QueryTimings,
+    MAX_FUZZY_DISTANCE, MIN_WRITE_BUFFER, WRITE_BUFFER,
+};
+pub use language::{detect_language, language_code, language_from_code};
+pub use pattern::{PatternField, PatternQuery};
+pub use supplement::{
+    Ballistics, ItemLocations, Preset, Price, Supplements, Translation, Translations,
+};
 pub use tantivy::tokenizer::Language;
 
 pub type Result<T> = result::Result<T, Error>;
@@ -22,4 +39,21 @@ pub enum Error {
     UnhealthyIndex(String),
     #[error("Parse error: {0}")]
     ParseError(String),
+    #[error("Document not found: {0}")]
+    DocumentNotFound(String),
+    #[error("Query is too complex: {0}")]
+    QueryTooComplex(String),
+    #[error("Unknown kind \"{kind}\"{}", did_you_mean(suggestions))]
+    UnknownKind {
+        kind: String,
+        suggestions: Vec<String>,
+    },
+}
+
+fn did_you_mean(suggestions: &[String]) -> String {
+    if suggestions.is_empty() {
+        String::new()
+    } else {
+        format!(", did you mean {}?", suggestions.join(", "))
+    }
 }
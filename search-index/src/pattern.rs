@@ -0,0 +1,101 @@
+use crate::{schema::IndexField, Error, Result};
+
+use std::{fmt, str::FromStr, sync::Arc};
+
+use tantivy_fst::Regex;
+
+/// Fields which can be searched by pattern
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PatternField {
+    Id,
+    Name,
+    ShortName,
+    Kind,
+    Location,
+}
+
+impl PatternField {
+    pub(crate) fn index_field(&self) -> IndexField {
+        match self {
+            PatternField::Id => IndexField::ID,
+            PatternField::Name => IndexField::Name,
+            PatternField::ShortName => IndexField::ShortName,
+            PatternField::Kind => IndexField::Kind,
+            PatternField::Location => IndexField::Locations,
+        }
+    }
+}
+
+impl FromStr for PatternField {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let f = match s {
+            "id" => PatternField::Id,
+            "name" => PatternField::Name,
+            "short_name" => PatternField::ShortName,
+            "kind" => PatternField::Kind,
+            "location" => PatternField::Location,
+            _ => {
+                return Err(Error::ParseError(format!(
+                    "unknown pattern field \"{}\"",
+                    s
+                )))
+            }
+        };
+
+        Ok(f)
+    }
+}
+
+impl fmt::Display for PatternField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternField::Id => write!(f, "id"),
+            PatternField::Name => write!(f, "name"),
+            PatternField::ShortName => write!(f, "short_name"),
+            PatternField::Kind => write!(f, "kind"),
+            PatternField::Location => write!(f, "location"),
+        }
+    }
+}
+
+/// Pattern matched against the indexed terms of a field.
+///
+/// Parsed from `field:/regex/` or `field:wild*card?`.
+#[derive(Debug, Clone)]
+pub struct PatternQuery {
+    pub field: PatternField,
+    /// Automaton searching the term dictionary, matches whole terms
+    pub(crate) regex: Arc<Regex>,
+}
+
+impl FromStr for PatternQuery {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (field, pattern) = s
+            .split_once(':')
+            .ok_or_else(|| Error::ParseError("pattern must be prefixed by a field".to_string()))?;
+        let field = PatternField::from_str(field.trim())?;
+
+        let pattern = match pattern.strip_prefix('/').and_then(|p| p.strip_suffix('/')) {
+            Some(p) => p.to_owned(),
+            None => regex::escape(pattern)
+                .replace("\\*", ".*")
+                .replace("\\?", "."),
+        };
+
+        if pattern.is_empty() {
+            return Err(Error::ParseError("pattern is empty".to_string()));
+        }
+
+        let regex = Regex::new(&format!("(?i)(?:{})", pattern))
+            .map_err(|e| Error::ParseError(e.to_string()))?;
+
+        Ok(Self {
+            field,
+            regex: Arc::new(regex),
+        })
+    }
+}
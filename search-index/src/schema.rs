@@ -2,7 +2,8 @@ use crate::tokenizer::{NgramOptions, Tokenizer};
 
 use tantivy::{
     schema::{
-        FieldEntry, IndexRecordOption, Schema, SchemaBuilder, TextFieldIndexing, TextOptions,
+        DateOptions, FieldEntry, IndexRecordOption, NumericOptions, Schema, SchemaBuilder,
+        TextFieldIndexing, TextOptions,
     },
     tokenizer::Language,
 };
@@ -11,9 +12,23 @@ use tantivy::{
 pub(crate) enum IndexField {
     ID,
     Name,
+    ShortName,
+    NamePrefix,
+    /// Words of the name for fuzzy matching, the ngrams of the name are too
+    /// short for edit distances
+    NameTerms,
     Description(Language),
     Kind,
     Type,
+    Locations,
+    Parent,
+    Compatible,
+    Penetration,
+    Damage,
+    Price,
+    PriceOriginal,
+    Currency,
+    Modified,
 }
 
 impl IndexField {
@@ -21,22 +36,67 @@ impl IndexField {
         match self {
             IndexField::ID => "id",
             IndexField::Name => "name",
+            IndexField::ShortName => "short_name",
+            IndexField::NamePrefix => "name_prefix",
+            IndexField::NameTerms => "name_terms",
             IndexField::Description(_) => "description",
             IndexField::Kind => "kind",
             IndexField::Type => "type",
+            IndexField::Locations => "location",
+            IndexField::Parent => "parent",
+            IndexField::Compatible => "compatible",
+            IndexField::Penetration => "penetration",
+            IndexField::Damage => "damage",
+            IndexField::Price => "price",
+            IndexField::PriceOriginal => "price_original",
+            IndexField::Currency => "currency",
+            IndexField::Modified => "modified",
+        }
+    }
+
+    fn numeric_options(&self) -> Option<NumericOptions> {
+        match self {
+            IndexField::Penetration | IndexField::Damage | IndexField::Price => Some(
+                NumericOptions::default()
+                    .set_stored()
+                    .set_indexed()
+                    .set_fast(),
+            ),
+            IndexField::PriceOriginal => Some(NumericOptions::default().set_stored()),
+            _ => None,
         }
     }
 
     fn options(&self) -> Option<TextOptions> {
         match self {
-            IndexField::ID => Some(TextOptions::default().set_stored()),
-            IndexField::Name => Some(
+            IndexField::ID => Some(
+                TextOptions::default().set_stored().set_indexing_options(
+                    TextFieldIndexing::default()
+                        .set_tokenizer("raw")
+                        .set_index_option(IndexRecordOption::Basic),
+                ),
+            ),
+            IndexField::Name | IndexField::ShortName => Some(
                 TextOptions::default().set_stored().set_indexing_options(
                     TextFieldIndexing::default()
                         .set_tokenizer(Tokenizer::Ngram(NgramOptions::default()).name())
                         .set_index_option(IndexRecordOption::WithFreqsAndPositions),
                 ),
             ),
+            IndexField::NamePrefix => Some(
+                TextOptions::default().set_indexing_options(
+                    TextFieldIndexing::default()
+                        .set_tokenizer(Tokenizer::Ngram(NgramOptions::edge()).name())
+                        .set_index_option(IndexRecordOption::WithFreqs),
+                ),
+            ),
+            IndexField::NameTerms => Some(
+                TextOptions::default().set_indexing_options(
+                    TextFieldIndexing::default()
+                        .set_tokenizer("default")
+                        .set_index_option(IndexRecordOption::WithFreqs),
+                ),
+            ),
             IndexField::Description(lang) => Some(
                 TextOptions::default().set_stored().set_indexing_options(
                     TextFieldIndexing::default()
@@ -58,6 +118,39 @@ impl IndexField {
                         .set_index_option(IndexRecordOption::Basic),
                 ),
             ),
+            IndexField::Locations => Some(
+                TextOptions::default().set_stored().set_indexing_options(
+                    TextFieldIndexing::default()
+                        .set_tokenizer(Tokenizer::Keyword.name())
+                        .set_index_option(IndexRecordOption::Basic),
+                ),
+            ),
+            IndexField::Parent => Some(
+                TextOptions::default().set_stored().set_indexing_options(
+                    TextFieldIndexing::default()
+                        .set_tokenizer("raw")
+                        .set_index_option(IndexRecordOption::Basic),
+                ),
+            ),
+            IndexField::Compatible => Some(
+                TextOptions::default().set_indexing_options(
+                    TextFieldIndexing::default()
+                        .set_tokenizer(Tokenizer::Ngram(NgramOptions::default()).name())
+                        .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+                ),
+            ),
+            IndexField::Currency => Some(
+                TextOptions::default().set_stored().set_indexing_options(
+                    TextFieldIndexing::default()
+                        .set_tokenizer(Tokenizer::Keyword.name())
+                        .set_index_option(IndexRecordOption::Basic),
+                ),
+            ),
+            IndexField::Penetration
+            | IndexField::Damage
+            | IndexField::Price
+            | IndexField::PriceOriginal
+            | IndexField::Modified => None,
         }
     }
 }
@@ -79,9 +172,16 @@ impl Into<FieldEntry> for IndexField {
         match self {
             IndexField::ID
             | IndexField::Name
+            | IndexField::ShortName
+            | IndexField::NamePrefix
+            | IndexField::NameTerms
             | IndexField::Description(_)
             | IndexField::Kind
-            | IndexField::Type => {
+            | IndexField::Type
+            | IndexField::Locations
+            | IndexField::Parent
+            | IndexField::Compatible
+            | IndexField::Currency => {
                 let name = self.to_string();
                 let opts = match self.options() {
                     Some(o) => o,
@@ -90,6 +190,20 @@ impl Into<FieldEntry> for IndexField {
 
                 FieldEntry::new_text(name, opts)
             }
+            IndexField::Penetration
+            | IndexField::Damage
+            | IndexField::Price
+            | IndexField::PriceOriginal => {
+                let name = self.to_string();
+                let opts = self.numeric_options().unwrap_or_default();
+
+                FieldEntry::new_f64(name, opts)
+            }
+            IndexField::Modified => {
+                let opts = DateOptions::default().set_stored().set_indexed().set_fast();
+
+                FieldEntry::new_date(self.to_string(), opts)
+            }
         }
     }
 }
@@ -108,9 +222,21 @@ impl IndexSchema {
 
         builder.add_field(IndexField::ID.into());
         builder.add_field(IndexField::Name.into());
+        builder.add_field(IndexField::ShortName.into());
+        builder.add_field(IndexField::NamePrefix.into());
+        builder.add_field(IndexField::NameTerms.into());
         builder.add_field(IndexField::Description(self.lang).into());
         builder.add_field(IndexField::Kind.into());
         builder.add_field(IndexField::Type.into());
+        builder.add_field(IndexField::Locations.into());
+        builder.add_field(IndexField::Parent.into());
+        builder.add_field(IndexField::Compatible.into());
+        builder.add_field(IndexField::Penetration.into());
+        builder.add_field(IndexField::Damage.into());
+        builder.add_field(IndexField::Price.into());
+        builder.add_field(IndexField::PriceOriginal.into());
+        builder.add_field(IndexField::Currency.into());
+        builder.add_field(IndexField::Modified.into());
 
         builder.build()
     }
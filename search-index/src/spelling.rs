@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+/// Minimum length of words considered for corrections
+const MIN_WORD_LEN: usize = 3;
+
+/// Words of the indexed names with their frequency, used for spelling corrections
+#[derive(Debug, Default)]
+pub(crate) struct Vocabulary(HashMap<String, u32>);
+
+impl Vocabulary {
+    pub(crate) fn add(&mut self, text: &str) {
+        for word in words(text) {
+            *self.0.entry(word).or_default() += 1;
+        }
+    }
+
+    /// Corrects each unknown word of the query to its closest known word.
+    ///
+    /// Returns `None` if no word was corrected.
+    pub(crate) fn correct(&self, query: &str) -> Option<String> {
+        let mut corrected = false;
+
+        let words = query
+            .split_whitespace()
+            .map(|w| match self.closest(w) {
+                Some(c) => {
+                    corrected = true;
+                    c.to_owned()
+                }
+                None => w.to_owned(),
+            })
+            .collect::<Vec<_>>();
+
+        corrected.then(|| words.join(" "))
+    }
+
+    fn closest(&self, word: &str) -> Option<&str> {
+        let word = word.to_lowercase();
+        let len = word.chars().count();
+
+        if len < MIN_WORD_LEN
+            || !word.chars().all(char::is_alphanumeric)
+            || self.0.contains_key(&word)
+        {
+            return None;
+        }
+
+        let max_distance = if len <= 5 { 1 } else { 2 };
+
+        self.0
+            .iter()
+            .filter_map(|(w, f)| {
+                let d = levenshtein(&word, w);
+                (d <= max_distance).then_some((d, *f, w.as_str()))
+            })
+            .min_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)).then(a.2.cmp(b.2)))
+            .map(|(_, _, w)| w)
+    }
+}
+
+/// Returns up to `limit` candidates closest to the word, ordered by their
+/// edit distance
+pub(crate) fn closest_candidates<'a, I>(word: &str, candidates: I, limit: usize) -> Vec<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let word = word.to_lowercase();
+    let max_distance = (word.chars().count() / 2).max(2);
+
+    let mut matches = candidates
+        .into_iter()
+        .map(|c| (levenshtein(&word, c), c))
+        .filter(|(d, _)| *d <= max_distance)
+        .collect::<Vec<_>>();
+    matches.sort_unstable();
+
+    matches.into_iter().take(limit).map(|(_, c)| c).collect()
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= MIN_WORD_LEN)
+        .map(|w| w.to_lowercase())
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            let next = (row[j + 1] + 1).min(row[j] + 1).min(prev + cost);
+            prev = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+
+    row[b.len()]
+}
@@ -0,0 +1,124 @@
+use crate::language::language_code;
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use tantivy::tokenizer::Language;
+use tarkov_database_rs::model::item::common::Item;
+
+/// Locations an item can be found in, keyed by item ID
+pub type ItemLocations = HashMap<String, Vec<String>>;
+
+/// Weapon preset with the mods it consists of
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Preset {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// ID of the parent weapon
+    pub weapon: String,
+    /// IDs of the mods compatible with the parent weapon
+    #[serde(default)]
+    pub mods: Vec<String>,
+}
+
+/// Ballistic properties of ammunition
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Ballistics {
+    pub penetration: f64,
+    pub damage: f64,
+}
+
+/// Price of an item in its original currency
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Price {
+    pub amount: f64,
+    /// ISO 4217 currency code
+    pub currency: String,
+}
+
+/// Translated texts of an item or preset, missing texts aren't replaced
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Translation {
+    pub name: Option<String>,
+    pub short_name: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Translations keyed by language code and item or preset ID
+pub type Translations = HashMap<String, HashMap<String, Translation>>;
+
+/// Data indexed in addition to the API items, which isn't provided by the API
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Supplements {
+    #[serde(default)]
+    pub locations: ItemLocations,
+    #[serde(default)]
+    pub presets: Vec<Preset>,
+    /// Ballistic properties keyed by item ID
+    #[serde(default)]
+    pub ballistics: HashMap<String, Ballistics>,
+    /// Prices keyed by item ID
+    #[serde(default)]
+    pub prices: HashMap<String, Price>,
+    /// Exchange rates to roubles keyed by lowercase currency code
+    #[serde(default)]
+    pub rates: HashMap<String, f64>,
+    /// Texts of the items and presets in other languages, the API only
+    /// provides them in English
+    #[serde(default)]
+    pub translations: Translations,
+}
+
+impl Supplements {
+    /// Converts a price to roubles, returns `None` if the currency rate is unknown
+    pub fn normalize_price(&self, price: &Price) -> Option<f64> {
+        let currency = price.currency.to_lowercase();
+        if currency == "rub" {
+            return Some(price.amount);
+        }
+
+        self.rates.get(&currency).map(|r| price.amount * r)
+    }
+
+    /// Returns the items and the supplements with the texts translated to the
+    /// language, or `None` if there are no translations for it
+    pub fn localize(&self, lang: Language, items: &[Item]) -> Option<(Vec<Item>, Supplements)> {
+        let translations = self.translations.get(language_code(lang))?;
+
+        let items = items
+            .iter()
+            .map(|item| {
+                let mut item = item.clone();
+                if let Some(t) = translations.get(&item.id) {
+                    replace(&mut item.name, &t.name);
+                    replace(&mut item.short_name, &t.short_name);
+                    replace(&mut item.description, &t.description);
+                }
+                item
+            })
+            .collect();
+
+        let mut supplements = self.clone();
+        for preset in supplements.presets.iter_mut() {
+            if let Some(t) = translations.get(&preset.id) {
+                replace(&mut preset.name, &t.name);
+                replace(&mut preset.description, &t.description);
+            }
+        }
+
+        Some((items, supplements))
+    }
+}
+
+fn replace(text: &mut String, translated: &Option<String>) {
+    if let Some(t) = translated {
+        text.clone_from(t);
+    }
+}
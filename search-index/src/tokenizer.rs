@@ -1,7 +1,7 @@
 use tantivy::{
     tokenizer::{
-        Language, LowerCaser, NgramTokenizer, RemoveLongFilter, SimpleTokenizer, Stemmer,
-        StopWordFilter, TextAnalyzer,
+        Language, LowerCaser, NgramTokenizer, RawTokenizer, RemoveLongFilter, SimpleTokenizer,
+        Stemmer, StopWordFilter, TextAnalyzer,
     },
     Index, TantivyError,
 };
@@ -21,13 +21,16 @@ const STOP_WORDS_OEC: [&str; 100] = [
 pub(crate) enum Tokenizer {
     Ngram(NgramOptions),
     Custom(Language),
+    Keyword,
 }
 
 impl Tokenizer {
     pub(crate) fn name(&self) -> &'static str {
         match self {
+            Tokenizer::Ngram(o) if o.prefix => "edge_ngram",
             Tokenizer::Ngram(_) => "ngram",
             Tokenizer::Custom(_) => "custom",
+            Tokenizer::Keyword => "keyword",
         }
     }
 
@@ -55,6 +58,9 @@ impl Tokenizer {
                 .filter(stop_words)
                 .filter(Stemmer::new(lang.to_owned()))
                 .build(),
+            Tokenizer::Keyword => TextAnalyzer::builder(RawTokenizer::default())
+                .filter(LowerCaser)
+                .build(),
         };
 
         Ok(analyzer)
@@ -64,6 +70,7 @@ impl Tokenizer {
         let lang = match self {
             Tokenizer::Ngram(o) => &o.lang,
             Tokenizer::Custom(l) => l,
+            Tokenizer::Keyword => return StopWordFilter::remove(Vec::new()),
         };
 
         let stop_words = match lang {
@@ -93,6 +100,11 @@ impl NgramOptions {
         }
     }
 
+    /// Options for prefix ngrams of whole values
+    pub(crate) fn edge() -> Self {
+        Self::new(1, crate::index::MAX_PREFIX_LEN, true)
+    }
+
     pub(crate) fn set_language(mut self, lang: Language) -> Self {
         self.lang = lang;
         self
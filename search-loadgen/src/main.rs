@@ -0,0 +1,208 @@
+//! Replays a query log or synthetic queries against a running server at a
+//! fixed rate and reports the latency percentiles.
+//!
+//! Configured by `LOADGEN_` prefixed variables, e.g.
+//! `LOADGEN_URL=https://localhost:8080 LOADGEN_TOKEN=... LOADGEN_RATE=50`.
+
+mod queries;
+
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use search_client::{Client, SearchQuery};
+use serde::Deserialize;
+use tokio::{
+    sync::{Mutex, Semaphore},
+    time::MissedTickBehavior,
+};
+
+const fn default_rate() -> f64 {
+    10.0
+}
+
+const fn default_duration() -> Duration {
+    Duration::from_secs(60)
+}
+
+const fn default_concurrency() -> usize {
+    64
+}
+
+const fn default_synthetic_queries() -> usize {
+    1000
+}
+
+fn default_seed_terms() -> Vec<String> {
+    [
+        "ammo", "rifle", "armor", "keycard", "medkit", "grenade", "sight",
+    ]
+    .iter()
+    .map(|t| t.to_string())
+    .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct Config {
+    url: String,
+    token: String,
+    /// CA certificate of the server, for self-signed certificates
+    ca_cert: Option<PathBuf>,
+    /// Log of the queries to replay, synthetic queries are used otherwise
+    query_log: Option<PathBuf>,
+    /// Requests per second
+    #[serde(default = "default_rate")]
+    rate: f64,
+    #[serde(default = "default_duration", with = "humantime_serde")]
+    duration: Duration,
+    /// Maximum of requests in flight, requests beyond are dropped
+    #[serde(default = "default_concurrency")]
+    concurrency: usize,
+    #[serde(default = "default_synthetic_queries")]
+    synthetic_queries: usize,
+    #[serde(default = "default_seed_terms")]
+    seed_terms: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct Outcomes {
+    latencies: Vec<Duration>,
+    /// Failed requests by status code, zero for transport errors
+    errors: BTreeMap<u16, usize>,
+    dropped: usize,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let config: Config = envy::prefixed("LOADGEN_").from_env()?;
+    if !config.rate.is_finite() || config.rate <= 0.0 {
+        return Err("LOADGEN_RATE must be greater than zero".into());
+    }
+
+    let mut http = reqwest::Client::builder();
+    if let Some(path) = &config.ca_cert {
+        http = http.add_root_certificate(reqwest::Certificate::from_pem(&std::fs::read(path)?)?);
+    }
+    let client = Client::with_http_client(http.build()?, &config.url).with_token(&config.token);
+
+    let queries = match &config.query_log {
+        Some(path) => queries::from_log(path)?,
+        None => queries::synthetic(&client, &config.seed_terms, config.synthetic_queries).await?,
+    };
+    if queries.is_empty() {
+        return Err("no queries to replay".into());
+    }
+
+    println!(
+        "Replaying {} queries at {} req/s for {:?}",
+        queries.len(),
+        config.rate,
+        config.duration
+    );
+
+    let outcomes = Arc::new(Mutex::new(Outcomes::default()));
+    let permits = Arc::new(Semaphore::new(config.concurrency));
+
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / config.rate));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Burst);
+
+    let start = Instant::now();
+    let mut tasks = Vec::new();
+
+    for query in queries.iter().cycle() {
+        interval.tick().await;
+        if start.elapsed() >= config.duration {
+            break;
+        }
+
+        // Open loop, a saturated server doesn't slow down the request rate
+        let permit = match permits.clone().try_acquire_owned() {
+            Ok(p) => p,
+            Err(_) => {
+                outcomes.lock().await.dropped += 1;
+                continue;
+            }
+        };
+
+        let client = client.clone();
+        let query = SearchQuery::new(query.as_str());
+        let outcomes = outcomes.clone();
+
+        tasks.push(tokio::spawn(async move {
+            let sent = Instant::now();
+            let result = client.search(&query).await;
+            let took = sent.elapsed();
+            drop(permit);
+
+            let mut outcomes = outcomes.lock().await;
+            match result {
+                Ok(_) => outcomes.latencies.push(took),
+                Err(search_client::Error::ApiError(s)) => {
+                    *outcomes.errors.entry(s.code.as_u16()).or_default() += 1
+                }
+                Err(_) => *outcomes.errors.entry(0).or_default() += 1,
+            }
+        }));
+    }
+
+    for task in tasks {
+        task.await?;
+    }
+
+    report(&mut *outcomes.lock().await, start.elapsed());
+
+    Ok(())
+}
+
+fn report(outcomes: &mut Outcomes, elapsed: Duration) {
+    outcomes.latencies.sort_unstable();
+
+    let succeeded = outcomes.latencies.len();
+    let failed = outcomes.errors.values().sum::<usize>();
+
+    println!(
+        "Requests:   {} ok, {} failed, {} dropped",
+        succeeded, failed, outcomes.dropped
+    );
+    println!(
+        "Throughput: {:.1} req/s",
+        (succeeded + failed) as f64 / elapsed.as_secs_f64()
+    );
+
+    for (code, count) in outcomes.errors.iter() {
+        match code {
+            0 => println!("  transport error: {}", count),
+            c => println!("  status {}: {}", c, count),
+        }
+    }
+
+    if succeeded == 0 {
+        return;
+    }
+
+    for p in [50.0, 90.0, 95.0, 99.0, 99.9] {
+        println!(
+            "p{:<5} {:>10.3} ms",
+            p,
+            ms(percentile(&outcomes.latencies, p))
+        );
+    }
+    println!(
+        "max    {:>10.3} ms",
+        ms(*outcomes.latencies.last().unwrap())
+    );
+}
+
+/// Returns the percentile of the sorted latencies by the nearest rank
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
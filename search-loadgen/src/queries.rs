@@ -0,0 +1,92 @@
+use std::{collections::HashSet, fs, io, path::Path};
+
+use rand::{seq::SliceRandom, Rng};
+use search_client::{Client, SearchQuery};
+use serde_json::Value;
+
+/// Documents fetched per seed term to build the synthetic corpus
+const SEED_LIMIT: usize = 100;
+
+/// Length bounds of queries accepted by the server
+const MIN_QUERY_LEN: usize = 3;
+const MAX_QUERY_LEN: usize = 100;
+
+/// Reads the queries of a query log, one per line.
+///
+/// Lines are either plain queries or JSON log events with a `query` field,
+/// as written by the server with JSON logging.
+pub fn from_log(path: &Path) -> io::Result<Vec<String>> {
+    let queries = fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.starts_with('{') {
+                let event: Value = serde_json::from_str(line).ok()?;
+                let query = event
+                    .get("query")
+                    .or_else(|| event.get("fields").and_then(|f| f.get("query")))?
+                    .as_str()?;
+                // Debug formatted fields are quoted
+                Some(query.trim_matches('"').to_string())
+            } else {
+                Some(line.to_string())
+            }
+        })
+        .filter(|q| (MIN_QUERY_LEN..=MAX_QUERY_LEN).contains(&q.len()))
+        .collect();
+
+    Ok(queries)
+}
+
+/// Generates queries from the names of the documents found by the seed terms
+pub async fn synthetic(
+    client: &Client,
+    seed_terms: &[String],
+    count: usize,
+) -> search_client::Result<Vec<String>> {
+    let mut names = HashSet::new();
+
+    for term in seed_terms {
+        let result = client
+            .search(&SearchQuery::new(term).limit(SEED_LIMIT))
+            .await?;
+        for doc in result.data {
+            names.extend(doc.short_name);
+            names.insert(doc.name);
+        }
+    }
+
+    let names = names.into_iter().collect::<Vec<_>>();
+    if names.is_empty() {
+        return Ok(seed_terms.to_vec());
+    }
+
+    let mut rng = rand::thread_rng();
+    let queries = (0..count)
+        .filter_map(|_| {
+            let name = names.choose(&mut rng)?;
+            let words = name
+                .split_whitespace()
+                .filter(|w| w.len() >= MIN_QUERY_LEN)
+                .collect::<Vec<_>>();
+
+            // Mix of full names and partial ones, as typed by users
+            let query = match words.len() {
+                0 => name.clone(),
+                1 => words[0].to_string(),
+                l => {
+                    let take = rng.gen_range(1..=l.min(3));
+                    words
+                        .choose_multiple(&mut rng, take)
+                        .copied()
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                }
+            };
+
+            (query.len() >= MIN_QUERY_LEN).then_some(query)
+        })
+        .collect();
+
+    Ok(queries)
+}
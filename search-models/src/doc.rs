@@ -0,0 +1,52 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, thiserror::Error)]
+#[error("unknown doc type")]
+pub struct ParseDocTypeError;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum DocType {
+    Item,
+    Location,
+    Module,
+    Preset,
+}
+
+impl DocType {
+    pub const ALL: [DocType; 4] = [
+        DocType::Item,
+        DocType::Location,
+        DocType::Module,
+        DocType::Preset,
+    ];
+}
+
+impl FromStr for DocType {
+    type Err = ParseDocTypeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let t = match s {
+            "item" => DocType::Item,
+            "location" => DocType::Location,
+            "module" => DocType::Module,
+            "preset" => DocType::Preset,
+            _ => return Err(ParseDocTypeError),
+        };
+
+        Ok(t)
+    }
+}
+
+impl fmt::Display for DocType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocType::Item => write!(f, "item"),
+            DocType::Location => write!(f, "location"),
+            DocType::Module => write!(f, "module"),
+            DocType::Preset => write!(f, "preset"),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthStatus {
+    pub ok: bool,
+    pub service: Services,
+    pub indexes: Vec<IndexStatus>,
+    /// Indexes were built at least once, they may still be stale
+    #[serde(default = "default_true")]
+    pub populated: bool,
+    pub sync_deferred: bool,
+    /// Maintenance set by an admin, API failures don't affect `ok` meanwhile
+    #[serde(default)]
+    pub maintenance: bool,
+    pub api_origin: usize,
+    pub stale: bool,
+    pub schema_incompatible: bool,
+    pub skipped_documents: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub document_errors: Vec<String>,
+    /// Uptime in seconds
+    pub uptime: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Services {
+    pub index: ServiceStatus,
+    pub api: ServiceStatus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexStatus {
+    pub language: String,
+    pub status: ServiceStatus,
+    #[serde(default = "default_true")]
+    pub populated: bool,
+    pub modified: DateTime<Utc>,
+}
+
+const fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceStatus {
+    Ok,
+    Warning,
+    Failure,
+}
+
+impl ServiceStatus {
+    fn value(&self) -> u8 {
+        match self {
+            ServiceStatus::Ok => 0,
+            ServiceStatus::Warning => 1,
+            ServiceStatus::Failure => 2,
+        }
+    }
+}
+
+impl Serialize for ServiceStatus {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u8(self.value())
+    }
+}
+
+impl<'de> Deserialize<'de> for ServiceStatus {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match u8::deserialize(deserializer)? {
+            0 => Ok(ServiceStatus::Ok),
+            1 => Ok(ServiceStatus::Warning),
+            _ => Ok(ServiceStatus::Failure),
+        }
+    }
+}
@@ -0,0 +1,11 @@
+mod doc;
+mod health;
+mod search;
+mod status;
+mod token;
+
+pub use doc::{DocType, ParseDocTypeError};
+pub use health::{HealthStatus, IndexStatus, ServiceStatus, Services};
+pub use search::{AppliedOptions, SearchMeta, SearchResult};
+pub use status::Status;
+pub use token::{Claims, CreateTokenRequest, Scope, TokenResponse};
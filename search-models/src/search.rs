@@ -0,0 +1,63 @@
+use crate::DocType;
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Result of a search, generic over the result documents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult<D> {
+    pub count: usize,
+    pub data: Vec<D>,
+    pub meta: SearchMeta,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchMeta {
+    pub query: String,
+    /// Normalized query, if it was autocorrected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_query: Option<String>,
+    /// Normalized query, if it differs from the query sent by the client
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalized_query: Option<String>,
+    pub options: AppliedOptions,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    /// Language detected from the query if none was requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detected_language: Option<String>,
+    /// Deprecations used by the request
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Fingerprint of the result, used to refine it by further filters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    pub index_modified: DateTime<Utc>,
+    /// Generation of the index, which changes with every update
+    #[serde(default)]
+    pub index_generation: u64,
+    /// Hash of the ranking config the result was ranked by
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ranking_hash: Option<String>,
+    pub took_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppliedOptions {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub r#type: Vec<DocType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<Vec<String>>,
+    /// Kind aliases of the request and the kinds they were resolved to
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub kind_aliases: BTreeMap<String, Vec<String>>,
+    pub limit: usize,
+    pub conjunction: bool,
+    pub language: String,
+    pub sort: String,
+}
@@ -0,0 +1,51 @@
+use http::StatusCode;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Error response of the API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    #[serde(
+        serialize_with = "se_status_code_as_u16",
+        deserialize_with = "de_status_code_from_u16"
+    )]
+    pub code: StatusCode,
+    pub message: String,
+}
+
+impl Status {
+    pub fn new<S>(code: StatusCode, message: S) -> Self
+    where
+        S: ToString,
+    {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "axum")]
+impl axum::response::IntoResponse for Status {
+    fn into_response(self) -> axum::response::Response {
+        let mut res = axum::Json(&self).into_response();
+        *res.status_mut() = self.code;
+
+        res
+    }
+}
+
+fn se_status_code_as_u16<S>(x: &StatusCode, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    s.serialize_u16(x.as_u16())
+}
+
+fn de_status_code_from_u16<'de, D>(d: D) -> Result<StatusCode, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let code = u16::deserialize(d)?;
+    StatusCode::from_u16(code).map_err(serde::de::Error::custom)
+}
@@ -0,0 +1,161 @@
+use std::time;
+
+use chrono::{serde::ts_seconds, DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Scope {
+    /// Search with full result documents
+    Search,
+    /// Search with results reduced to IDs and names
+    SearchSummary,
+    Stats,
+    Token,
+    Admin,
+    /// Regex and wildcard searches, which are expensive
+    Pattern,
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Self::Search
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Claims {
+    aud: Vec<String>,
+    #[serde(with = "ts_seconds")]
+    exp: DateTime<Utc>,
+    #[serde(with = "ts_seconds")]
+    iat: DateTime<Utc>,
+    sub: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    iss: Option<String>,
+    scope: Vec<Scope>,
+    /// ID of one-time tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    jti: Option<String>,
+    /// Name of the application using the token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    client_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    client_version: Option<String>,
+}
+
+impl Claims {
+    pub const DEFAULT_EXP_MINUTES: i64 = 60;
+
+    pub fn new<A, S>(aud: A, sub: &str, scope: S) -> Self
+    where
+        A: IntoIterator<Item = String>,
+        S: IntoIterator<Item = Scope>,
+    {
+        Self {
+            aud: aud.into_iter().collect(),
+            exp: Utc::now() + Duration::minutes(Self::DEFAULT_EXP_MINUTES),
+            iat: Utc::now(),
+            sub: sub.into(),
+            iss: None,
+            scope: scope.into_iter().collect(),
+            jti: None,
+            client_name: None,
+            client_version: None,
+        }
+    }
+
+    pub fn set_expiration(&mut self, date: DateTime<Utc>) {
+        self.exp = date;
+    }
+
+    /// Makes the token usable only once for token creation
+    pub fn set_one_time(&mut self) {
+        self.jti = Some(Uuid::new_v4().to_string());
+    }
+
+    pub fn set_issuer(&mut self, issuer: Option<String>) {
+        self.iss = issuer;
+    }
+
+    pub fn set_client(&mut self, name: Option<String>, version: Option<String>) {
+        self.client_name = name;
+        self.client_version = version;
+    }
+
+    pub fn audience(&self) -> &[String] {
+        &self.aud
+    }
+
+    pub fn subject(&self) -> &str {
+        &self.sub
+    }
+
+    pub fn issuer(&self) -> Option<&str> {
+        self.iss.as_deref()
+    }
+
+    pub fn scopes(&self) -> &[Scope] {
+        &self.scope
+    }
+
+    pub fn has_scope(&self, scope: &Scope) -> bool {
+        self.scope.contains(scope)
+    }
+
+    pub fn expiration(&self) -> DateTime<Utc> {
+        self.exp
+    }
+
+    pub fn issued_at(&self) -> DateTime<Utc> {
+        self.iat
+    }
+
+    /// ID of one-time tokens
+    pub fn one_time_id(&self) -> Option<&str> {
+        self.jti.as_deref()
+    }
+
+    pub fn client_name(&self) -> Option<&str> {
+        self.client_name.as_deref()
+    }
+
+    pub fn client_version(&self) -> Option<&str> {
+        self.client_version.as_deref()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenResponse {
+    pub token: String,
+    #[serde(with = "ts_seconds")]
+    pub expires_at: DateTime<Utc>,
+    /// Deployment which issued the token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issuer: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateTokenRequest {
+    pub sub: String,
+    /// Audience tier of the token, defaults to all configured audiences
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audience: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scope: Vec<Scope>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(with = "humantime_serde")]
+    pub valid_for: Option<time::Duration>,
+    /// Create a token which can be used only once to create another token
+    #[serde(default)]
+    pub one_time: bool,
+    /// Name of the application the token is issued for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_version: Option<String>,
+}
@@ -0,0 +1,49 @@
+use crate::tls::TlsOptions;
+
+use std::{path::PathBuf, sync::Arc};
+
+use futures::StreamExt;
+use rustls::ServerConfig;
+use rustls_acme::{caches::DirCache, AcmeConfig};
+use tracing::{error, info};
+
+#[derive(Debug)]
+pub struct AcmeOptions {
+    pub domains: Vec<String>,
+    /// Contact email addresses of the account
+    pub contact: Vec<String>,
+    /// Directory of the account key and certificates
+    pub cache_dir: Option<PathBuf>,
+    /// Use the Let's Encrypt staging environment
+    pub staging: bool,
+    pub tls: TlsOptions,
+}
+
+impl AcmeOptions {
+    /// Starts obtaining and renewing the certificate in the background.
+    ///
+    /// Returns the server config and the config answering TLS-ALPN-01 challenges.
+    pub fn provision(self) -> Result<(Arc<ServerConfig>, Arc<ServerConfig>), rustls::Error> {
+        let mut state = AcmeConfig::new(self.domains)
+            .contact(self.contact.iter().map(|c| format!("mailto:{}", c)))
+            .cache_option(self.cache_dir.map(DirCache::new))
+            .directory_lets_encrypt(!self.staging)
+            .state();
+
+        let mut config = self.tls.builder()?.with_cert_resolver(state.resolver());
+        config.alpn_protocols = self.tls.alpn_protocols();
+
+        let challenge = state.challenge_rustls_config();
+
+        tokio::spawn(async move {
+            while let Some(event) = state.next().await {
+                match event {
+                    Ok(ok) => info!(event = ?ok, "ACME event"),
+                    Err(err) => error!(error = ?err, "ACME error"),
+                }
+            }
+        });
+
+        Ok((Arc::new(config), challenge))
+    }
+}
@@ -0,0 +1,500 @@
+use crate::{
+    allocator::{self, AllocatorStats},
+    authentication::AuthenticationError,
+    extract::{Json, Query, TokenData},
+    maintenance::{Maintenance, MaintenanceWindow},
+    model::{Response, Status},
+    search::{
+        CacheStats, KindBoostMap, KindBoosts, Ranking, RefinementStore, ResponseCache, SearchError,
+        SlowQuery, SlowQueryLog,
+    },
+    token::{Claims, Scope},
+};
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse},
+};
+use chrono::Utc;
+use hyper::StatusCode;
+use search_index::{
+    language_code, language_from_code, DocType, Explained, GenerationDiff, NameAnalyzer,
+    QueryOptions,
+};
+use search_state::{DryRunReport, IndexState, UpdateTrigger};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+const fn default_playground_limit() -> usize {
+    10
+}
+
+const fn default_optimize_segments() -> usize {
+    1
+}
+
+fn default_maintenance_message() -> String {
+    "service is under maintenance, try again later".to_string()
+}
+
+const DASHBOARD: &str = include_str!("../../assets/admin/index.html");
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexStats {
+    language: &'static str,
+    documents: u64,
+    commits: u64,
+    last_opstamp: u64,
+    last_documents: usize,
+    last_skipped: usize,
+    /// Documents of the last write left as they are
+    last_unchanged: usize,
+    last_commit_ms: f64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeCountResponse {
+    r#type: DocType,
+    before: u64,
+    after: u64,
+    delta: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SmokeQueryResponse {
+    query: String,
+    hits_before: usize,
+    hits_after: usize,
+    passed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanguageDryRunResponse {
+    language: &'static str,
+    documents_before: u64,
+    documents_after: u64,
+    delta: i64,
+    types: Vec<TypeCountResponse>,
+    skipped: usize,
+    skipped_documents: Vec<String>,
+    healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    health_error: Option<String>,
+    smoke_queries: Vec<SmokeQueryResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunResponse {
+    passed: bool,
+    items: usize,
+    languages: Vec<LanguageDryRunResponse>,
+    took_ms: f64,
+}
+
+impl From<DryRunReport> for DryRunResponse {
+    fn from(report: DryRunReport) -> Self {
+        let delta = |before: u64, after: u64| after as i64 - before as i64;
+
+        Self {
+            passed: report.passed(),
+            items: report.items,
+            took_ms: report.duration.as_secs_f64() * 1000.0,
+            languages: report
+                .languages
+                .into_iter()
+                .map(|l| LanguageDryRunResponse {
+                    language: language_code(l.language),
+                    documents_before: l.documents_before,
+                    documents_after: l.documents_after,
+                    delta: delta(l.documents_before, l.documents_after),
+                    types: l
+                        .types
+                        .into_iter()
+                        .map(|t| TypeCountResponse {
+                            r#type: t.doc_type,
+                            before: t.before,
+                            after: t.after,
+                            delta: delta(t.before, t.after),
+                        })
+                        .collect(),
+                    skipped: l.batch.skipped,
+                    skipped_documents: l.batch.errors.iter().map(|e| e.to_string()).collect(),
+                    healthy: l.health_error.is_none(),
+                    smoke_queries: l
+                        .smoke_queries
+                        .into_iter()
+                        .map(|q| SmokeQueryResponse {
+                            passed: q.passed(),
+                            query: q.query,
+                            hits_before: q.hits_before,
+                            hits_after: q.hits_after,
+                            error: q.error,
+                        })
+                        .collect(),
+                    health_error: l.health_error,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexDiff {
+    language: &'static str,
+    #[serde(flatten)]
+    diff: GenerationDiff,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatsResponse {
+    indexes: Vec<IndexStats>,
+    slow_queries: Vec<SlowQuery>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allocator: Option<AllocatorStats>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReindexParams {
+    /// Reports what the reindex would change instead of applying it
+    #[serde(default)]
+    dry_run: bool,
+}
+
+pub async fn reindex(
+    TokenData(claims): TokenData<Claims, true>,
+    Query(params): Query<ReindexParams>,
+    State(trigger): State<UpdateTrigger>,
+) -> crate::Result<axum::response::Response> {
+    if !claims.has_scope(&Scope::Admin) {
+        return Err(AuthenticationError::InsufficientPermission.into());
+    }
+
+    if !params.dry_run {
+        trigger.reindex();
+        return Ok(Status::new(StatusCode::ACCEPTED, "reindex scheduled").into_response());
+    }
+
+    let res = match trigger.dry_run().await {
+        Some(Ok(report)) => {
+            let report = DryRunResponse::from(report);
+            info!(
+                subject = claims.subject(),
+                passed = report.passed,
+                items = report.items,
+                "Reindex dry run finished"
+            );
+            Response::new(report).into_response()
+        }
+        Some(Err(e)) => {
+            Status::new(StatusCode::BAD_GATEWAY, format!("dry run failed: {}", e)).into_response()
+        }
+        None => Status::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "index handler isn't running",
+        )
+        .into_response(),
+    };
+
+    Ok(res)
+}
+
+/// Changes of the documents by the last sync of each index
+pub async fn index_diff(
+    TokenData(claims): TokenData<Claims, true>,
+    State(state): State<IndexState>,
+) -> crate::Result<Response<Vec<IndexDiff>>> {
+    if !claims.has_scope(&Scope::Admin) {
+        return Err(AuthenticationError::InsufficientPermission.into());
+    }
+
+    let diffs = state
+        .languages()
+        .into_iter()
+        .filter_map(|lang| state.get_index_by_lang(lang))
+        .map(|index| IndexDiff {
+            language: language_code(index.language()),
+            diff: index.commit_stats().last_diff,
+        })
+        .collect();
+
+    Ok(Response::new(diffs))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OptimizeParams {
+    /// Number of segments the indexes are merged down to
+    #[serde(default = "default_optimize_segments")]
+    segments: usize,
+}
+
+pub async fn optimize(
+    TokenData(claims): TokenData<Claims, true>,
+    Query(params): Query<OptimizeParams>,
+    State(state): State<IndexState>,
+) -> crate::Result<Status> {
+    if !claims.has_scope(&Scope::Admin) {
+        return Err(AuthenticationError::InsufficientPermission.into());
+    }
+
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = state.optimize(params.segments) {
+            error!(error = %e, "Index optimization failed");
+        }
+    });
+
+    Ok(Status::new(StatusCode::ACCEPTED, "optimization scheduled"))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceBody {
+    /// Message returned to rejected requests
+    #[serde(default = "default_maintenance_message")]
+    message: String,
+    #[serde(default, with = "humantime_serde")]
+    retry_after: Option<Duration>,
+}
+
+pub async fn maintenance(
+    TokenData(claims): TokenData<Claims, true>,
+    State(maintenance): State<Arc<Maintenance>>,
+) -> crate::Result<Response<Option<MaintenanceWindow>>> {
+    if !claims.has_scope(&Scope::Admin) {
+        return Err(AuthenticationError::InsufficientPermission.into());
+    }
+
+    Ok(Response::new(maintenance.current()))
+}
+
+pub async fn start_maintenance(
+    TokenData(claims): TokenData<Claims, true>,
+    State(maintenance): State<Arc<Maintenance>>,
+    Json(body): Json<MaintenanceBody>,
+) -> crate::Result<Response<MaintenanceWindow>> {
+    if !claims.has_scope(&Scope::Admin) {
+        return Err(AuthenticationError::InsufficientPermission.into());
+    }
+
+    let window = MaintenanceWindow {
+        message: body.message,
+        since: Utc::now(),
+        retry_after: body.retry_after,
+        started_by: claims.subject().to_string(),
+    };
+    maintenance.start(window.clone());
+
+    info!(
+        subject = claims.subject(),
+        message = %window.message,
+        retry_after = ?window.retry_after,
+        "Maintenance started"
+    );
+
+    Ok(Response::new(window))
+}
+
+pub async fn end_maintenance(
+    TokenData(claims): TokenData<Claims, true>,
+    State(maintenance): State<Arc<Maintenance>>,
+) -> crate::Result<Status> {
+    if !claims.has_scope(&Scope::Admin) {
+        return Err(AuthenticationError::InsufficientPermission.into());
+    }
+
+    match maintenance.end() {
+        Some(w) => {
+            info!(
+                subject = claims.subject(),
+                since = %w.since,
+                "Maintenance ended"
+            );
+            Ok(Status::new(StatusCode::OK, "maintenance ended"))
+        }
+        None => Ok(Status::new(StatusCode::OK, "no maintenance active")),
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStatsResponse {
+    responses: CacheStats,
+    refinements: CacheStats,
+}
+
+pub async fn cache_stats(
+    TokenData(claims): TokenData<Claims, true>,
+    State(cache): State<Arc<ResponseCache>>,
+    State(refinements): State<Arc<RefinementStore>>,
+) -> crate::Result<Response<CacheStatsResponse>> {
+    if !claims.has_scope(&Scope::Admin) {
+        return Err(AuthenticationError::InsufficientPermission.into());
+    }
+
+    Ok(Response::new(CacheStatsResponse {
+        responses: cache.stats(),
+        refinements: refinements.stats(),
+    }))
+}
+
+pub async fn clear_cache(
+    TokenData(claims): TokenData<Claims, true>,
+    State(cache): State<Arc<ResponseCache>>,
+    State(refinements): State<Arc<RefinementStore>>,
+) -> crate::Result<Status> {
+    if !claims.has_scope(&Scope::Admin) {
+        return Err(AuthenticationError::InsufficientPermission.into());
+    }
+
+    let responses = cache.clear();
+    let refined = refinements.clear();
+
+    info!(
+        subject = claims.subject(),
+        responses,
+        refinements = refined,
+        "Caches cleared"
+    );
+
+    Ok(Status::new(
+        StatusCode::OK,
+        format!("{} cache entries cleared", responses + refined),
+    ))
+}
+
+pub async fn kind_boosts(
+    TokenData(claims): TokenData<Claims, true>,
+    State(kind_boosts): State<Arc<KindBoosts>>,
+) -> crate::Result<Response<KindBoostMap>> {
+    if !claims.has_scope(&Scope::Admin) {
+        return Err(AuthenticationError::InsufficientPermission.into());
+    }
+
+    Ok(Response::new(kind_boosts.current().as_ref().clone()))
+}
+
+pub async fn set_kind_boosts(
+    TokenData(claims): TokenData<Claims, true>,
+    State(kind_boosts): State<Arc<KindBoosts>>,
+    Json(body): Json<KindBoostMap>,
+) -> crate::Result<Response<KindBoostMap>> {
+    if !claims.has_scope(&Scope::Admin) {
+        return Err(AuthenticationError::InsufficientPermission.into());
+    }
+
+    let previous = kind_boosts.replace(body)?;
+    let current = kind_boosts.current();
+
+    info!(
+        subject = claims.subject(),
+        previous = ?previous,
+        current = ?current,
+        "Kind boosts changed"
+    );
+
+    Ok(Response::new(current.as_ref().clone()))
+}
+
+pub async fn stats(
+    TokenData(claims): TokenData<Claims, true>,
+    State(state): State<IndexState>,
+    State(slow_queries): State<Arc<SlowQueryLog>>,
+) -> crate::Result<Response<StatsResponse>> {
+    if !claims.has_scope(&Scope::Admin) {
+        return Err(AuthenticationError::InsufficientPermission.into());
+    }
+
+    let indexes = state
+        .languages()
+        .into_iter()
+        .filter_map(|lang| state.get_index_by_lang(lang))
+        .map(|index| {
+            let commits = index.commit_stats();
+
+            IndexStats {
+                language: language_code(index.language()),
+                documents: index.num_docs(),
+                commits: commits.commits,
+                last_opstamp: commits.last_opstamp,
+                last_documents: commits.last_documents,
+                last_skipped: commits.last_batch.skipped,
+                last_unchanged: commits.last_batch.unchanged,
+                last_commit_ms: commits.last_duration.as_secs_f64() * 1000.0,
+            }
+        })
+        .collect();
+
+    Ok(Response::new(StatsResponse {
+        indexes,
+        slow_queries: slow_queries.recent(),
+        allocator: allocator::stats(),
+    }))
+}
+
+/// Serves the dashboard without authentication, the API calls it makes use the
+/// token entered on the page
+pub async fn ui() -> Html<&'static str> {
+    Html(DASHBOARD)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaygroundRequest {
+    query: String,
+    r#type: Option<DocType>,
+    #[serde(default = "default_playground_limit")]
+    limit: usize,
+    conjunction: Option<bool>,
+    /// Maximum edit distance of name terms
+    fuzzy: Option<u8>,
+    lang: Option<String>,
+}
+
+pub async fn playground(
+    TokenData(claims): TokenData<Claims, true>,
+    State(state): State<IndexState>,
+    State(kind_boosts): State<Arc<KindBoosts>>,
+    State(ranking): State<Arc<Ranking>>,
+    Json(body): Json<PlaygroundRequest>,
+) -> crate::Result<Response<Explained>> {
+    if !claims.has_scope(&Scope::Admin) {
+        return Err(AuthenticationError::InsufficientPermission.into());
+    }
+
+    let index = match body.lang {
+        Some(code) => language_from_code(&code)
+            .and_then(|l| state.get_index_by_lang(l))
+            .ok_or(SearchError::UnsupportedLanguage)?,
+        None => state.get_index(),
+    };
+
+    let ranking = ranking.current();
+    let opts = QueryOptions {
+        limit: body.limit,
+        conjunction: body.conjunction.unwrap_or_else(|| ranking.conjunction()),
+        boosts: ranking.field_boosts(),
+        filters: Vec::new(),
+        lenient: false,
+        kind_boosts: kind_boosts.to_options(),
+        type_boosts: ranking.type_boosts.clone(),
+        fuzzy: body.fuzzy,
+        name_analyzer: NameAnalyzer::default(),
+    };
+
+    let explained =
+        tokio::task::spawn_blocking(move || index.explain(&body.query, body.r#type, opts))
+            .await?
+            .map_err(SearchError::IndexError)?;
+
+    Ok(Response::new(explained))
+}
@@ -0,0 +1,33 @@
+use crate::AppState;
+
+use super::handler;
+
+use axum::routing::{delete, get, post};
+
+/// Admin routes, the dashboard is only served if `ui` is enabled
+pub fn routes(ui: bool) -> axum::Router<AppState> {
+    let router = axum::Router::new()
+        .route("/reindex", post(handler::reindex))
+        .route("/index/optimize", post(handler::optimize))
+        .route("/index/diff", get(handler::index_diff))
+        .route(
+            "/maintenance",
+            get(handler::maintenance)
+                .put(handler::start_maintenance)
+                .delete(handler::end_maintenance),
+        )
+        .route(
+            "/ranking/kinds",
+            get(handler::kind_boosts).put(handler::set_kind_boosts),
+        )
+        .route("/cache", delete(handler::clear_cache))
+        .route("/cache/stats", get(handler::cache_stats))
+        .route("/stats", get(handler::stats))
+        .route("/playground", post(handler::playground));
+
+    if ui {
+        router.route("/ui", get(handler::ui))
+    } else {
+        router
+    }
+}
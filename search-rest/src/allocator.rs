@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use metrics::gauge;
+use serde::Serialize;
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Memory statistics of the jemalloc allocator
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AllocatorStats {
+    allocated: u64,
+    resident: u64,
+    /// Share of resident memory not allocated by the application
+    fragmentation: f64,
+}
+
+impl AllocatorStats {
+    pub fn resident(&self) -> u64 {
+        self.resident
+    }
+}
+
+/// Returns the current allocator statistics, if jemalloc is enabled
+#[cfg(feature = "jemalloc")]
+pub fn stats() -> Option<AllocatorStats> {
+    use jemalloc_ctl::{epoch, stats};
+
+    // Statistics are cached until the epoch is advanced
+    epoch::advance().ok()?;
+
+    let allocated = stats::allocated::read().ok()? as u64;
+    let resident = stats::resident::read().ok()? as u64;
+    let fragmentation = if resident > 0 {
+        1.0 - allocated as f64 / resident as f64
+    } else {
+        0.0
+    };
+
+    Some(AllocatorStats {
+        allocated,
+        resident,
+        fragmentation,
+    })
+}
+
+#[cfg(not(feature = "jemalloc"))]
+pub fn stats() -> Option<AllocatorStats> {
+    None
+}
+
+/// Periodically reports the allocator statistics as gauges
+pub async fn report() {
+    let mut interval = tokio::time::interval(REPORT_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let stats = match stats() {
+            Some(s) => s,
+            None => return,
+        };
+
+        gauge!("search_allocator_allocated_bytes", stats.allocated as f64);
+        gauge!("search_allocator_resident_bytes", stats.resident as f64);
+        gauge!("search_allocator_fragmentation_ratio", stats.fragmentation);
+    }
+}
@@ -0,0 +1,54 @@
+use crate::{
+    quota::QuotaLimits,
+    token::{Claims, Scope},
+};
+
+use std::{fs, path::Path};
+
+use serde::Deserialize;
+
+/// Token audience with its own default scopes and quotas
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudienceTier {
+    pub name: String,
+    /// Scopes of created tokens if none are requested
+    #[serde(default)]
+    pub scopes: Vec<Scope>,
+    /// Quotas replacing the global quotas
+    pub quota: Option<QuotaLimits>,
+    /// Maximum result count replacing the global maximum
+    pub max_limit: Option<usize>,
+}
+
+#[derive(Debug, Default)]
+pub struct AudienceTiers(Vec<AudienceTier>);
+
+impl AudienceTiers {
+    pub fn new(tiers: Vec<AudienceTier>) -> Self {
+        Self(tiers)
+    }
+
+    pub fn from_file<P>(path: P) -> crate::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = fs::read(path)?;
+        let tiers = serde_json::from_slice(&file)?;
+
+        Ok(Self::new(tiers))
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|t| t.name.as_str())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AudienceTier> {
+        self.0.iter().find(|t| t.name == name)
+    }
+
+    /// Returns the first tier matching an audience of the token
+    pub fn resolve(&self, claims: &Claims) -> Option<&AudienceTier> {
+        claims.audience().iter().find_map(|a| self.get(a))
+    }
+}
@@ -1,12 +1,38 @@
-use crate::{error, model::Status};
+use crate::{
+    error,
+    model::Status,
+    token::{Claims, RevocationList},
+};
+
+use std::{
+    borrow::Cow,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
 
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
 use hyper::StatusCode;
 use jsonwebtoken::{
     errors::{Error as JwtError, ErrorKind},
     Algorithm, DecodingKey, EncodingKey, Validation,
 };
+use metrics::increment_counter;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    digest::{digest, SHA256},
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use secrecy::ExposeSecret;
 use serde::{de::DeserializeOwned, Serialize};
-use tracing::error;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::broadcast::Receiver,
+};
+use tracing::{error, info};
+use zeroize::Zeroizing;
 
 #[derive(Debug, thiserror::Error)]
 pub enum AuthenticationError {
@@ -16,6 +42,8 @@ pub enum AuthenticationError {
     InvalidHeader(String),
     #[error("Insufficient permission")]
     InsufficientPermission,
+    #[error("Access denied: {0}")]
+    Denied(String),
     #[error("User is blocked")]
     LockedUser,
     #[error("User doesn't exist")]
@@ -34,6 +62,7 @@ impl error::ErrorResponse for AuthenticationError {
             | AuthenticationError::Token(_) => StatusCode::UNAUTHORIZED,
             AuthenticationError::LockedUser
             | AuthenticationError::InsufficientPermission
+            | AuthenticationError::Denied(_)
             | AuthenticationError::UnknownUser => StatusCode::FORBIDDEN,
         }
     }
@@ -51,8 +80,26 @@ pub enum TokenError {
     Immature,
     #[error("token is invalid")]
     Invalid,
+    #[error("token is missing the claim {0}")]
+    MissingClaim(String),
+    #[error("unknown audience")]
+    UnknownAudience,
+    #[error("token was already used")]
+    Replayed,
+    #[error("token is not a one-time token")]
+    NotOneTime,
+    #[error("one-time tokens can't be renewed")]
+    NotRenewable,
+    #[error("token was revoked")]
+    Revoked,
+    #[error("requested scopes exceed the scopes of the token")]
+    ScopeEscalation,
+    #[error("client metadata is too long")]
+    InvalidClientMetadata,
     #[error("Token could not be encoded: {0}")]
     EncodingFailed(JwtError),
+    #[error("Token could not be encrypted")]
+    EncryptionFailed,
 }
 
 impl From<JwtError> for TokenError {
@@ -60,7 +107,8 @@ impl From<JwtError> for TokenError {
         match *error.kind() {
             ErrorKind::ExpiredSignature => Self::Expired,
             ErrorKind::ImmatureSignature => Self::Immature,
-            ErrorKind::InvalidToken => Self::Invalid,
+            ErrorKind::InvalidToken | ErrorKind::InvalidIssuer => Self::Invalid,
+            ErrorKind::MissingRequiredClaim(ref claim) => Self::MissingClaim(claim.clone()),
             _ => {
                 error!(error = ?error, "JWT error");
                 Self::Invalid
@@ -77,7 +125,18 @@ impl error::ErrorResponse for TokenError {
             TokenError::Expired => StatusCode::UNAUTHORIZED,
             TokenError::Immature => StatusCode::UNAUTHORIZED,
             TokenError::Invalid => StatusCode::UNAUTHORIZED,
-            TokenError::EncodingFailed(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            TokenError::MissingClaim(_) => StatusCode::UNAUTHORIZED,
+            TokenError::UnknownAudience | TokenError::InvalidClientMetadata => {
+                StatusCode::BAD_REQUEST
+            }
+            TokenError::Replayed
+            | TokenError::NotOneTime
+            | TokenError::NotRenewable
+            | TokenError::Revoked => StatusCode::UNAUTHORIZED,
+            TokenError::ScopeEscalation => StatusCode::FORBIDDEN,
+            TokenError::EncodingFailed(_) | TokenError::EncryptionFailed => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
         }
     }
 
@@ -100,32 +159,117 @@ where
             config.validation.clone()
         };
 
-        let data = jsonwebtoken::decode::<Self>(token, &config.dec_key, &validation)?;
+        let token = match &config.encryption {
+            Some(enc) if token.split('.').count() == 5 => Cow::Owned(enc.decrypt(token)?),
+            Some(enc) if !enc.accept_plain => return Err(TokenError::Invalid),
+            _ => Cow::Borrowed(token),
+        };
+
+        // Keys are tried in order, the current key first
+        let keys = config.keys.read().unwrap();
+        let mut result = Err(TokenError::Invalid);
+        for (i, key) in keys.dec_keys.iter().enumerate() {
+            match jsonwebtoken::decode::<Self>(&token, key, &validation) {
+                Ok(data) => {
+                    increment_counter!("search_token_decoding_key_matches_total", "key" => i.to_string());
+
+                    let required = &config.validation.required_spec_claims;
+                    if required.contains("sub")
+                        && data.claims.issued().is_some_and(|(sub, _)| sub.is_empty())
+                    {
+                        return Err(TokenError::MissingClaim("sub".to_string()));
+                    }
+
+                    if let Some((sub, iat)) = data.claims.issued() {
+                        if config.revocations.is_revoked(sub, iat) {
+                            return Err(TokenError::Revoked);
+                        }
+                    }
+
+                    return Ok(data.claims);
+                }
+                Err(e) if matches!(e.kind(), ErrorKind::InvalidSignature) => {
+                    result = Err(e.into());
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        result
+    }
+
+    /// Subject and issue time, used to check revocations
+    fn issued(&self) -> Option<(&str, DateTime<Utc>)> {
+        None
+    }
+
+    /// Name and version of the client the token was issued for
+    fn client(&self) -> (Option<&str>, Option<&str>) {
+        (None, None)
+    }
 
-        Ok(data.claims)
+    /// Claims passed to the authorizer, requests aren't authorized without
+    fn authorization_claims(&self) -> Option<&Claims> {
+        None
     }
 
     fn encode(&self, config: &TokenConfig) -> Result<String, TokenError> {
         let header = jsonwebtoken::Header::new(config.alg);
-        let token = jsonwebtoken::encode(&header, self, &config.enc_key).map_err(|e| {
+        let enc_key = &config.keys.read().unwrap().enc_key;
+        let token = jsonwebtoken::encode(&header, self, enc_key).map_err(|e| {
             error!(error = ?e, "Error while encoding token");
             TokenError::EncodingFailed(e)
         })?;
 
-        Ok(token)
+        match &config.encryption {
+            Some(enc) => enc.encrypt(&token),
+            None => Ok(token),
+        }
+    }
+}
+
+/// Keys of the secrets, replaced when the secret is reloaded
+struct SigningKeys {
+    enc_key: EncodingKey,
+    /// Decoding keys of the current and previous secrets
+    dec_keys: Vec<DecodingKey>,
+    /// HMAC keys of the current and previous secrets, used for signed links
+    hmac_keys: Vec<hmac::Key>,
+    /// Digest of the current secret to detect changes
+    digest: Vec<u8>,
+}
+
+impl SigningKeys {
+    fn from_secret(secret: &[u8]) -> Self {
+        Self {
+            enc_key: EncodingKey::from_secret(secret),
+            dec_keys: vec![DecodingKey::from_secret(secret)],
+            hmac_keys: vec![hmac::Key::new(hmac::HMAC_SHA256, secret)],
+            digest: digest(&SHA256, secret).as_ref().to_vec(),
+        }
     }
 }
 
 #[derive(Clone)]
 pub struct TokenConfig {
     pub alg: Algorithm,
-    pub enc_key: EncodingKey,
-    pub dec_key: DecodingKey,
+    keys: Arc<RwLock<SigningKeys>>,
     pub validation: Validation,
+    pub encryption: Option<TokenEncryption>,
+    pub revocations: Arc<RevocationList>,
+    /// Issuer set in created tokens
+    pub issuer: Option<String>,
 }
 
 impl TokenConfig {
-    const LEEWAY: u64 = 10;
+    pub const DEFAULT_LEEWAY: Duration = Duration::from_secs(10);
+    pub const DEFAULT_REQUIRED_CLAIMS: [&'static str; 4] = ["exp", "sub", "aud", "iat"];
+
+    /// Maximum number of previous secrets kept after reloads
+    const MAX_PREVIOUS_SECRETS: usize = 8;
+
+    /// Claims which can be required
+    const SPEC_CLAIMS: [&'static str; 6] = ["exp", "nbf", "iat", "sub", "aud", "iss"];
 
     pub fn from_secret<S, A, T>(secret: S, audience: A) -> Self
     where
@@ -134,14 +278,318 @@ impl TokenConfig {
         T: ToString,
     {
         let mut validation = Validation::default();
-        validation.leeway = Self::LEEWAY;
+        validation.leeway = Self::DEFAULT_LEEWAY.as_secs();
         validation.set_audience(audience.as_ref());
+        validation.set_required_spec_claims(&Self::DEFAULT_REQUIRED_CLAIMS);
 
         Self {
             alg: Algorithm::HS256,
-            enc_key: EncodingKey::from_secret(secret.as_ref()),
-            dec_key: DecodingKey::from_secret(secret.as_ref()),
+            keys: Arc::new(RwLock::new(SigningKeys::from_secret(secret.as_ref()))),
             validation,
+            encryption: None,
+            revocations: Arc::default(),
+            issuer: None,
+        }
+    }
+
+    /// Tolerance of the expiration and not-before times
+    pub fn with_leeway(mut self, leeway: Duration) -> Self {
+        self.validation.leeway = leeway.as_secs();
+        self
+    }
+
+    /// Rejects tokens missing one of the claims, `iat` is always required
+    /// by the claims of issued tokens
+    pub fn with_required_claims<T>(mut self, claims: &[T]) -> Result<Self, String>
+    where
+        T: AsRef<str> + ToString,
+    {
+        if let Some(claim) = claims
+            .iter()
+            .find(|c| !Self::SPEC_CLAIMS.contains(&c.as_ref()))
+        {
+            return Err(format!("unsupported claim {}", claim.as_ref()));
+        }
+
+        self.validation.set_required_spec_claims(claims);
+        if self.issuer.is_some() {
+            self.validation
+                .required_spec_claims
+                .insert("iss".to_string());
+        }
+
+        Ok(self)
+    }
+
+    /// Sets the issuer of created tokens and accepts only tokens of it
+    pub fn with_issuer(mut self, issuer: String) -> Self {
+        self.validation.set_issuer(&[&issuer]);
+        self.validation
+            .required_spec_claims
+            .insert("iss".to_string());
+        self.issuer = Some(issuer);
+        self
+    }
+
+    /// Accepts tokens signed with previous secrets, tried in the given order
+    pub fn with_previous_secrets<I, S>(self, secrets: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<[u8]>,
+    {
+        let mut keys = self.keys.write().unwrap();
+        for secret in secrets {
+            keys.dec_keys
+                .push(DecodingKey::from_secret(secret.as_ref()));
+            keys.hmac_keys
+                .push(hmac::Key::new(hmac::HMAC_SHA256, secret.as_ref()));
+        }
+        drop(keys);
+
+        self
+    }
+
+    /// Signs tokens with the new secret, the current secret is kept as
+    /// previous secret so issued tokens remain valid.
+    ///
+    /// Returns false if the secret didn't change.
+    pub fn rotate_secret(&self, secret: &[u8]) -> bool {
+        let mut keys = self.keys.write().unwrap();
+
+        let new = SigningKeys::from_secret(secret);
+        if new.digest == keys.digest {
+            return false;
+        }
+
+        let previous = std::mem::replace(&mut *keys, new);
+        keys.dec_keys.extend(
+            previous
+                .dec_keys
+                .into_iter()
+                .take(Self::MAX_PREVIOUS_SECRETS),
+        );
+        keys.hmac_keys.extend(
+            previous
+                .hmac_keys
+                .into_iter()
+                .take(Self::MAX_PREVIOUS_SECRETS),
+        );
+
+        true
+    }
+
+    /// Returns the HMAC-SHA256 of the message with the current secret,
+    /// encoded as URL-safe base64
+    pub fn sign(&self, message: &[u8]) -> String {
+        let keys = self.keys.read().unwrap();
+        let tag = hmac::sign(&keys.hmac_keys[0], message);
+
+        URL_SAFE_NO_PAD.encode(tag.as_ref())
+    }
+
+    /// Returns true if the signature is of the current or a previous secret
+    pub fn verify(&self, message: &[u8], signature: &str) -> bool {
+        let Ok(tag) = URL_SAFE_NO_PAD.decode(signature) else {
+            return false;
+        };
+
+        let keys = self.keys.read().unwrap();
+        keys.hmac_keys
+            .iter()
+            .any(|k| hmac::verify(k, message, &tag).is_ok())
+    }
+
+    pub fn with_revocations(mut self, revocations: Arc<RevocationList>) -> Self {
+        self.revocations = revocations;
+        self
+    }
+
+    /// Encrypts issued tokens as JWE with the given key
+    pub fn with_encryption(mut self, encryption: TokenEncryption) -> Self {
+        self.encryption = Some(encryption);
+        self
+    }
+}
+
+/// Replaces the secret with the content of the file on every SIGHUP
+pub async fn reload_secret_on_hangup(
+    config: TokenConfig,
+    path: PathBuf,
+    mut shutdown: Receiver<()>,
+) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(s) => s,
+        Err(e) => {
+            error!(error = %e, "Couldn't listen for SIGHUP, the JWT secret won't be reloaded");
+            return;
+        }
+    };
+
+    loop {
+        tokio::select! {
+            _ = hangup.recv() => {}
+            _ = shutdown.recv() => break,
+        }
+
+        match crate::utils::read_secret(&path) {
+            Ok(secret) if config.rotate_secret(secret.expose_secret().as_bytes()) => {
+                info!("JWT secret reloaded");
+            }
+            Ok(_) => {}
+            Err(e) => {
+                error!(error = %e, path = %path.display(), "Couldn't read the JWT secret file");
+            }
         }
     }
 }
+
+/// JWE encryption of signed tokens with direct key agreement (`dir`) and `A256GCM`
+#[derive(Clone)]
+pub struct TokenEncryption {
+    key: Arc<LessSafeKey>,
+    rng: SystemRandom,
+    /// Accepts signed tokens without encryption, issued before it was enabled
+    accept_plain: bool,
+}
+
+impl TokenEncryption {
+    const HEADER: &'static str = r#"{"alg":"dir","enc":"A256GCM","cty":"JWT"}"#;
+
+    /// Creates the encryption from a base64 encoded 256-bit key
+    pub fn from_base64(key: &str) -> Result<Self, String> {
+        let key = base64::engine::general_purpose::STANDARD
+            .decode(key.trim())
+            .map(Zeroizing::new)
+            .map_err(|e| e.to_string())?;
+        let key = UnboundKey::new(&AES_256_GCM, &key).map_err(|_| "key must be 256 bits")?;
+
+        Ok(Self {
+            key: Arc::new(LessSafeKey::new(key)),
+            rng: SystemRandom::new(),
+            accept_plain: false,
+        })
+    }
+
+    /// Accepts unencrypted tokens as well, to migrate tokens issued before
+    /// the encryption was enabled
+    pub fn with_plain_tokens(mut self, accept: bool) -> Self {
+        self.accept_plain = accept;
+        self
+    }
+
+    fn encrypt(&self, token: &str) -> Result<String, TokenError> {
+        let header = URL_SAFE_NO_PAD.encode(Self::HEADER);
+
+        let mut iv = [0u8; NONCE_LEN];
+        self.rng
+            .fill(&mut iv)
+            .map_err(|_| TokenError::EncryptionFailed)?;
+
+        let mut data = token.as_bytes().to_vec();
+        let tag = self
+            .key
+            .seal_in_place_separate_tag(
+                Nonce::assume_unique_for_key(iv),
+                Aad::from(header.as_bytes()),
+                &mut data,
+            )
+            .map_err(|_| TokenError::EncryptionFailed)?;
+
+        Ok(format!(
+            "{}..{}.{}.{}",
+            header,
+            URL_SAFE_NO_PAD.encode(iv),
+            URL_SAFE_NO_PAD.encode(data),
+            URL_SAFE_NO_PAD.encode(tag.as_ref()),
+        ))
+    }
+
+    fn decrypt(&self, token: &str) -> Result<String, TokenError> {
+        let parts = token.split('.').collect::<Vec<_>>();
+        let [header, key, iv, data, tag] = parts[..] else {
+            return Err(TokenError::Invalid);
+        };
+
+        if !key.is_empty()
+            || URL_SAFE_NO_PAD.decode(header).ok().as_deref() != Some(Self::HEADER.as_bytes())
+        {
+            return Err(TokenError::Invalid);
+        }
+
+        let decode = |v: &str| URL_SAFE_NO_PAD.decode(v).map_err(|_| TokenError::Invalid);
+        let iv: [u8; NONCE_LEN] = decode(iv)?.try_into().map_err(|_| TokenError::Invalid)?;
+        let mut data = decode(data)?;
+        data.extend(decode(tag)?);
+
+        let plain = self
+            .key
+            .open_in_place(
+                Nonce::assume_unique_for_key(iv),
+                Aad::from(header.as_bytes()),
+                &mut data,
+            )
+            .map_err(|_| TokenError::Invalid)?;
+
+        String::from_utf8(plain.to_vec()).map_err(|_| TokenError::Invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::token::Scope;
+
+    /// Base64 encoded 256-bit key
+    const KEY: &str = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=";
+
+    fn encrypted_config(encryption: TokenEncryption) -> TokenConfig {
+        TokenConfig::from_secret("secret", ["test"]).with_encryption(encryption)
+    }
+
+    fn claims() -> Claims {
+        Claims::new(["test".to_string()], "user", [Scope::Search])
+    }
+
+    #[test]
+    fn encrypted_token_round_trip() {
+        let config = encrypted_config(TokenEncryption::from_base64(KEY).unwrap());
+
+        let token = claims().encode(&config).unwrap();
+        assert_eq!(token.split('.').count(), 5);
+
+        let decoded = Claims::decode(&token, &config, true).unwrap();
+        assert_eq!(decoded.subject(), "user");
+    }
+
+    #[test]
+    fn tampered_ciphertext_is_rejected() {
+        let config = encrypted_config(TokenEncryption::from_base64(KEY).unwrap());
+
+        let token = claims().encode(&config).unwrap();
+        let mut parts = token.split('.').map(str::to_owned).collect::<Vec<_>>();
+        let mut data = URL_SAFE_NO_PAD.decode(&parts[3]).unwrap();
+        data[0] ^= 1;
+        parts[3] = URL_SAFE_NO_PAD.encode(data);
+
+        let result = Claims::decode(&parts.join("."), &config, true);
+        assert!(matches!(result, Err(TokenError::Invalid)));
+    }
+
+    #[test]
+    fn plain_token_requires_migration_flag() {
+        let plain = TokenConfig::from_secret("secret", ["test"]);
+        let token = claims().encode(&plain).unwrap();
+
+        let config = encrypted_config(TokenEncryption::from_base64(KEY).unwrap());
+        let result = Claims::decode(&token, &config, true);
+        assert!(matches!(result, Err(TokenError::Invalid)));
+
+        let config = encrypted_config(
+            TokenEncryption::from_base64(KEY)
+                .unwrap()
+                .with_plain_tokens(true),
+        );
+        let decoded = Claims::decode(&token, &config, true).unwrap();
+        assert_eq!(decoded.subject(), "user");
+    }
+}
@@ -0,0 +1,101 @@
+//! Authorization of requests after the token is validated.
+//!
+//! Deployments can restrict requests beyond the scopes checked by the
+//! handlers, e.g. kinds or doc types per audience, by passing their own
+//! [`Authorizer`] to [`build_app_with_authorizer`](crate::build_app_with_authorizer).
+
+use std::sync::Arc;
+
+use hyper::Method;
+use search_models::{Claims, Scope};
+
+/// Request as seen by an [`Authorizer`]
+#[derive(Debug)]
+pub struct AuthorizationRequest<'a> {
+    pub claims: &'a Claims,
+    pub method: &'a Method,
+    /// Pattern of the matched route, e.g. `/search/:id/related`
+    pub route: &'a str,
+    /// Query parameters in order, repeated parameters aren't merged
+    pub params: &'a [(String, String)],
+}
+
+impl AuthorizationRequest<'_> {
+    /// Returns all values of the parameter, comma-separated values are split
+    pub fn param_values<'b>(&'b self, name: &'b str) -> impl Iterator<Item = &'b str> + 'b {
+        self.params
+            .iter()
+            .filter(move |(k, _)| k == name)
+            .flat_map(|(_, v)| v.split(','))
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    }
+}
+
+/// Policy invoked for every request with a valid token
+pub trait Authorizer: Send + Sync {
+    /// Returns the reason if the request is denied
+    fn authorize(&self, request: &AuthorizationRequest<'_>) -> Result<(), String>;
+}
+
+/// Shared authorizer of the app state
+pub type SharedAuthorizer = Arc<dyn Authorizer>;
+
+/// Routes requiring one of the scopes
+#[derive(Debug, Clone)]
+pub struct ScopeRule {
+    /// Route pattern or prefix of nested routes
+    pub route: String,
+    pub scopes: Vec<Scope>,
+}
+
+/// Authorizer requiring scopes by route, the most specific rule applies
+#[derive(Debug, Clone)]
+pub struct ScopeAuthorizer {
+    rules: Vec<ScopeRule>,
+}
+
+impl ScopeAuthorizer {
+    pub fn new(rules: Vec<ScopeRule>) -> Self {
+        Self { rules }
+    }
+
+    fn rule(&self, route: &str) -> Option<&ScopeRule> {
+        self.rules
+            .iter()
+            .filter(|r| {
+                route == r.route
+                    || route
+                        .strip_prefix(r.route.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            })
+            .max_by_key(|r| r.route.len())
+    }
+}
+
+impl Default for ScopeAuthorizer {
+    /// Rules matching the scope checks of the handlers
+    fn default() -> Self {
+        Self::new(vec![
+            ScopeRule {
+                route: "/admin".to_string(),
+                scopes: vec![Scope::Admin],
+            },
+            ScopeRule {
+                route: "/search/pattern".to_string(),
+                scopes: vec![Scope::Pattern],
+            },
+        ])
+    }
+}
+
+impl Authorizer for ScopeAuthorizer {
+    fn authorize(&self, request: &AuthorizationRequest<'_>) -> Result<(), String> {
+        match self.rule(request.route) {
+            Some(rule) if !rule.scopes.iter().any(|s| request.claims.has_scope(s)) => {
+                Err(format!("one of the scopes {:?} is required", rule.scopes))
+            }
+            _ => Ok(()),
+        }
+    }
+}
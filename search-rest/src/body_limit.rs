@@ -0,0 +1,46 @@
+use crate::model::Status;
+
+use axum::{
+    extract::{DefaultBodyLimit, State},
+    http::Request,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use hyper::{header::CONTENT_LENGTH, StatusCode};
+
+/// Maximum size of request bodies in bytes
+#[derive(Debug, Clone, Copy)]
+pub struct BodyLimit(pub usize);
+
+impl BodyLimit {
+    /// Rejects oversized request bodies of the routes with a `413` status.
+    ///
+    /// Bodies without a declared length are limited when read by an extractor.
+    pub fn apply<S>(self, router: Router<S>) -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        router
+            .layer(DefaultBodyLimit::max(self.0))
+            .layer(middleware::from_fn_with_state(self, enforce))
+    }
+}
+
+async fn enforce<B>(State(limit): State<BodyLimit>, req: Request<B>, next: Next<B>) -> Response {
+    let length = req
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+
+    if length.is_some_and(|l| l > limit.0) {
+        return Status::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("request body exceeds the limit of {} bytes", limit.0),
+        )
+        .into_response();
+    }
+
+    next.run(req).await
+}
@@ -0,0 +1,135 @@
+use std::{fmt, path::Path, time::Duration};
+
+/// Minimum length of the JWT secret in bytes
+const MIN_SECRET_LEN: usize = 32;
+
+/// Minimum estimated entropy of the JWT secret in bits
+const MIN_SECRET_ENTROPY: f64 = 128.0;
+
+/// Problems of the configuration, collected to report all of them at once
+#[derive(Debug, Default)]
+pub struct ConfigProblems(Vec<(String, String)>);
+
+impl ConfigProblems {
+    pub fn add<V, P>(&mut self, var: V, problem: P)
+    where
+        V: fmt::Display,
+        P: fmt::Display,
+    {
+        self.0.push((
+            format!("SEARCH_{}", var).to_uppercase(),
+            problem.to_string(),
+        ));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Checks that the file exists
+    pub fn check_file(&mut self, var: &str, path: Option<&Path>) {
+        if let Some(path) = path {
+            if !path.is_file() {
+                self.add(var, format!("file \"{}\" doesn't exist", path.display()));
+            }
+        }
+    }
+
+    /// Checks that the directory of a file exists, the file itself may be
+    /// created later
+    pub fn check_parent_dir(&mut self, var: &str, path: Option<&Path>) {
+        let dir = path
+            .and_then(Path::parent)
+            .filter(|p| !p.as_os_str().is_empty());
+
+        if let Some(dir) = dir {
+            if !dir.is_dir() {
+                self.add(
+                    var,
+                    format!("directory \"{}\" doesn't exist", dir.display()),
+                );
+            }
+        }
+    }
+
+    /// Checks that both or none of the variables are set
+    pub fn check_pair<A, B>(
+        &mut self,
+        (var_a, a): (&str, Option<A>),
+        (var_b, b): (&str, Option<B>),
+    ) {
+        match (a, b) {
+            (Some(_), None) => self.add(
+                var_b,
+                format!("must be set with SEARCH_{}", var_a.to_uppercase()),
+            ),
+            (None, Some(_)) => self.add(
+                var_a,
+                format!("must be set with SEARCH_{}", var_b.to_uppercase()),
+            ),
+            _ => {}
+        }
+    }
+
+    /// Checks that the duration is within the bounds
+    pub fn check_duration(
+        &mut self,
+        var: &str,
+        value: Duration,
+        min: Duration,
+        max: Option<Duration>,
+    ) {
+        if value < min {
+            self.add(var, format!("must be at least {min:?}"));
+        } else if let Some(max) = max.filter(|m| value > *m) {
+            self.add(var, format!("must be at most {max:?}"));
+        }
+    }
+
+    pub fn check_secret(&mut self, var: &str, secret: &str) {
+        if secret.len() < MIN_SECRET_LEN {
+            self.add(var, format!("must be at least {MIN_SECRET_LEN} bytes long"));
+        } else if entropy(secret.as_bytes()) < MIN_SECRET_ENTROPY {
+            self.add(var, "is too predictable, use a randomly generated secret");
+        }
+    }
+
+    pub fn into_result(self) -> crate::Result<()> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self.into())
+        }
+    }
+}
+
+impl fmt::Display for ConfigProblems {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid configuration:")?;
+        for (var, problem) in &self.0 {
+            write!(f, "\n  {var}: {problem}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Estimates the entropy of the data in bits from the frequency of its bytes
+fn entropy(data: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for b in data {
+        counts[*b as usize] += 1;
+    }
+
+    let len = data.len() as f64;
+    let per_byte: f64 = counts
+        .iter()
+        .filter(|c| **c > 0)
+        .map(|c| {
+            let p = *c as f64 / len;
+            -p * p.log2()
+        })
+        .sum();
+
+    per_byte * len
+}
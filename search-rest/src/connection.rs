@@ -0,0 +1,309 @@
+use std::{
+    future::Future,
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use axum::extract::connect_info::Connected;
+use futures::stream::{FuturesUnordered, StreamExt};
+use hyper::{
+    header::CONNECTION,
+    http::HeaderValue,
+    server::{accept::Accept, conn::AddrStream},
+    Request, Response,
+};
+use metrics::increment_counter;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tower::Service;
+use tracing::debug;
+
+/// Maximum duration of the setup of a connection
+const SETUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+type SetupFuture<T> = Pin<Box<dyn Future<Output = io::Result<Option<T>>> + Send>>;
+type SetupFn<C, T> = Box<dyn Fn(C) -> SetupFuture<T> + Send + Sync>;
+
+/// Accepts connections once an asynchronous setup, like a handshake, is
+/// completed. Connections are set up concurrently, failed ones or those
+/// resolving to `None` are dropped.
+pub struct SetupIncoming<A, T>
+where
+    A: Accept,
+{
+    inner: A,
+    setup: SetupFn<A::Conn, T>,
+    pending: FuturesUnordered<SetupFuture<T>>,
+}
+
+impl<A, T> SetupIncoming<A, T>
+where
+    A: Accept,
+    T: Send + 'static,
+{
+    pub fn new<F, Fut>(inner: A, setup: F) -> Self
+    where
+        F: Fn(A::Conn) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = io::Result<Option<T>>> + Send + 'static,
+    {
+        let setup: SetupFn<A::Conn, T> = Box::new(move |conn| {
+            let fut = setup(conn);
+            Box::pin(async move {
+                tokio::time::timeout(SETUP_TIMEOUT, fut)
+                    .await
+                    .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into()))
+            })
+        });
+
+        Self {
+            inner,
+            setup,
+            pending: FuturesUnordered::new(),
+        }
+    }
+}
+
+impl<A, T> Accept for SetupIncoming<A, T>
+where
+    A: Accept + Unpin,
+{
+    type Conn = T;
+    type Error = A::Error;
+
+    fn poll_accept(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        let this = self.get_mut();
+
+        loop {
+            match Pin::new(&mut this.inner).poll_accept(cx) {
+                Poll::Ready(Some(Ok(conn))) => {
+                    this.pending.push((this.setup)(conn));
+                    continue;
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => {}
+            }
+
+            match this.pending.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(Some(conn)))) => return Poll::Ready(Some(Ok(conn))),
+                Poll::Ready(Some(Ok(None))) => continue,
+                Poll::Ready(Some(Err(e))) => {
+                    debug!(error = %e, "Connection setup failed");
+                    continue;
+                }
+                Poll::Ready(None) | Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Accepts connections up to a maximum number of open ones, connections above
+/// the limit are closed immediately
+pub struct LimitedIncoming<A> {
+    inner: A,
+    max: Option<usize>,
+    open: Arc<AtomicUsize>,
+}
+
+impl<A> LimitedIncoming<A> {
+    pub fn new(inner: A, max: Option<usize>) -> Self {
+        Self {
+            inner,
+            max,
+            open: Arc::default(),
+        }
+    }
+}
+
+impl<A> Accept for LimitedIncoming<A>
+where
+    A: Accept + Unpin,
+{
+    type Conn = LimitedConn<A::Conn>;
+    type Error = A::Error;
+
+    fn poll_accept(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        loop {
+            let conn = match ready!(Pin::new(&mut self.inner).poll_accept(cx)) {
+                Some(Ok(c)) => c,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            };
+
+            let open = self.open.fetch_add(1, Ordering::SeqCst);
+            let conn = LimitedConn {
+                inner: conn,
+                open: self.open.clone(),
+            };
+
+            if self.max.is_some_and(|m| open >= m) {
+                increment_counter!("search_connections_rejected_total");
+                continue;
+            }
+
+            return Poll::Ready(Some(Ok(conn)));
+        }
+    }
+}
+
+/// Connection counted as open until dropped
+pub struct LimitedConn<C> {
+    inner: C,
+    open: Arc<AtomicUsize>,
+}
+
+impl<C> Drop for LimitedConn<C> {
+    fn drop(&mut self) {
+        self.open.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl<C> AsyncRead for LimitedConn<C>
+where
+    C: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<C> AsyncWrite for LimitedConn<C>
+where
+    C: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
+
+/// Connection with a known remote address
+pub trait RemoteAddr {
+    fn remote_addr(&self) -> Option<SocketAddr>;
+}
+
+impl RemoteAddr for AddrStream {
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        Some(AddrStream::remote_addr(self))
+    }
+}
+
+impl<C> RemoteAddr for tokio_rustls::server::TlsStream<C>
+where
+    C: RemoteAddr,
+{
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.get_ref().0.remote_addr()
+    }
+}
+
+impl<C> RemoteAddr for LimitedConn<C>
+where
+    C: RemoteAddr,
+{
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.inner.remote_addr()
+    }
+}
+
+/// Address of the client, attached to requests as connect info
+#[derive(Debug, Clone, Copy)]
+pub struct ClientAddr(pub Option<SocketAddr>);
+
+impl<C> Connected<&LimitedConn<C>> for ClientAddr
+where
+    C: RemoteAddr,
+{
+    fn connect_info(target: &LimitedConn<C>) -> Self {
+        Self(target.remote_addr())
+    }
+}
+
+/// Service of a single connection which asks the client to close the
+/// connection once the maximum number of requests is reached
+#[derive(Clone)]
+pub struct RequestLimit<S> {
+    inner: S,
+    max: Option<usize>,
+    count: Arc<AtomicUsize>,
+}
+
+impl<S> RequestLimit<S> {
+    pub fn new(inner: S, max: Option<usize>) -> Self {
+        Self {
+            inner,
+            max,
+            count: Arc::default(),
+        }
+    }
+}
+
+impl<S, B, ResBody> Service<Request<B>> for RequestLimit<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let count = self.count.fetch_add(1, Ordering::SeqCst) + 1;
+        let close = self.max.is_some_and(|m| count >= m);
+
+        let fut = self.inner.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+            if close {
+                res.headers_mut()
+                    .insert(CONNECTION, HeaderValue::from_static("close"));
+            }
+
+            Ok(res)
+        })
+    }
+}
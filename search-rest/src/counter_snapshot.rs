@@ -0,0 +1,188 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use metrics::{counter, describe_counter, gauge, Label};
+use metrics_exporter_prometheus::PrometheusHandle;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::Receiver;
+use tracing::{info, warn};
+
+/// Counters persisted by default, they're reset by every restart otherwise
+pub const DEFAULT_PERSISTED_COUNTERS: [&str; 2] =
+    ["search_queries_total", "search_indexed_documents_total"];
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CounterValue {
+    name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    labels: Vec<(String, String)>,
+    value: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Snapshot {
+    saved_at: DateTime<Utc>,
+    counters: Vec<CounterValue>,
+}
+
+/// Snapshots of selected counters, restored on startup
+pub struct CounterSnapshot {
+    path: PathBuf,
+    names: Vec<String>,
+    handle: PrometheusHandle,
+}
+
+impl CounterSnapshot {
+    pub fn new(path: PathBuf, names: Vec<String>, handle: PrometheusHandle) -> Self {
+        Self {
+            path,
+            names,
+            handle,
+        }
+    }
+
+    /// Adds the values of the snapshot to the counters.
+    ///
+    /// Restored counters are described as such and the time of the snapshot
+    /// is reported by `search_counters_restored_timestamp_seconds`.
+    pub fn restore(&self) -> io::Result<()> {
+        for name in self.names.iter() {
+            describe_counter!(
+                name.clone(),
+                "Counter persisted across restarts, see search_counters_restored_timestamp_seconds"
+            );
+        }
+
+        let file = match File::open(&self.path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let snapshot: Snapshot = serde_json::from_reader(BufReader::new(file))?;
+
+        let mut restored = 0;
+        for c in snapshot
+            .counters
+            .into_iter()
+            .filter(|c| self.names.contains(&c.name))
+        {
+            let labels = c
+                .labels
+                .into_iter()
+                .map(|(k, v)| Label::new(k, v))
+                .collect::<Vec<_>>();
+            counter!(c.name, c.value, labels);
+            restored += 1;
+        }
+
+        gauge!(
+            "search_counters_restored_timestamp_seconds",
+            snapshot.saved_at.timestamp() as f64
+        );
+
+        info!(
+            counters = restored,
+            saved_at = %snapshot.saved_at,
+            "Counters restored from snapshot"
+        );
+
+        Ok(())
+    }
+
+    /// Replaces the snapshot with the current values of the counters
+    pub fn save(&self) -> io::Result<()> {
+        let snapshot = Snapshot {
+            saved_at: Utc::now(),
+            counters: parse_counters(&self.handle.render(), &self.names),
+        };
+
+        let tmp = self.path.with_extension("tmp");
+
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        serde_json::to_writer(&mut writer, &snapshot)?;
+        writer.flush()?;
+
+        // Renaming keeps the previous snapshot intact if writing fails
+        fs::rename(tmp, &self.path)
+    }
+
+    /// Saves the snapshot periodically and a last time on shutdown
+    pub async fn run(self, interval: Duration, mut shutdown: Receiver<()>) {
+        let mut interval = tokio::time::interval(interval);
+        interval.tick().await;
+
+        loop {
+            let stop = tokio::select! {
+                _ = interval.tick() => false,
+                _ = shutdown.recv() => true,
+            };
+
+            if let Err(e) = self.save() {
+                warn!(error = %e, path = %self.path.display(), "Couldn't write counter snapshot");
+            }
+
+            if stop {
+                break;
+            }
+        }
+    }
+}
+
+/// Parses the counters with the given names from the Prometheus text format
+fn parse_counters(text: &str, names: &[String]) -> Vec<CounterValue> {
+    text.lines()
+        .filter(|l| !l.starts_with('#'))
+        .filter_map(|line| {
+            let (series, value) = line.rsplit_once(' ')?;
+            let (name, labels) = match series.split_once('{') {
+                Some((name, labels)) => (name, parse_labels(labels.strip_suffix('}')?)?),
+                None => (series, Vec::new()),
+            };
+
+            if !names.iter().any(|n| n == name) {
+                return None;
+            }
+
+            Some(CounterValue {
+                name: name.to_string(),
+                labels,
+                value: value.parse::<f64>().ok()? as u64,
+            })
+        })
+        .collect()
+}
+
+/// Parses labels in the form of `key="value",...`
+fn parse_labels(text: &str) -> Option<Vec<(String, String)>> {
+    let mut labels = Vec::new();
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let (key, value) = rest.split_once("=\"")?;
+
+        let mut unescaped = String::new();
+        let mut chars = value.char_indices();
+        let end = loop {
+            match chars.next()? {
+                (i, '"') => break i,
+                (_, '\\') => match chars.next()?.1 {
+                    'n' => unescaped.push('\n'),
+                    c => unescaped.push(c),
+                },
+                (_, c) => unescaped.push(c),
+            }
+        };
+
+        labels.push((key.trim_start_matches(',').to_string(), unescaped));
+        rest = &value[end + 1..];
+    }
+
+    Some(labels)
+}
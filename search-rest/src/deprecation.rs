@@ -0,0 +1,166 @@
+use crate::{
+    authentication::{TokenClaims, TokenConfig},
+    token::Claims,
+};
+
+use std::{
+    collections::HashSet,
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use axum::{extract::State, http::Request, middleware::Next, response::Response};
+use chrono::{DateTime, Utc};
+use hyper::{
+    header::{HeaderName, AUTHORIZATION, LINK},
+    http::HeaderValue,
+};
+use metrics::increment_counter;
+use serde::Deserialize;
+
+static DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+static SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+/// Deprecated route or query parameter of a route
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Deprecation {
+    pub path: String,
+    /// Query parameter, the whole route is deprecated if none is given
+    pub param: Option<String>,
+    pub since: DateTime<Utc>,
+    pub sunset: Option<DateTime<Utc>>,
+    /// Documentation of the deprecation
+    pub link: Option<String>,
+}
+
+impl Deprecation {
+    fn matches<B>(&self, req: &Request<B>) -> bool {
+        if req.uri().path().trim_end_matches('/') != self.path.trim_end_matches('/') {
+            return false;
+        }
+
+        match &self.param {
+            Some(param) => req.uri().query().is_some_and(|q| {
+                q.split('&')
+                    .any(|p| p.split('=').next() == Some(param.as_str()))
+            }),
+            None => true,
+        }
+    }
+
+    fn message(&self) -> String {
+        let subject = match &self.param {
+            Some(p) => format!("Parameter \"{}\" of {}", p, self.path),
+            None => format!("Route {}", self.path),
+        };
+
+        match self.sunset {
+            Some(s) => format!(
+                "{} is deprecated and will be removed on {}",
+                subject,
+                s.date_naive()
+            ),
+            None => format!("{} is deprecated", subject),
+        }
+    }
+}
+
+/// Warnings about deprecations used by the request, attached to the request
+/// extensions. Each deprecation is only reported once per token subject.
+#[derive(Debug, Clone, Default)]
+pub struct DeprecationNotice(pub Vec<String>);
+
+#[derive(Debug, Default)]
+pub struct Deprecations {
+    entries: Vec<Deprecation>,
+    warned: Mutex<HashSet<(usize, String)>>,
+}
+
+impl Deprecations {
+    pub fn new(entries: Vec<Deprecation>) -> Self {
+        Self {
+            entries,
+            warned: Mutex::default(),
+        }
+    }
+
+    pub fn from_file<P>(path: P) -> crate::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = fs::read(path)?;
+        let entries = serde_json::from_slice(&file)?;
+
+        Ok(Self::new(entries))
+    }
+
+    /// Returns true if the subject wasn't warned about the deprecation yet
+    fn first_use(&self, index: usize, subject: Option<&str>) -> bool {
+        match subject {
+            Some(s) => self.warned.lock().unwrap().insert((index, s.to_owned())),
+            None => true,
+        }
+    }
+}
+
+/// Middleware emitting `Deprecation`, `Sunset` and `Link` headers for
+/// deprecated routes and parameters
+pub async fn emit<B>(
+    State(deprecations): State<Arc<Deprecations>>,
+    State(config): State<TokenConfig>,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let used = deprecations
+        .entries
+        .iter()
+        .enumerate()
+        .filter(|(_, d)| d.matches(&req))
+        .collect::<Vec<_>>();
+
+    if used.is_empty() {
+        return next.run(req).await;
+    }
+
+    let claims = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .and_then(|t| Claims::decode(t, &config, true).ok());
+    let subject = claims.as_ref().map(Claims::subject);
+    let notice = used
+        .iter()
+        .filter(|(i, _)| deprecations.first_use(*i, subject))
+        .map(|(_, d)| d.message())
+        .collect();
+    req.extensions_mut().insert(DeprecationNotice(notice));
+
+    let mut res = next.run(req).await;
+    let headers = res.headers_mut();
+
+    for (_, d) in used.iter() {
+        increment_counter!("search_deprecated_requests_total", "path" => d.path.clone());
+    }
+
+    if let Some(since) = used.iter().map(|(_, d)| d.since).min() {
+        if let Ok(v) = HeaderValue::from_str(&format!("@{}", since.timestamp())) {
+            headers.insert(DEPRECATION.clone(), v);
+        }
+    }
+    if let Some(sunset) = used.iter().filter_map(|(_, d)| d.sunset).min() {
+        let date = sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        if let Ok(v) = HeaderValue::from_str(&date) {
+            headers.insert(SUNSET.clone(), v);
+        }
+    }
+    for link in used.iter().filter_map(|(_, d)| d.link.as_ref()) {
+        if let Ok(v) = HeaderValue::from_str(&format!("<{}>; rel=\"deprecation\"", link)) {
+            headers.append(LINK, v);
+        }
+    }
+
+    res
+}
@@ -1,6 +1,8 @@
 use crate::{
     authentication::{AuthenticationError, TokenError},
+    config::ConfigProblems,
     model::Status,
+    quota::QuotaError,
     search,
 };
 
@@ -12,6 +14,10 @@ use tracing::error;
 pub enum Error {
     #[error("missing config variable: {0}")]
     MissingConfigVar(&'static str),
+    #[error("invalid config variable {0}: {1}")]
+    InvalidConfigVar(&'static str, String),
+    #[error("{0}")]
+    Config(ConfigProblems),
     #[error("search index error: {0}")]
     Index(#[from] search_index::Error),
     #[error("search error: {0}")]
@@ -20,18 +26,32 @@ pub enum Error {
     Authentiaction(#[from] AuthenticationError),
     #[error("action error: {0}")]
     Token(#[from] TokenError),
+    #[error("quota error: {0}")]
+    Quota(#[from] QuotaError),
     #[error("API lib error: {0}")]
     ApiLibrary(#[from] tarkov_database_rs::Error),
     #[error("Envy error: {0}")]
     Envy(#[from] envy::Error),
+    #[error("config file error: {0}")]
+    ConfigFile(#[from] serde_json::Error),
     #[error("hyper error: {0}")]
     Hyper(#[from] hyper::Error),
     #[error("rustls error: {0}")]
     TlsConfig(#[from] rustls::Error),
+    #[error("metrics error: {0}")]
+    Metrics(#[from] metrics_exporter_prometheus::BuildError),
     #[error("task error: {0}")]
     Task(#[from] tokio::task::JoinError),
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("webhook client error: {0}")]
+    Webhook(#[from] reqwest::Error),
+}
+
+impl From<ConfigProblems> for Error {
+    fn from(problems: ConfigProblems) -> Self {
+        Self::Config(problems)
+    }
 }
 
 impl axum::response::IntoResponse for Error {
@@ -40,6 +60,7 @@ impl axum::response::IntoResponse for Error {
             Error::Search(e) => e.error_response(),
             Error::Authentiaction(e) => e.error_response(),
             Error::Token(e) => e.error_response(),
+            Error::Quota(e) => e.error_response(),
             Error::Hyper(e) => {
                 error!(error = %e, "Hyper error");
                 Status::new(StatusCode::INTERNAL_SERVER_ERROR, "internal error")
@@ -53,10 +74,18 @@ impl axum::response::IntoResponse for Error {
                 Status::new(StatusCode::INTERNAL_SERVER_ERROR, "internal error")
             }
             Error::Envy(_) => unreachable!(),
+            Error::ConfigFile(_) => unreachable!(),
             Error::MissingConfigVar(_) => unreachable!(),
-            Error::Task(_) => unreachable!(),
+            Error::InvalidConfigVar(_, _) => unreachable!(),
+            Error::Config(_) => unreachable!(),
+            Error::Task(e) => {
+                error!(error = %e, "Task error");
+                Status::new(StatusCode::INTERNAL_SERVER_ERROR, "internal error")
+            }
             Error::TlsConfig(_) => unreachable!(),
+            Error::Metrics(_) => unreachable!(),
             Error::Io(_) => unreachable!(),
+            Error::Webhook(_) => unreachable!(),
         };
 
         res.into_response()
@@ -68,13 +97,6 @@ pub async fn handle_error(error: BoxError) -> Status {
         return Status::new(StatusCode::REQUEST_TIMEOUT, "request timed out");
     }
 
-    if error.is::<tower::load_shed::error::Overloaded>() {
-        return Status::new(
-            StatusCode::SERVICE_UNAVAILABLE,
-            "service is overloaded, try again later",
-        );
-    }
-
     error!(error = %error, "internal error");
     Status::new(StatusCode::INTERNAL_SERVER_ERROR, "internal error")
 }
@@ -1,17 +1,32 @@
 use crate::{
     authentication::{AuthenticationError, TokenClaims, TokenConfig, TokenError},
+    authorization::{AuthorizationRequest, SharedAuthorizer},
     error::Error,
     model::Status,
+    search::SearchLinks,
+    token::Claims,
 };
 
+use std::{convert::Infallible, time::Duration};
+
 use axum::{
     async_trait,
-    extract::{rejection::JsonRejection, FromRef, FromRequest, FromRequestParts, TypedHeader},
+    extract::{
+        rejection::JsonRejection, FromRef, FromRequest, FromRequestParts, MatchedPath, TypedHeader,
+    },
     http::request::Parts,
+    http::HeaderName,
 };
+use chrono::{DateTime, TimeZone, Utc};
 use headers::{authorization::Bearer, Authorization};
-use hyper::Request;
+use hyper::{
+    header::{ACCEPT_LANGUAGE, AUTHORIZATION},
+    Request, StatusCode,
+};
+use metrics::increment_counter;
 use serde::de::DeserializeOwned;
+use tokio::time::Instant;
+use tracing::Span;
 
 /// JSON extractor with custom error response
 pub struct Json<T>(pub T);
@@ -34,6 +49,8 @@ where
     }
 }
 
+/// Query extractor with custom error response, repeated parameters can be
+/// deserialized into sequences
 pub struct Query<T>(pub T);
 
 #[async_trait]
@@ -44,10 +61,15 @@ where
 {
     type Rejection = Status;
 
-    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        match axum::extract::Query::<T>::from_request_parts(parts, state).await {
-            Ok(value) => Ok(Self(value.0)),
-            Err(rejection) => Err(Status::new(rejection.status(), rejection.body_text())),
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let query = parts.uri.query().unwrap_or_default();
+
+        match serde_html_form::from_str::<T>(query) {
+            Ok(value) => Ok(Self(value)),
+            Err(e) => Err(Status::new(
+                StatusCode::BAD_REQUEST,
+                format!("Failed to deserialize query string: {}", e),
+            )),
         }
     }
 }
@@ -60,6 +82,7 @@ where
 impl<S, T, const VE: bool> FromRequestParts<S> for TokenData<T, VE>
 where
     TokenConfig: FromRef<S>,
+    SharedAuthorizer: FromRef<S>,
     T: TokenClaims,
     S: Send + Sync,
 {
@@ -77,6 +100,193 @@ where
 
         let claims = T::decode(bearer.token(), &config, VE).map_err(TokenError::from)?;
 
+        if let (Some(name), version) = claims.client() {
+            let version = version.unwrap_or_default();
+
+            let span = Span::current();
+            span.record("client_name", name);
+            span.record("client_version", version);
+
+            increment_counter!(
+                "search_client_requests_total",
+                "client" => name.to_owned(),
+                "version" => version.to_owned()
+            );
+        }
+
+        if let Some(c) = claims.authorization_claims() {
+            authorize(parts, state, c)?;
+        }
+
+        Ok(Self(claims))
+    }
+}
+
+/// Claims of the bearer token, or of a signed search link if the request has
+/// no authorization header
+pub struct SearchClaims(pub Claims);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for SearchClaims
+where
+    TokenConfig: FromRef<S>,
+    SharedAuthorizer: FromRef<S>,
+    SearchLinks: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let links = SearchLinks::from_ref(state);
+        let query = parts.uri.query().unwrap_or_default();
+
+        if !links.enabled()
+            || parts.headers.contains_key(AUTHORIZATION)
+            || !SearchLinks::is_signed(query)
+        {
+            let TokenData(claims) =
+                TokenData::<Claims, true>::from_request_parts(parts, state).await?;
+            return Ok(Self(claims));
+        }
+
+        let claims = links.verify(query, &TokenConfig::from_ref(state))?;
+        authorize(parts, state, &claims)?;
+
+        increment_counter!("search_link_requests_total");
+
         Ok(Self(claims))
     }
 }
+
+/// Checks the claims against the authorization policies of the route
+fn authorize<S>(parts: &Parts, state: &S, claims: &Claims) -> Result<(), AuthenticationError>
+where
+    SharedAuthorizer: FromRef<S>,
+{
+    let route = parts
+        .extensions
+        .get::<MatchedPath>()
+        .map_or(parts.uri.path(), MatchedPath::as_str);
+    let params: Vec<(String, String)> =
+        serde_html_form::from_str(parts.uri.query().unwrap_or_default()).unwrap_or_default();
+
+    SharedAuthorizer::from_ref(state)
+        .authorize(&AuthorizationRequest {
+            claims,
+            method: &parts.method,
+            route,
+            params: &params,
+        })
+        .map_err(AuthenticationError::Denied)
+}
+
+/// Primary language subtags of the `Accept-Language` header, ordered by preference
+pub struct AcceptLanguage(pub Vec<String>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AcceptLanguage
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let header = match parts
+            .headers
+            .get(ACCEPT_LANGUAGE)
+            .and_then(|v| v.to_str().ok())
+        {
+            Some(v) => v,
+            None => return Ok(Self(Vec::new())),
+        };
+
+        let mut langs = header
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+
+                if tag.is_empty() || tag == "*" || quality <= 0.0 {
+                    return None;
+                }
+
+                let primary = tag.split('-').next()?.to_ascii_lowercase();
+
+                Some((primary, quality))
+            })
+            .collect::<Vec<_>>();
+
+        langs.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        Ok(Self(langs.into_iter().map(|(l, _)| l).collect()))
+    }
+}
+
+static GRPC_TIMEOUT: HeaderName = HeaderName::from_static("grpc-timeout");
+static REQUEST_DEADLINE: HeaderName = HeaderName::from_static("x-request-deadline");
+
+/// Deadline of the client, if it's shorter than the request timeout.
+///
+/// Taken from `grpc-timeout` (e.g. `500m`) or `X-Request-Deadline` as
+/// RFC 3339 or Unix timestamp in milliseconds, the earlier one wins.
+pub struct RequestDeadline(pub Option<Instant>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RequestDeadline
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let header = |name: &HeaderName| parts.headers.get(name).and_then(|v| v.to_str().ok());
+
+        let timeout = header(&GRPC_TIMEOUT).and_then(parse_grpc_timeout);
+        let deadline = header(&REQUEST_DEADLINE)
+            .and_then(parse_deadline)
+            .map(|d| (d - Utc::now()).to_std().unwrap_or_default());
+
+        let budget = match (timeout, deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        Ok(Self(
+            budget
+                .filter(|b| *b < crate::REQUEST_TIMEOUT)
+                .map(|b| Instant::now() + b),
+        ))
+    }
+}
+
+/// Parses a gRPC timeout, at most eight digits followed by the unit
+fn parse_grpc_timeout(value: &str) -> Option<Duration> {
+    if value.len() < 2 || value.len() > 9 {
+        return None;
+    }
+
+    let (amount, unit) = value.split_at(value.len() - 1);
+    let amount = amount.parse::<u64>().ok()?;
+
+    match unit {
+        "H" => Some(Duration::from_secs(amount * 60 * 60)),
+        "M" => Some(Duration::from_secs(amount * 60)),
+        "S" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_millis(amount)),
+        "u" => Some(Duration::from_micros(amount)),
+        "n" => Some(Duration::from_nanos(amount)),
+        _ => None,
+    }
+}
+
+fn parse_deadline(value: &str) -> Option<DateTime<Utc>> {
+    match value.parse::<i64>() {
+        Ok(millis) => Utc.timestamp_millis_opt(millis).single(),
+        Err(_) => DateTime::parse_from_rfc3339(value)
+            .ok()
+            .map(|d| d.with_timezone(&Utc)),
+    }
+}
@@ -1,42 +1,141 @@
-use crate::{extract::TokenData, model::Response, token::Claims};
-
-use super::{ServiceStatus, Services};
+use crate::{extract::TokenData, maintenance::Maintenance, model::Response, token::Claims};
 
 use std::sync::Arc;
 
 use axum::extract::State;
-use search_state::HandlerStatus;
+use chrono::{DateTime, Utc};
+use search_index::language_code;
+use search_models::{HealthStatus, IndexStatus, ServiceStatus, Services};
+use search_state::{HandlerStatus, HealthService, HealthTransition, IndexState};
 use serde::Serialize;
 
-#[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct StatusResponse {
-    ok: bool,
-    service: Services,
-}
-
 pub async fn get(
     TokenData(_claims): TokenData<Claims, true>,
     State(status): State<Arc<HandlerStatus>>,
-) -> crate::Result<Response<StatusResponse>> {
+    State(state): State<IndexState>,
+    State(maintenance): State<Arc<Maintenance>>,
+) -> crate::Result<Response<HealthStatus>> {
+    let maintenance = maintenance.is_active();
+    let populated = state.is_populated();
+    let (ok, services) = service_status(&status, &state, maintenance);
+
+    let mut indexes = Vec::new();
+    for lang in state.languages() {
+        let index_status = if state.is_healthy_by_lang(lang).unwrap_or_default() {
+            ServiceStatus::Ok
+        } else {
+            ServiceStatus::Failure
+        };
+
+        indexes.push(IndexStatus {
+            language: language_code(lang).to_string(),
+            status: index_status,
+            populated: state.is_populated_by_lang(lang).unwrap_or_default(),
+            modified: state.get_modified_by_lang(lang).await.unwrap_or_default(),
+        });
+    }
+
+    Ok(Response::new(HealthStatus {
+        ok,
+        service: services,
+        indexes,
+        populated,
+        sync_deferred: status.is_sync_deferred(),
+        maintenance,
+        api_origin: status.active_origin(),
+        stale: status.is_stale(),
+        schema_incompatible: status.is_schema_incompatible(),
+        skipped_documents: status.skipped_documents(),
+        document_errors: status.document_errors(),
+        uptime: (Utc::now() - status.started()).num_seconds(),
+    }))
+}
+
+/// Returns the status of the services and whether the service is ok overall
+pub fn service_status(
+    status: &HandlerStatus,
+    state: &IndexState,
+    maintenance: bool,
+) -> (bool, Services) {
     let mut ok = true;
 
     let index = if status.is_index_error() {
         ok = false;
         ServiceStatus::Failure
+    } else if !state.is_populated() {
+        // The first build is still pending, searches are rejected until then
+        ok = false;
+        ServiceStatus::Warning
+    } else if status.is_stale() || status.skipped_documents() > 0 {
+        ServiceStatus::Warning
     } else {
         ServiceStatus::Ok
     };
 
     let api = if status.is_client_error() {
-        ok = false;
+        // Upstream failures are expected during maintenance
+        ok = ok && maintenance;
         ServiceStatus::Failure
+    } else if status.active_origin() != 0 {
+        ServiceStatus::Warning
     } else {
         ServiceStatus::Ok
     };
 
-    Ok(Response::new(StatusResponse {
-        ok,
-        service: Services { index, api },
+    (ok, Services { index, api })
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryResponse {
+    started: DateTime<Utc>,
+    /// Uptime in seconds
+    uptime: i64,
+    index_since: DateTime<Utc>,
+    api_since: DateTime<Utc>,
+    transitions: Vec<Transition>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transition {
+    time: DateTime<Utc>,
+    service: &'static str,
+    status: ServiceStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+}
+
+impl From<HealthTransition> for Transition {
+    fn from(t: HealthTransition) -> Self {
+        Self {
+            time: t.time,
+            service: t.service.name(),
+            status: if t.failed {
+                ServiceStatus::Failure
+            } else {
+                ServiceStatus::Ok
+            },
+            message: t.message,
+        }
+    }
+}
+
+pub async fn history(
+    TokenData(_claims): TokenData<Claims, true>,
+    State(status): State<Arc<HandlerStatus>>,
+) -> crate::Result<Response<HistoryResponse>> {
+    let started = status.started();
+
+    Ok(Response::new(HistoryResponse {
+        started,
+        uptime: (Utc::now() - started).num_seconds(),
+        index_since: status.state_since(HealthService::Index),
+        api_since: status.state_since(HealthService::Api),
+        transitions: status
+            .health_history()
+            .into_iter()
+            .map(Transition::from)
+            .collect(),
     }))
 }
@@ -6,5 +6,7 @@ use axum::routing::get;
 
 /// Health routes
 pub fn routes() -> axum::Router<AppState> {
-    axum::Router::new().route("/", get(handler::get))
+    axum::Router::new()
+        .route("/", get(handler::get))
+        .route("/history", get(handler::history))
 }
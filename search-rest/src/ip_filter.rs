@@ -0,0 +1,80 @@
+use crate::{connection::ClientAddr, model::Status};
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{ConnectInfo, State},
+    http::Request,
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    Router,
+};
+use hyper::StatusCode;
+use ipnet::IpNet;
+use metrics::increment_counter;
+use tracing::warn;
+
+/// Filter of client addresses, the denylist takes precedence over the allowlist
+#[derive(Debug, Clone, Default)]
+pub struct IpFilter {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+}
+
+impl IpFilter {
+    pub fn new(allow: Vec<IpNet>, deny: Vec<IpNet>) -> Self {
+        Self { allow, deny }
+    }
+
+    fn is_enabled(&self) -> bool {
+        !self.allow.is_empty() || !self.deny.is_empty()
+    }
+
+    fn is_allowed(&self, addr: Option<&ClientAddr>) -> bool {
+        let ip = match addr.and_then(|a| a.0) {
+            Some(a) => a.ip(),
+            None => return false,
+        };
+
+        if self.deny.iter().any(|n| n.contains(&ip)) {
+            return false;
+        }
+
+        self.allow.is_empty() || self.allow.iter().any(|n| n.contains(&ip))
+    }
+
+    /// Rejects requests of clients not allowed by the filter with a `403` status
+    pub fn apply<S>(self, router: Router<S>) -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        if !self.is_enabled() {
+            return router;
+        }
+
+        router.layer(middleware::from_fn_with_state(Arc::new(self), enforce))
+    }
+}
+
+async fn enforce<B>(
+    State(filter): State<Arc<IpFilter>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let addr = req.extensions().get::<ConnectInfo<ClientAddr>>();
+
+    if !filter.is_allowed(addr.map(|c| &c.0)) {
+        warn!(
+            target: "audit",
+            client = ?addr.and_then(|c| c.0 .0),
+            method = %req.method(),
+            path = %req.uri().path(),
+            "Request rejected by IP filter"
+        );
+        increment_counter!("search_ip_filter_rejected_total");
+
+        return Status::new(StatusCode::FORBIDDEN, "client address is not allowed").into_response();
+    }
+
+    next.run(req).await
+}
@@ -0,0 +1,1519 @@
+mod acme;
+mod admin;
+pub mod allocator;
+mod audience;
+mod authentication;
+pub mod authorization;
+mod body_limit;
+mod config;
+mod connection;
+mod counter_snapshot;
+mod deprecation;
+pub mod error;
+mod extract;
+mod health;
+mod ip_filter;
+mod logging;
+mod maintenance;
+mod model;
+mod negotiate;
+mod proxy;
+mod quota;
+mod search;
+mod shedding;
+mod tls;
+mod token;
+mod utils;
+mod version;
+mod webhook;
+
+use crate::{
+    acme::AcmeOptions,
+    audience::AudienceTiers,
+    authentication::{reload_secret_on_hangup, TokenConfig, TokenEncryption},
+    authorization::{Authorizer, ScopeAuthorizer, SharedAuthorizer},
+    body_limit::BodyLimit,
+    config::ConfigProblems,
+    connection::{ClientAddr, LimitedIncoming, RequestLimit},
+    counter_snapshot::{CounterSnapshot, DEFAULT_PERSISTED_COUNTERS},
+    deprecation::Deprecations,
+    error::Error,
+    ip_filter::IpFilter,
+    logging::{FileSink, LogFormat, LogRotation},
+    maintenance::Maintenance,
+    quota::{QuotaLimits, UsageTracker},
+    search::{
+        Experiments, KindAliases, KindBoosts, LanguageDetection, Ranking, RefinementStore,
+        ResponseCache, ResultLimits, SearchContext, SearchLinks, SlowQueryLog, TypeQuotas,
+    },
+    shedding::LoadShedder,
+    tls::TlsOptions,
+    token::{ReplayGuard, RevocationList, ScopePolicy, UserCache},
+    version::VersionInfo,
+    webhook::HealthNotifier,
+};
+
+use std::{
+    collections::HashMap,
+    iter::once,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use axum::{error_handling::HandleErrorLayer, extract::FromRef, routing::get, Router, Server};
+use chrono::NaiveTime;
+use hyper::{header::AUTHORIZATION, server::conn::AddrIncoming, Body};
+use ipnet::IpNet;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use search_index::{language_from_code, Index, Supplements, MIN_WRITE_BUFFER, WRITE_BUFFER};
+use search_state::{
+    FailoverPolicy, HandlerStatus, HealthChecker, IndexState, IndexStateHandler, LoadMonitor,
+    LoadThresholds, MemoryLimit, OptimizeSchedule, RateLimit, UpdateTrigger,
+};
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+use tarkov_database_rs::client::{Client, ClientBuilder};
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::broadcast::{Receiver, Sender},
+};
+use tower::{ServiceBuilder, ServiceExt};
+use tower_http::{
+    sensitive_headers::SetSensitiveHeadersLayer,
+    trace::{DefaultOnResponse, TraceLayer},
+    LatencyUnit,
+};
+use tracing_appender::non_blocking::WorkerGuard;
+use zeroize::Zeroizing;
+
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+/// Histogram buckets of upstream API call durations in seconds
+const UPSTREAM_DURATION_BUCKETS: [f64; 10] =
+    [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+/// Histogram buckets of upstream API payload sizes in bytes
+const UPSTREAM_PAYLOAD_BUCKETS: [f64; 8] = [1e3, 1e4, 1e5, 1e6, 5e6, 1e7, 5e7, 1e8];
+
+pub type Result<T> = std::result::Result<T, error::Error>;
+
+/// Variables without a default value
+const REQUIRED_VARS: [&str; 4] = ["jwt_secret", "jwt_audience", "api_origin", "api_token"];
+
+/// Secrets which can be read from the file of the `_FILE` suffixed variable
+const SECRET_FILE_VARS: [&str; 2] = ["jwt_secret", "api_token"];
+
+/// Timeout of all requests, shorter client deadlines take precedence for searches
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+const MIN_UPDATE_INTERVAL: Duration = Duration::from_secs(10);
+
+const MAX_JWT_LEEWAY: Duration = Duration::from_secs(5 * 60);
+
+const fn default_addr() -> IpAddr {
+    IpAddr::V4(Ipv4Addr::LOCALHOST)
+}
+
+const fn default_port() -> u16 {
+    8080
+}
+
+const fn default_log_file_format() -> LogFormat {
+    LogFormat::Json
+}
+
+const fn default_body_limit() -> usize {
+    16 * 1024
+}
+
+const fn default_admin_body_limit() -> usize {
+    1024 * 1024
+}
+
+const fn default_true() -> bool {
+    true
+}
+
+const fn default_header_read_timeout() -> Duration {
+    Duration::from_secs(30)
+}
+
+const fn default_max_concurrent_requests() -> usize {
+    1024
+}
+
+const fn default_response_cache_max_staleness() -> Duration {
+    Duration::from_secs(30)
+}
+
+const fn default_user_cache_ttl() -> Duration {
+    Duration::from_secs(5 * 60)
+}
+
+const fn default_user_cache_negative_ttl() -> Duration {
+    Duration::from_secs(30)
+}
+
+const fn default_user_cache_max_staleness() -> Duration {
+    Duration::from_secs(60 * 60)
+}
+
+const fn default_response_cache_capacity() -> usize {
+    1000
+}
+
+const fn default_optimize_segments() -> usize {
+    1
+}
+
+const fn default_refinement_ttl() -> Duration {
+    Duration::from_secs(10 * 60)
+}
+
+const fn default_search_link_ttl() -> Duration {
+    Duration::from_secs(15 * 60)
+}
+
+const fn default_slow_query_threshold() -> Duration {
+    Duration::from_millis(100)
+}
+
+const fn default_counter_snapshot_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_smoke_queries() -> Vec<String> {
+    ["ammo", "rifle", "armor", "keycard", "medkit"]
+        .iter()
+        .map(|q| q.to_string())
+        .collect()
+}
+
+const fn default_ranking_reload_interval() -> Duration {
+    Duration::from_secs(10)
+}
+
+const fn default_health_webhook_debounce() -> Duration {
+    Duration::from_secs(60)
+}
+
+const fn default_health_webhook_sync_failures() -> usize {
+    3
+}
+
+fn default_persisted_counters() -> Vec<String> {
+    DEFAULT_PERSISTED_COUNTERS
+        .iter()
+        .map(|c| c.to_string())
+        .collect()
+}
+
+const fn default_interval() -> Duration {
+    Duration::from_secs(10 * 60)
+}
+
+const fn default_jwt_leeway() -> Duration {
+    TokenConfig::DEFAULT_LEEWAY
+}
+
+fn default_jwt_required_claims() -> Vec<String> {
+    TokenConfig::DEFAULT_REQUIRED_CLAIMS
+        .iter()
+        .map(|c| c.to_string())
+        .collect()
+}
+
+fn default_languages() -> Vec<String> {
+    vec!["en".to_string()]
+}
+
+/// Configuration of the server, read from `SEARCH_` prefixed variables
+#[derive(Debug, Deserialize)]
+pub struct AppConfig {
+    // Logging
+    #[serde(default)]
+    log_format: LogFormat,
+    log_file: Option<PathBuf>,
+    #[serde(default = "default_log_file_format")]
+    log_file_format: LogFormat,
+    #[serde(default)]
+    log_file_rotation: LogRotation,
+
+    // HTTP server
+    #[serde(default = "default_addr")]
+    server_addr: IpAddr,
+    #[serde(default = "default_port")]
+    server_port: u16,
+    #[serde(default)]
+    server_tls: bool,
+    /// Expect a PROXY protocol v2 header on connections
+    #[serde(default)]
+    server_proxy_protocol: bool,
+    server_max_connections: Option<usize>,
+    /// Requests after which a connection is closed
+    server_max_connection_requests: Option<usize>,
+    #[serde(default, with = "humantime_serde")]
+    server_tcp_keepalive: Option<Duration>,
+    #[serde(default = "default_true")]
+    server_http1_keepalive: bool,
+    #[serde(default = "default_header_read_timeout", with = "humantime_serde")]
+    server_header_read_timeout: Duration,
+    #[serde(default = "default_body_limit")]
+    body_limit_search: usize,
+    #[serde(default = "default_body_limit")]
+    body_limit_token: usize,
+    #[serde(default = "default_admin_body_limit")]
+    body_limit_admin: usize,
+    server_tls_cert: Option<PathBuf>,
+    server_tls_key: Option<PathBuf>,
+    /// Protocol versions (`1.2`, `1.3`), both if empty
+    #[serde(default)]
+    server_tls_versions: Vec<String>,
+    #[serde(default)]
+    server_tls13_only: bool,
+    /// Cipher suites by name, all safe ones of the versions if empty
+    #[serde(default)]
+    server_tls_cipher_suites: Vec<String>,
+    /// ALPN protocols, `h2`, `http/1.1` and `http/1.0` if empty
+    #[serde(default)]
+    server_tls_alpn: Vec<String>,
+    #[serde(default)]
+    server_acme_domains: Vec<String>,
+    #[serde(default)]
+    server_acme_contact: Vec<String>,
+    server_acme_cache_dir: Option<PathBuf>,
+    #[serde(default)]
+    server_acme_staging: bool,
+
+    // JWT
+    jwt_secret: SecretString,
+    /// File the secret was read from, reloaded on SIGHUP
+    jwt_secret_file: Option<PathBuf>,
+    #[serde(default)]
+    jwt_previous_secrets: Vec<SecretString>,
+    jwt_audience: Vec<String>,
+    jwt_audience_tiers_file: Option<PathBuf>,
+    jwt_encryption_key: Option<SecretString>,
+    /// Accepts unencrypted tokens while the encryption is enabled, to migrate
+    /// issued tokens
+    #[serde(default)]
+    jwt_encryption_allow_plain: bool,
+    /// Tolerance of the token expiration
+    #[serde(default = "default_jwt_leeway", with = "humantime_serde")]
+    jwt_leeway: Duration,
+    #[serde(default = "default_jwt_required_claims")]
+    jwt_required_claims: Vec<String>,
+    /// Issuer of created tokens, tokens of other issuers are rejected
+    jwt_issuer: Option<String>,
+    #[serde(default)]
+    jwt_require_one_time_creation: bool,
+    /// Subjects allowed to create tokens with scopes they don't hold
+    #[serde(default)]
+    jwt_trusted_subjects: Vec<String>,
+    #[serde(default = "default_user_cache_ttl", with = "humantime_serde")]
+    token_user_cache_ttl: Duration,
+    #[serde(default = "default_user_cache_negative_ttl", with = "humantime_serde")]
+    token_user_cache_negative_ttl: Duration,
+    #[serde(default = "default_user_cache_max_staleness", with = "humantime_serde")]
+    token_user_cache_max_staleness: Duration,
+    /// Renew tokens with the cached user state if the API is unavailable
+    #[serde(default)]
+    token_renewal_grace: bool,
+    /// Interval of checking the users of issued tokens for locks
+    #[serde(default, with = "humantime_serde")]
+    token_revocation_sweep_interval: Option<Duration>,
+
+    // Admin
+    #[serde(default)]
+    admin_ui: bool,
+    #[serde(default)]
+    admin_ip_allow: Vec<IpNet>,
+    #[serde(default)]
+    admin_ip_deny: Vec<IpNet>,
+
+    // API
+    api_origin: String,
+    #[serde(default)]
+    api_fallback_origins: Vec<String>,
+    api_failover_max_failures: Option<u32>,
+    #[serde(default, with = "humantime_serde")]
+    api_failback_after: Option<Duration>,
+    api_token: SecretString,
+    /// File the token was read from, reloaded on SIGHUP
+    api_token_file: Option<PathBuf>,
+    api_client_ca: Option<PathBuf>,
+    api_client_cert: Option<PathBuf>,
+    api_client_key: Option<PathBuf>,
+
+    // Search
+    #[serde(default = "default_interval", with = "humantime_serde")]
+    update_interval: Duration,
+    #[serde(default = "default_languages")]
+    index_languages: Vec<String>,
+    /// Directory the indexes are persisted in, one subdirectory per language
+    index_dir: Option<PathBuf>,
+    /// Interval of health checks independent of the index updates
+    #[serde(default, with = "humantime_serde")]
+    index_health_check_interval: Option<Duration>,
+    /// Time of the day (UTC) of the daily segment merge
+    index_optimize_at: Option<NaiveTime>,
+    #[serde(default = "default_optimize_segments")]
+    index_optimize_segments: usize,
+    #[serde(default, with = "humantime_serde")]
+    sync_defer_latency: Option<Duration>,
+    sync_defer_cpu_load: Option<f64>,
+    #[serde(default, with = "humantime_serde")]
+    sync_max_deferral: Option<Duration>,
+    #[serde(default = "default_slow_query_threshold", with = "humantime_serde")]
+    slow_query_threshold: Duration,
+    #[serde(default = "default_max_concurrent_requests")]
+    max_concurrent_requests: usize,
+    #[serde(default)]
+    detect_query_language: bool,
+    search_max_limit: Option<usize>,
+    search_max_limit_summary: Option<usize>,
+    #[serde(default, with = "humantime_serde")]
+    response_cache_ttl: Option<Duration>,
+    #[serde(
+        default = "default_response_cache_max_staleness",
+        with = "humantime_serde"
+    )]
+    response_cache_max_staleness: Duration,
+    #[serde(default = "default_response_cache_capacity")]
+    response_cache_capacity: usize,
+    /// Retention of results for refinements, zero disables refinements
+    #[serde(default = "default_refinement_ttl", with = "humantime_serde")]
+    refinement_ttl: Duration,
+    /// Validity of signed search links, zero disables links
+    #[serde(default = "default_search_link_ttl", with = "humantime_serde")]
+    search_link_ttl: Duration,
+    /// Queries which must still have hits in a reindex dry run
+    #[serde(default = "default_smoke_queries")]
+    smoke_queries: Vec<String>,
+    memory_limit: Option<u64>,
+    api_rate_limit: Option<f64>,
+    api_rate_burst: Option<u32>,
+    #[serde(default, with = "humantime_serde")]
+    index_stats_cache_ttl: Option<Duration>,
+    items_snapshot_file: Option<PathBuf>,
+    /// Experiment variants, only read at startup
+    experiments_file: Option<PathBuf>,
+    /// Relevance settings, reloaded when the file changes
+    ranking_file: Option<PathBuf>,
+    #[serde(default = "default_ranking_reload_interval", with = "humantime_serde")]
+    ranking_reload_interval: Duration,
+    deprecations_file: Option<PathBuf>,
+    #[serde(default)]
+    type_quotas: Vec<String>,
+    /// Aliases of item kinds in the form of `alias:kind`
+    #[serde(default)]
+    kind_aliases: Vec<String>,
+    item_locations_file: Option<PathBuf>,
+    presets_file: Option<PathBuf>,
+    ballistics_file: Option<PathBuf>,
+    prices_file: Option<PathBuf>,
+    /// Texts of the items and presets keyed by language code and ID
+    translations_file: Option<PathBuf>,
+    #[serde(default)]
+    currency_rates: Vec<String>,
+
+    // Metrics
+    /// File the persisted counters are written to, restored on startup
+    counter_snapshot_file: Option<PathBuf>,
+    #[serde(
+        default = "default_counter_snapshot_interval",
+        with = "humantime_serde"
+    )]
+    counter_snapshot_interval: Duration,
+    #[serde(default = "default_persisted_counters")]
+    persisted_counters: Vec<String>,
+
+    // Alerting
+    /// Slack or Discord compatible webhooks notified of health transitions
+    #[serde(default)]
+    health_webhook_urls: Vec<String>,
+    /// Time a health transition must persist before it's notified
+    #[serde(default = "default_health_webhook_debounce", with = "humantime_serde")]
+    health_webhook_debounce: Duration,
+    /// Failed syncs in a row before they're notified
+    #[serde(default = "default_health_webhook_sync_failures")]
+    health_webhook_sync_failures: usize,
+
+    // Quotas
+    quota_daily_requests: Option<u64>,
+    quota_monthly_requests: Option<u64>,
+    quota_daily_bytes: Option<u64>,
+    quota_monthly_bytes: Option<u64>,
+}
+
+#[derive(Clone)]
+pub struct AppState {
+    index: IndexState,
+    index_status: Arc<HandlerStatus>,
+    token_config: TokenConfig,
+    /// Client of the primary origin, replaced when the API token is reloaded
+    api_client: Arc<RwLock<Client>>,
+    experiments: Arc<Experiments>,
+    deprecations: Arc<Deprecations>,
+    load_monitor: Arc<LoadMonitor>,
+    update_trigger: UpdateTrigger,
+    type_quotas: Arc<TypeQuotas>,
+    usage_tracker: Arc<UsageTracker>,
+    audience_tiers: Arc<AudienceTiers>,
+    replay_guard: Arc<ReplayGuard>,
+    scope_policy: Arc<ScopePolicy>,
+    authorizer: SharedAuthorizer,
+    user_cache: Arc<UserCache>,
+    slow_queries: Arc<SlowQueryLog>,
+    language_detection: LanguageDetection,
+    search_links: SearchLinks,
+    result_limits: ResultLimits,
+    response_cache: Arc<ResponseCache>,
+    refinements: Arc<RefinementStore>,
+    kind_boosts: Arc<KindBoosts>,
+    kind_aliases: Arc<KindAliases>,
+    ranking: Arc<Ranking>,
+    maintenance: Arc<Maintenance>,
+    version: Arc<VersionInfo>,
+}
+
+impl FromRef<AppState> for IndexState {
+    fn from_ref(state: &AppState) -> Self {
+        state.index.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<HandlerStatus> {
+    fn from_ref(state: &AppState) -> Self {
+        state.index_status.clone()
+    }
+}
+
+impl FromRef<AppState> for TokenConfig {
+    fn from_ref(state: &AppState) -> Self {
+        state.token_config.clone()
+    }
+}
+
+impl FromRef<AppState> for Client {
+    fn from_ref(state: &AppState) -> Self {
+        state.api_client.read().unwrap().clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Deprecations> {
+    fn from_ref(state: &AppState) -> Self {
+        state.deprecations.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Experiments> {
+    fn from_ref(state: &AppState) -> Self {
+        state.experiments.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<LoadMonitor> {
+    fn from_ref(state: &AppState) -> Self {
+        state.load_monitor.clone()
+    }
+}
+
+impl FromRef<AppState> for UpdateTrigger {
+    fn from_ref(state: &AppState) -> Self {
+        state.update_trigger.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<TypeQuotas> {
+    fn from_ref(state: &AppState) -> Self {
+        state.type_quotas.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<UsageTracker> {
+    fn from_ref(state: &AppState) -> Self {
+        state.usage_tracker.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<AudienceTiers> {
+    fn from_ref(state: &AppState) -> Self {
+        state.audience_tiers.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ReplayGuard> {
+    fn from_ref(state: &AppState) -> Self {
+        state.replay_guard.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<UserCache> {
+    fn from_ref(state: &AppState) -> Self {
+        state.user_cache.clone()
+    }
+}
+
+impl FromRef<AppState> for SharedAuthorizer {
+    fn from_ref(state: &AppState) -> Self {
+        state.authorizer.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ScopePolicy> {
+    fn from_ref(state: &AppState) -> Self {
+        state.scope_policy.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<SlowQueryLog> {
+    fn from_ref(state: &AppState) -> Self {
+        state.slow_queries.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<ResponseCache> {
+    fn from_ref(state: &AppState) -> Self {
+        state.response_cache.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<RefinementStore> {
+    fn from_ref(state: &AppState) -> Self {
+        state.refinements.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<KindBoosts> {
+    fn from_ref(state: &AppState) -> Self {
+        state.kind_boosts.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<KindAliases> {
+    fn from_ref(state: &AppState) -> Self {
+        state.kind_aliases.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Ranking> {
+    fn from_ref(state: &AppState) -> Self {
+        state.ranking.clone()
+    }
+}
+
+impl FromRef<AppState> for SearchContext {
+    fn from_ref(state: &AppState) -> Self {
+        SearchContext {
+            index: state.index.clone(),
+            experiments: state.experiments.clone(),
+            load: state.load_monitor.clone(),
+            quotas: state.type_quotas.clone(),
+            slow_queries: state.slow_queries.clone(),
+            detection: state.language_detection,
+            cache: state.response_cache.clone(),
+            limits: state.result_limits,
+            refinements: state.refinements.clone(),
+            kind_boosts: state.kind_boosts.clone(),
+            kind_aliases: state.kind_aliases.clone(),
+            ranking: state.ranking.clone(),
+        }
+    }
+}
+
+impl FromRef<AppState> for LanguageDetection {
+    fn from_ref(state: &AppState) -> Self {
+        state.language_detection
+    }
+}
+
+impl FromRef<AppState> for SearchLinks {
+    fn from_ref(state: &AppState) -> Self {
+        state.search_links
+    }
+}
+
+impl FromRef<AppState> for ResultLimits {
+    fn from_ref(state: &AppState) -> Self {
+        state.result_limits
+    }
+}
+
+impl FromRef<AppState> for Arc<Maintenance> {
+    fn from_ref(state: &AppState) -> Self {
+        state.maintenance.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<VersionInfo> {
+    fn from_ref(state: &AppState) -> Self {
+        state.version.clone()
+    }
+}
+
+impl AppConfig {
+    /// Reads the configuration from the environment, or from a `.env` file
+    /// if present, and validates it
+    pub fn from_env() -> Result<Self> {
+        let mut vars = if dotenv::dotenv().is_ok() {
+            dotenv::vars().collect::<Vec<_>>()
+        } else {
+            std::env::vars().collect()
+        };
+
+        let mut problems = ConfigProblems::default();
+        for var in SECRET_FILE_VARS {
+            let name = format!("SEARCH_{}", var.to_uppercase());
+            let file_name = format!("{name}_FILE");
+            let Some((_, path)) = vars.iter().find(|(k, _)| *k == file_name) else {
+                continue;
+            };
+
+            if vars.iter().any(|(k, _)| *k == name) {
+                problems.add(var, format!("must not be set with {file_name}"));
+                continue;
+            }
+            match utils::read_secret(Path::new(path)) {
+                Ok(secret) => vars.push((name, secret.expose_secret().clone())),
+                Err(e) => problems.add(format!("{var}_file"), format!("can't be read: {e}")),
+            }
+        }
+        for var in REQUIRED_VARS {
+            let name = format!("SEARCH_{}", var.to_uppercase());
+            let file_name = format!("{name}_FILE");
+            if !vars.iter().any(|(k, _)| *k == name || *k == file_name) {
+                problems.add(var, "missing");
+            }
+        }
+        problems.into_result()?;
+
+        let config: Self = envy::prefixed("SEARCH_")
+            .from_iter(vars)
+            .map_err(|e| match e {
+                envy::Error::MissingValue(field) => {
+                    let mut problems = ConfigProblems::default();
+                    problems.add(field, "missing");
+                    problems.into()
+                }
+                e => Error::Envy(e),
+            })?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    fn api_client_options(&self) -> ApiClientOptions {
+        ApiClientOptions {
+            origins: once(&self.api_origin)
+                .chain(&self.api_fallback_origins)
+                .cloned()
+                .collect(),
+            ca: self.api_client_ca.clone(),
+            cert: self.api_client_cert.clone(),
+            key: self.api_client_key.clone(),
+        }
+    }
+
+    fn tls_options(&self) -> TlsOptions {
+        TlsOptions {
+            versions: self.server_tls_versions.clone(),
+            tls13_only: self.server_tls13_only,
+            cipher_suites: self.server_tls_cipher_suites.clone(),
+            alpn: self.server_tls_alpn.clone(),
+        }
+    }
+
+    /// Checks the configuration for problems the format of the values
+    /// doesn't reveal, all problems are reported at once
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = ConfigProblems::default();
+
+        problems.check_secret("jwt_secret", self.jwt_secret.expose_secret());
+        problems.check_duration(
+            "jwt_leeway",
+            self.jwt_leeway,
+            Duration::ZERO,
+            Some(MAX_JWT_LEEWAY),
+        );
+
+        if self.server_tls && self.server_acme_domains.is_empty() {
+            if self.server_tls_cert.is_none() {
+                problems.add("server_tls_cert", "must be set with SEARCH_SERVER_TLS");
+            }
+            if self.server_tls_key.is_none() {
+                problems.add("server_tls_key", "must be set with SEARCH_SERVER_TLS");
+            }
+        }
+        for (var, problem) in self.tls_options().check() {
+            problems.add(var, problem);
+        }
+        problems.check_pair(
+            ("api_client_cert", self.api_client_cert.as_ref()),
+            ("api_client_key", self.api_client_key.as_ref()),
+        );
+
+        let files = [
+            ("server_tls_cert", &self.server_tls_cert),
+            ("server_tls_key", &self.server_tls_key),
+            ("api_client_ca", &self.api_client_ca),
+            ("api_client_cert", &self.api_client_cert),
+            ("api_client_key", &self.api_client_key),
+            ("jwt_audience_tiers_file", &self.jwt_audience_tiers_file),
+            ("experiments_file", &self.experiments_file),
+            ("ranking_file", &self.ranking_file),
+            ("deprecations_file", &self.deprecations_file),
+            ("item_locations_file", &self.item_locations_file),
+            ("presets_file", &self.presets_file),
+            ("ballistics_file", &self.ballistics_file),
+            ("prices_file", &self.prices_file),
+            ("translations_file", &self.translations_file),
+        ];
+        for (var, path) in files {
+            problems.check_file(var, path.as_deref());
+        }
+        problems.check_parent_dir("log_file", self.log_file.as_deref());
+        problems.check_parent_dir("items_snapshot_file", self.items_snapshot_file.as_deref());
+        problems.check_parent_dir(
+            "counter_snapshot_file",
+            self.counter_snapshot_file.as_deref(),
+        );
+
+        problems.check_duration(
+            "update_interval",
+            self.update_interval,
+            MIN_UPDATE_INTERVAL,
+            None,
+        );
+        problems.check_duration(
+            "server_header_read_timeout",
+            self.server_header_read_timeout,
+            Duration::from_secs(1),
+            None,
+        );
+        if let Some(interval) = self.index_health_check_interval {
+            problems.check_duration(
+                "index_health_check_interval",
+                interval,
+                Duration::from_secs(1),
+                None,
+            );
+        }
+        problems.check_duration(
+            "counter_snapshot_interval",
+            self.counter_snapshot_interval,
+            Duration::from_secs(1),
+            None,
+        );
+        problems.check_duration(
+            "ranking_reload_interval",
+            self.ranking_reload_interval,
+            Duration::from_secs(1),
+            None,
+        );
+        problems.check_duration(
+            "search_link_ttl",
+            self.search_link_ttl,
+            Duration::ZERO,
+            Some(Duration::from_secs(24 * 60 * 60)),
+        );
+        problems.check_duration(
+            "health_webhook_debounce",
+            self.health_webhook_debounce,
+            Duration::ZERO,
+            Some(Duration::from_secs(24 * 60 * 60)),
+        );
+        for url in self.health_webhook_urls.iter() {
+            if !url.starts_with("https://") && !url.starts_with("http://") {
+                problems.add("health_webhook_urls", format!("{url} isn't an HTTP URL"));
+            }
+        }
+        if let Some(interval) = self.token_revocation_sweep_interval {
+            problems.check_duration(
+                "token_revocation_sweep_interval",
+                interval,
+                Duration::from_secs(1),
+                None,
+            );
+        }
+
+        problems.into_result()
+    }
+
+    /// Sets up logging as configured, the returned guard must be held while
+    /// the file sink is used
+    pub fn init_logging(&self) -> Option<WorkerGuard> {
+        logging::init(
+            &self.log_format,
+            self.log_file.as_deref().map(|path| FileSink {
+                path,
+                format: &self.log_file_format,
+                rotation: &self.log_file_rotation,
+            }),
+        )
+    }
+}
+
+/// Installs the global Prometheus recorder with the buckets of the
+/// histograms
+pub fn install_metrics_recorder() -> Result<PrometheusHandle> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full("search_upstream_duration_seconds".to_string()),
+            &UPSTREAM_DURATION_BUCKETS,
+        )?
+        .set_buckets_for_metric(
+            Matcher::Full("search_upstream_payload_bytes".to_string()),
+            &UPSTREAM_PAYLOAD_BUCKETS,
+        )?
+        .install_recorder()?;
+
+    Ok(handle)
+}
+
+/// Restores the persisted counters and snapshots them until the shutdown,
+/// if a snapshot file is configured
+pub fn persist_counters(
+    app_config: &AppConfig,
+    handle: PrometheusHandle,
+    shutdown_signal: &Sender<()>,
+) {
+    let path = match &app_config.counter_snapshot_file {
+        Some(p) => p.clone(),
+        None => return,
+    };
+
+    let snapshot = CounterSnapshot::new(path, app_config.persisted_counters.clone(), handle);
+    if let Err(e) = snapshot.restore() {
+        tracing::warn!(error = %e, "Couldn't restore counters from snapshot");
+    }
+
+    let interval = app_config.counter_snapshot_interval;
+    let signal = shutdown_signal.subscribe();
+    tokio::spawn(async move { snapshot.run(interval, signal).await });
+}
+
+/// Builds the router and the index handler from the configuration,
+/// background tasks are spawned and stopped by the shutdown signal.
+///
+/// The index handler must be run to keep the indexes up to date.
+pub async fn build_app(
+    app_config: &AppConfig,
+    shutdown_signal: &Sender<()>,
+) -> Result<(Router, IndexStateHandler)> {
+    build_app_with_authorizer(app_config, ScopeAuthorizer::default(), shutdown_signal).await
+}
+
+/// Builds the app like [`build_app`], requests with a valid token are
+/// authorized by the given authorizer
+pub async fn build_app_with_authorizer<A>(
+    app_config: &AppConfig,
+    authorizer: A,
+    shutdown_signal: &Sender<()>,
+) -> Result<(Router, IndexStateHandler)>
+where
+    A: Authorizer + 'static,
+{
+    let audience_tiers = match &app_config.jwt_audience_tiers_file {
+        Some(path) => AudienceTiers::from_file(path)?,
+        None => AudienceTiers::default(),
+    };
+
+    let audiences = app_config
+        .jwt_audience
+        .iter()
+        .map(String::as_str)
+        .chain(audience_tiers.names())
+        .collect::<Vec<_>>();
+    let revocations = Arc::new(RevocationList::default());
+    let token_config =
+        TokenConfig::from_secret(app_config.jwt_secret.expose_secret().as_bytes(), audiences)
+            .with_previous_secrets(
+                app_config
+                    .jwt_previous_secrets
+                    .iter()
+                    .map(|s| s.expose_secret().as_bytes()),
+            )
+            .with_revocations(revocations.clone())
+            .with_leeway(app_config.jwt_leeway);
+    let token_config = match &app_config.jwt_issuer {
+        Some(issuer) => token_config.with_issuer(issuer.clone()),
+        None => token_config,
+    };
+    let token_config = token_config
+        .with_required_claims(&app_config.jwt_required_claims)
+        .map_err(|e| Error::InvalidConfigVar("SEARCH_JWT_REQUIRED_CLAIMS", e))?;
+    let token_config = match &app_config.jwt_encryption_key {
+        Some(key) => token_config.with_encryption(
+            TokenEncryption::from_base64(key.expose_secret())
+                .map_err(|e| Error::InvalidConfigVar("SEARCH_JWT_ENCRYPTION_KEY", e))?
+                .with_plain_tokens(app_config.jwt_encryption_allow_plain),
+        ),
+        None => token_config,
+    };
+
+    let api_options = app_config.api_client_options();
+    let mut api_clients = api_options
+        .build(app_config.api_token.expose_secret())
+        .await?;
+    let fallback_clients = api_clients.split_off(1);
+    let api_client = api_clients.remove(0);
+
+    let experiments = match &app_config.experiments_file {
+        Some(path) => Experiments::from_file(path)?,
+        None => Experiments::default(),
+    };
+
+    let ranking = Arc::new(Ranking::load(app_config.ranking_file.clone())?);
+
+    let deprecations = match &app_config.deprecations_file {
+        Some(path) => Deprecations::from_file(path)?,
+        None => Deprecations::default(),
+    };
+
+    let type_quotas = TypeQuotas::parse(&app_config.type_quotas)
+        .map_err(|e| Error::InvalidConfigVar("SEARCH_TYPE_QUOTAS", e))?;
+    let kind_aliases = KindAliases::parse(&app_config.kind_aliases)
+        .map_err(|e| Error::InvalidConfigVar("SEARCH_KIND_ALIASES", e))?;
+
+    // Half of the memory limit is reserved for the index writers
+    let write_buffer = app_config.memory_limit.map(|limit| {
+        let buffer = limit as usize / 2 / app_config.index_languages.len().max(1);
+        if buffer < MIN_WRITE_BUFFER {
+            tracing::warn!(
+                limit_bytes = limit,
+                write_buffer_bytes = MIN_WRITE_BUFFER,
+                "Memory limit is too low, using the minimum index write buffer"
+            );
+        }
+        buffer
+    });
+
+    let indexes = app_config
+        .index_languages
+        .iter()
+        .map(|code| {
+            let lang = language_from_code(code).ok_or_else(|| {
+                Error::InvalidConfigVar(
+                    "SEARCH_INDEX_LANGUAGES",
+                    format!("unknown language {code}"),
+                )
+            })?;
+            let buffer = write_buffer.unwrap_or(WRITE_BUFFER);
+            let index = match &app_config.index_dir {
+                Some(dir) => {
+                    Index::open_or_create(dir.join(code), lang, buffer, ranking.ngram_range())?
+                }
+                None => Index::with_ngram_range(lang, buffer, ranking.ngram_range())?,
+            };
+            Ok(index)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if indexes.is_empty() {
+        return Err(Error::MissingConfigVar("SEARCH_INDEX_LANGUAGES"));
+    }
+
+    let index = IndexState::with_indexes(indexes);
+
+    let mut supplements = Supplements::default();
+    if let Some(path) = &app_config.item_locations_file {
+        supplements.locations = serde_json::from_slice(&std::fs::read(path)?)?;
+    }
+    if let Some(path) = &app_config.presets_file {
+        supplements.presets = serde_json::from_slice(&std::fs::read(path)?)?;
+    }
+    if let Some(path) = &app_config.ballistics_file {
+        supplements.ballistics = serde_json::from_slice(&std::fs::read(path)?)?;
+    }
+    if let Some(path) = &app_config.prices_file {
+        supplements.prices = serde_json::from_slice(&std::fs::read(path)?)?;
+    }
+    if let Some(path) = &app_config.translations_file {
+        supplements.translations = serde_json::from_slice(&std::fs::read(path)?)?;
+    }
+    supplements.rates = parse_currency_rates(&app_config.currency_rates)
+        .map_err(|e| Error::InvalidConfigVar("SEARCH_CURRENCY_RATES", e))?;
+
+    let response_cache = Arc::new(
+        ResponseCache::new(
+            app_config.response_cache_ttl,
+            app_config.response_cache_max_staleness,
+        )
+        .with_capacity(app_config.response_cache_capacity),
+    );
+    let refinements = Arc::new(RefinementStore::new(app_config.refinement_ttl));
+
+    let index_handler = IndexStateHandler::new(
+        index.clone(),
+        api_client.clone(),
+        app_config.update_interval,
+    )
+    .with_load_thresholds(LoadThresholds {
+        latency: app_config.sync_defer_latency,
+        cpu_load: app_config.sync_defer_cpu_load,
+        max_deferral: app_config.sync_max_deferral,
+    })
+    .with_supplements(supplements)
+    .with_smoke_queries(app_config.smoke_queries.clone());
+
+    let index_handler = match app_config.memory_limit {
+        Some(bytes) => {
+            let response_cache = response_cache.clone();
+            let refinements = refinements.clone();
+
+            index_handler.with_memory_limit(MemoryLimit {
+                bytes,
+                usage: || allocator::stats().map(|s| s.resident()),
+                shrink: Some(Arc::new(move || {
+                    let responses = response_cache.shrink();
+                    let refined = refinements.shrink();
+                    tracing::warn!(responses, refined, "Caches shrunk above the memory limit");
+                })),
+            })
+        }
+        None => index_handler,
+    };
+
+    let index_handler = if fallback_clients.is_empty() {
+        index_handler
+    } else {
+        let defaults = FailoverPolicy::default();
+        index_handler.with_fallback_origins(
+            fallback_clients,
+            FailoverPolicy {
+                max_failures: app_config
+                    .api_failover_max_failures
+                    .unwrap_or(defaults.max_failures)
+                    .max(1),
+                failback_after: app_config
+                    .api_failback_after
+                    .unwrap_or(defaults.failback_after),
+            },
+        )
+    };
+
+    let index_handler = match &app_config.items_snapshot_file {
+        Some(path) => index_handler.with_snapshot(path.clone()),
+        None => index_handler,
+    };
+
+    let index_handler = match app_config.index_optimize_at {
+        Some(at) => index_handler.with_optimize_schedule(OptimizeSchedule {
+            at,
+            target_segments: app_config.index_optimize_segments,
+        }),
+        None => index_handler,
+    };
+
+    let index_handler = match app_config.index_stats_cache_ttl {
+        Some(ttl) => index_handler.with_stats_cache_ttl(ttl),
+        None => index_handler,
+    };
+
+    let index_handler = match app_config.api_rate_limit {
+        Some(per_second) if per_second > 0.0 => index_handler.with_rate_limit(RateLimit {
+            per_second,
+            burst: app_config
+                .api_rate_burst
+                .unwrap_or_else(|| per_second.ceil() as u32)
+                .max(1),
+        }),
+        _ => index_handler,
+    };
+
+    let status = index_handler.status_ref();
+    let load_monitor = index_handler.load_monitor();
+    let update_trigger = index_handler.trigger();
+
+    if let Some(interval) = app_config.index_health_check_interval {
+        let checker = HealthChecker::new(index.clone(), status.clone(), interval);
+        let signal = shutdown_signal.subscribe();
+        tokio::spawn(async move { checker.run(signal).await });
+    }
+
+    if let Some(path) = app_config.jwt_secret_file.clone() {
+        let config = token_config.clone();
+        let signal = shutdown_signal.subscribe();
+        tokio::spawn(async move { reload_secret_on_hangup(config, path, signal).await });
+    }
+
+    let api_client = Arc::new(RwLock::new(api_client));
+
+    if let Some(path) = app_config.api_token_file.clone() {
+        let token = Zeroizing::new(app_config.api_token.expose_secret().clone());
+        let client = api_client.clone();
+        let trigger = update_trigger.clone();
+        let signal = shutdown_signal.subscribe();
+        tokio::spawn(async move {
+            reload_api_token_on_hangup(api_options, token, path, client, trigger, signal).await
+        });
+    }
+
+    if let Some(interval) = app_config.token_revocation_sweep_interval {
+        let client = api_client.clone();
+        let signal = shutdown_signal.subscribe();
+        tokio::spawn(async move { revocations.sweep(client, interval, signal).await });
+    }
+
+    if app_config.ranking_file.is_some() {
+        let ranking = ranking.clone();
+        let interval = app_config.ranking_reload_interval;
+        let signal = shutdown_signal.subscribe();
+        tokio::spawn(async move { ranking.watch(interval, signal).await });
+    }
+
+    let maintenance = Arc::<Maintenance>::default();
+
+    if !app_config.health_webhook_urls.is_empty() {
+        let notifier = HealthNotifier::new(
+            app_config.health_webhook_urls.clone(),
+            status.clone(),
+            index.clone(),
+            maintenance.clone(),
+        )?
+        .with_debounce(app_config.health_webhook_debounce)
+        .with_max_failed_syncs(app_config.health_webhook_sync_failures);
+        let signal = shutdown_signal.subscribe();
+        tokio::spawn(async move { notifier.run(signal).await });
+    }
+
+    let state = AppState {
+        index,
+        index_status: status,
+        token_config,
+        api_client,
+        experiments: Arc::new(experiments),
+        deprecations: Arc::new(deprecations),
+        load_monitor,
+        update_trigger,
+        type_quotas: Arc::new(type_quotas),
+        usage_tracker: Arc::new(UsageTracker::new(QuotaLimits {
+            daily_requests: app_config.quota_daily_requests,
+            monthly_requests: app_config.quota_monthly_requests,
+            daily_bytes: app_config.quota_daily_bytes,
+            monthly_bytes: app_config.quota_monthly_bytes,
+        })),
+        audience_tiers: Arc::new(audience_tiers),
+        replay_guard: Arc::new(ReplayGuard::new(app_config.jwt_require_one_time_creation)),
+        scope_policy: Arc::new(ScopePolicy::new(app_config.jwt_trusted_subjects.clone())),
+        authorizer: Arc::new(authorizer),
+        user_cache: Arc::new(
+            UserCache::new(
+                app_config.token_user_cache_ttl,
+                app_config.token_user_cache_negative_ttl,
+                app_config.token_user_cache_max_staleness,
+            )
+            .with_grace(app_config.token_renewal_grace),
+        ),
+        slow_queries: Arc::new(SlowQueryLog::new(app_config.slow_query_threshold)),
+        language_detection: LanguageDetection {
+            enabled: app_config.detect_query_language,
+        },
+        search_links: SearchLinks {
+            ttl: app_config.search_link_ttl,
+        },
+        result_limits: ResultLimits {
+            max: app_config.search_max_limit,
+            max_summary: app_config.search_max_limit_summary,
+        },
+        response_cache,
+        refinements,
+        kind_boosts: Arc::default(),
+        kind_aliases: Arc::new(kind_aliases),
+        ranking,
+        maintenance,
+        version: Arc::new(VersionInfo::new(app_config.server_tls)),
+    };
+
+    let middleware = ServiceBuilder::new()
+        .layer(HandleErrorLayer::new(error::handle_error))
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(LoadShedder::new(app_config.max_concurrent_requests)),
+            shedding::shed,
+        ))
+        .timeout(REQUEST_TIMEOUT)
+        .layer(SetSensitiveHeadersLayer::new(once(AUTHORIZATION)))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(logging::request_span::<Body>)
+                .on_response(
+                    DefaultOnResponse::new()
+                        .include_headers(true)
+                        .latency_unit(LatencyUnit::Micros),
+                ),
+        );
+
+    let svc_routes: Router<()> = Router::new()
+        .route("/version", get(version::get))
+        .nest(
+            "/search",
+            BodyLimit(app_config.body_limit_search).apply(
+                search::routes()
+                    .route_layer(axum::middleware::from_fn_with_state(
+                        state.clone(),
+                        quota::enforce,
+                    ))
+                    .route_layer(axum::middleware::from_fn_with_state(
+                        state.clone(),
+                        search::require_populated,
+                    )),
+            ),
+        )
+        .nest(
+            "/token",
+            BodyLimit(app_config.body_limit_token).apply(token::routes()),
+        )
+        .nest("/health", health::routes())
+        .nest(
+            "/admin",
+            IpFilter::new(
+                app_config.admin_ip_allow.clone(),
+                app_config.admin_ip_deny.clone(),
+            )
+            .apply(
+                BodyLimit(app_config.body_limit_admin).apply(admin::routes(app_config.admin_ui)),
+            ),
+        )
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            deprecation::emit,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            maintenance::enforce,
+        ))
+        .layer(axum::middleware::from_fn(negotiate::negotiate))
+        .with_state(state);
+
+    let routes = Router::new()
+        .route("/", get(|| async { env!("CARGO_PKG_VERSION") }))
+        .merge(svc_routes)
+        .layer(middleware.into_inner());
+
+    Ok((routes, index_handler))
+}
+
+/// Serves the routes until the shutdown signal is received
+pub async fn serve(
+    app_config: AppConfig,
+    routes: Router,
+    shutdown_signal: &Sender<()>,
+) -> Result<()> {
+    let addr = SocketAddr::from((app_config.server_addr, app_config.server_port));
+    let mut incoming = AddrIncoming::bind(&addr)?;
+    incoming.set_keepalive(app_config.server_tcp_keepalive);
+    let incoming = proxy::incoming(incoming, app_config.server_proxy_protocol);
+
+    let max_requests = app_config.server_max_connection_requests;
+    let make_service = routes
+        .into_make_service_with_connect_info::<ClientAddr>()
+        .map_response(move |svc| RequestLimit::new(svc, max_requests));
+
+    let mut signal = shutdown_signal.subscribe();
+    let graceful_shutdown = async move {
+        signal.recv().await.ok();
+    };
+
+    if app_config.server_tls {
+        let tls_options = app_config.tls_options();
+        let (config, challenge) = if !app_config.server_acme_domains.is_empty() {
+            let (config, challenge) = AcmeOptions {
+                domains: app_config.server_acme_domains,
+                contact: app_config.server_acme_contact,
+                cache_dir: app_config.server_acme_cache_dir,
+                staging: app_config.server_acme_staging,
+                tls: tls_options,
+            }
+            .provision()?;
+
+            (config, Some(challenge))
+        } else {
+            let certs = {
+                let path = app_config
+                    .server_tls_cert
+                    .ok_or(Error::MissingConfigVar("SEARCH_SERVER_TLS_CERT"))?;
+                let file = std::fs::read(path)?;
+                utils::read_certs(&file[..])?
+                    .into_iter()
+                    .map(rustls::Certificate)
+                    .collect()
+            };
+            let key = {
+                let path = app_config
+                    .server_tls_key
+                    .ok_or(Error::MissingConfigVar("SEARCH_SERVER_TLS_KEY"))?;
+                let file = Zeroizing::new(std::fs::read(path)?);
+                utils::read_key(&file[..]).map(rustls::PrivateKey)?
+            };
+
+            (tls::server_config(certs, key, &tls_options)?, None)
+        };
+
+        let incoming = tls::incoming(incoming, config, challenge);
+        let incoming = LimitedIncoming::new(incoming, app_config.server_max_connections);
+        let server = Server::builder(incoming)
+            .http1_keepalive(app_config.server_http1_keepalive)
+            .http1_header_read_timeout(app_config.server_header_read_timeout)
+            .serve(make_service)
+            .with_graceful_shutdown(graceful_shutdown);
+
+        tracing::info!(
+            ipAddress =? addr.ip(),
+            port =? addr.port(),
+            "HTTPS server started"
+        );
+
+        server.await?;
+    } else {
+        let incoming = LimitedIncoming::new(incoming, app_config.server_max_connections);
+        let server = Server::builder(incoming)
+            .http1_keepalive(app_config.server_http1_keepalive)
+            .http1_header_read_timeout(app_config.server_header_read_timeout)
+            .serve(make_service)
+            .with_graceful_shutdown(graceful_shutdown);
+
+        tracing::info!(
+            ipAddress =? addr.ip(),
+            port =? addr.port(),
+            "HTTP server started"
+        );
+
+        server.await?;
+    }
+
+    Ok(())
+}
+
+/// Settings the API clients of all origins are built with
+struct ApiClientOptions {
+    /// Primary origin followed by the fallback origins
+    origins: Vec<String>,
+    ca: Option<PathBuf>,
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
+}
+
+impl ApiClientOptions {
+    /// Builds the clients of the origins in order
+    async fn build(&self, token: &str) -> Result<Vec<Client>> {
+        let mut clients = Vec::with_capacity(self.origins.len());
+        for origin in self.origins.iter() {
+            let client = build_api_client(
+                origin,
+                token,
+                self.ca.as_deref(),
+                self.cert.as_deref(),
+                self.key.as_deref(),
+            )
+            .await?;
+            clients.push(client);
+        }
+
+        Ok(clients)
+    }
+}
+
+/// Rebuilds the API clients with the token of the file on every SIGHUP
+async fn reload_api_token_on_hangup(
+    options: ApiClientOptions,
+    mut token: Zeroizing<String>,
+    path: PathBuf,
+    api_client: Arc<RwLock<Client>>,
+    trigger: UpdateTrigger,
+    mut shutdown: Receiver<()>,
+) {
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::error!(error = %e, "Couldn't listen for SIGHUP, the API token won't be reloaded");
+            return;
+        }
+    };
+
+    loop {
+        tokio::select! {
+            _ = hangup.recv() => {}
+            _ = shutdown.recv() => break,
+        }
+
+        let secret = match utils::read_secret(&path) {
+            Ok(s) if s.expose_secret() == token.as_str() => continue,
+            Ok(s) => s,
+            Err(e) => {
+                tracing::error!(error = %e, path = %path.display(), "Couldn't read the API token file");
+                continue;
+            }
+        };
+
+        let clients = match options.build(secret.expose_secret()).await {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::error!(error = %e, "Couldn't build the API clients with the reloaded token");
+                continue;
+            }
+        };
+
+        *api_client.write().unwrap() = clients[0].clone();
+        if !trigger.replace_clients(clients).await {
+            tracing::warn!(
+                "Index handler isn't running, only the API client of requests was replaced"
+            );
+        }
+
+        *token = secret.expose_secret().clone();
+        tracing::info!("API token reloaded");
+    }
+}
+
+async fn build_api_client(
+    origin: &str,
+    token: &str,
+    ca: Option<&Path>,
+    cert: Option<&Path>,
+    key: Option<&Path>,
+) -> Result<Client> {
+    let builder = ClientBuilder::default()
+        .set_origin(origin)
+        .set_token(token)
+        .set_trust_dns(false)
+        .set_user_agent(USER_AGENT);
+
+    let builder = if let Some(v) = ca {
+        builder.set_ca(v.to_path_buf())
+    } else {
+        builder
+    };
+
+    let builder = if let Some(cert) = cert {
+        if let Some(key) = key {
+            builder.set_keypair(cert.to_path_buf(), key.to_path_buf())
+        } else {
+            return Err(error::Error::MissingConfigVar("SEARCH_API_CLIENT_KEY"));
+        }
+    } else {
+        builder
+    };
+
+    Ok(builder.build().await?)
+}
+
+/// Parses currency rates to roubles in the form of `currency:rate`
+fn parse_currency_rates(values: &[String]) -> std::result::Result<HashMap<String, f64>, String> {
+    values
+        .iter()
+        .map(|v| {
+            let (currency, rate) = v
+                .split_once(':')
+                .ok_or_else(|| format!("invalid rate \"{}\"", v))?;
+            let rate = rate
+                .trim()
+                .parse::<f64>()
+                .map_err(|e| format!("invalid rate \"{}\": {}", v, e))?;
+
+            Ok((currency.trim().to_lowercase(), rate))
+        })
+        .collect()
+}
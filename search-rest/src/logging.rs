@@ -0,0 +1,167 @@
+use crate::connection::ClientAddr;
+
+use std::{
+    io::{stdout, IsTerminal},
+    path::Path,
+};
+
+use axum::extract::ConnectInfo;
+use hyper::{header::HeaderName, Request};
+use serde::Deserialize;
+use tracing::{field, Span, Subscriber};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{
+    fmt::MakeWriter, layer::SubscriberExt, registry::LookupSpan, util::SubscriberInitExt,
+    EnvFilter, Layer,
+};
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Json,
+    Pretty,
+    Compact,
+    #[default]
+    Full,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    #[default]
+    Daily,
+    Never,
+}
+
+impl From<&LogRotation> for Rotation {
+    fn from(rotation: &LogRotation) -> Self {
+        match rotation {
+            LogRotation::Minutely => Rotation::MINUTELY,
+            LogRotation::Hourly => Rotation::HOURLY,
+            LogRotation::Daily => Rotation::DAILY,
+            LogRotation::Never => Rotation::NEVER,
+        }
+    }
+}
+
+/// Rotating log file sink
+#[derive(Debug)]
+pub struct FileSink<'a> {
+    pub path: &'a Path,
+    pub format: &'a LogFormat,
+    pub rotation: &'a LogRotation,
+}
+
+/// Initializes logging to stdout and optionally to a rotating file.
+///
+/// The returned guard flushes the file sink on drop and must be held until exit.
+pub fn init(console: &LogFormat, file: Option<FileSink>) -> Option<WorkerGuard> {
+    let console = layer(console, stdout, stdout().is_terminal());
+
+    let (file, guard) = match file {
+        Some(sink) => {
+            let dir = sink.path.parent().unwrap_or_else(|| Path::new("."));
+            let prefix = sink.path.file_name().unwrap_or_default();
+            let appender = RollingFileAppender::new(sink.rotation.into(), dir, prefix);
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+
+            (Some(layer(sink.format, writer, false)), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    tracing_subscriber::registry()
+        .with(console)
+        .with(file)
+        .with(EnvFilter::from_default_env())
+        .init();
+
+    guard
+}
+
+fn layer<S, W>(format: &LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+
+    match format {
+        LogFormat::Json => layer.json().boxed(),
+        LogFormat::Pretty => layer.pretty().boxed(),
+        LogFormat::Compact => layer.compact().boxed(),
+        LogFormat::Full => layer.boxed(),
+    }
+}
+
+static TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+
+/// W3C trace context of a request
+#[derive(Debug, Clone, Copy)]
+pub struct TraceContext<'a> {
+    pub trace_id: &'a str,
+    pub parent_id: &'a str,
+}
+
+impl<'a> TraceContext<'a> {
+    /// Parses a `traceparent` header value
+    pub fn parse(value: &'a str) -> Option<Self> {
+        let mut parts = value.trim().split('-');
+        let (version, trace_id, parent_id, flags) =
+            (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+
+        let is_hex = |s: &str, len: usize| {
+            s.len() == len && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        };
+
+        if !is_hex(version, 2) || version == "ff" || !is_hex(flags, 2) {
+            return None;
+        }
+        if !is_hex(trace_id, 32) || trace_id.bytes().all(|b| b == b'0') {
+            return None;
+        }
+        if !is_hex(parent_id, 16) || parent_id.bytes().all(|b| b == b'0') {
+            return None;
+        }
+
+        Some(Self {
+            trace_id,
+            parent_id,
+        })
+    }
+}
+
+/// Creates the span of a request with empty client fields, which are recorded
+/// once the token is decoded
+pub fn request_span<B>(req: &Request<B>) -> Span {
+    let remote_addr = req
+        .extensions()
+        .get::<ConnectInfo<ClientAddr>>()
+        .and_then(|c| c.0 .0);
+
+    let trace = req
+        .headers()
+        .get(TRACEPARENT)
+        .and_then(|v| v.to_str().ok())
+        .and_then(TraceContext::parse);
+
+    tracing::debug_span!(
+        "request",
+        trace_id = trace.map(|t| t.trace_id),
+        parent_id = trace.map(|t| t.parent_id),
+        remote_addr = ?remote_addr,
+        method = %req.method(),
+        uri = %req.uri(),
+        version = ?req.version(),
+        headers = ?req.headers(),
+        client_name = field::Empty,
+        client_version = field::Empty,
+    )
+}
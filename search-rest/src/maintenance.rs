@@ -0,0 +1,105 @@
+use crate::model::Response as ModelResponse;
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    extract::State,
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use hyper::{header::RETRY_AFTER, http::HeaderValue, StatusCode};
+use metrics::{gauge, increment_counter};
+use serde::Serialize;
+
+/// Paths which stay available during maintenance
+const EXEMPT_PATHS: [&str; 3] = ["/health", "/admin", "/version"];
+
+/// Maintenance window set by an admin
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceWindow {
+    pub message: String,
+    pub since: DateTime<Utc>,
+    /// Expected duration, sent as `Retry-After`
+    #[serde(with = "humantime_serde")]
+    pub retry_after: Option<Duration>,
+    /// Subject of the admin who started the maintenance
+    pub started_by: String,
+}
+
+/// Body of responses rejected during maintenance
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct MaintenanceResponse {
+    code: u16,
+    message: String,
+    maintenance: bool,
+    since: DateTime<Utc>,
+}
+
+#[derive(Debug, Default)]
+pub struct Maintenance {
+    window: Mutex<Option<MaintenanceWindow>>,
+}
+
+impl Maintenance {
+    pub fn current(&self) -> Option<MaintenanceWindow> {
+        self.window.lock().unwrap().clone()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.window.lock().unwrap().is_some()
+    }
+
+    pub fn start(&self, window: MaintenanceWindow) {
+        gauge!("search_maintenance_active", 1.0);
+        *self.window.lock().unwrap() = Some(window);
+    }
+
+    /// Ends the maintenance, returns the ended window
+    pub fn end(&self) -> Option<MaintenanceWindow> {
+        gauge!("search_maintenance_active", 0.0);
+        self.window.lock().unwrap().take()
+    }
+}
+
+fn is_exempt(path: &str) -> bool {
+    path == "/"
+        || EXEMPT_PATHS
+            .iter()
+            .any(|p| path == *p || path.starts_with(&format!("{}/", p)))
+}
+
+/// Middleware rejecting requests to non-admin routes during maintenance
+pub async fn enforce<B>(
+    State(maintenance): State<Arc<Maintenance>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let window = match maintenance.current() {
+        Some(w) if !is_exempt(req.uri().path()) => w,
+        _ => return next.run(req).await,
+    };
+
+    increment_counter!("search_maintenance_rejected_requests_total");
+
+    let body = MaintenanceResponse {
+        code: StatusCode::SERVICE_UNAVAILABLE.as_u16(),
+        message: window.message,
+        maintenance: true,
+        since: window.since,
+    };
+    let mut res = ModelResponse::with_status(StatusCode::SERVICE_UNAVAILABLE, body).into_response();
+
+    if let Some(retry_after) = window.retry_after {
+        res.headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from(retry_after.as_secs().max(1)));
+    }
+
+    res
+}
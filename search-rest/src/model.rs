@@ -1,11 +1,24 @@
+use crate::negotiate::Negotiable;
+
 use hyper::StatusCode;
-use serde::{Serialize, Serializer};
 
+pub use search_models::Status;
+
+/// Response body which is encoded in the format negotiated by
+/// `negotiate::negotiate`, JSON by default
 #[derive(Debug)]
 pub struct Response<T>(StatusCode, T)
 where
     T: serde::Serialize;
 
+/// Format of list responses, bare responses contain only the `data` field
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Envelope {
+    #[default]
+    Object,
+    Bare,
+}
+
 impl<T> Response<T>
 where
     T: serde::Serialize,
@@ -23,48 +36,13 @@ where
 
 impl<T> axum::response::IntoResponse for Response<T>
 where
-    T: serde::Serialize,
+    T: serde::Serialize + Send + Sync + 'static,
 {
     fn into_response(self) -> axum::response::Response {
         let mut res = axum::Json(&self.1).into_response();
         *res.status_mut() = self.0;
+        res.extensions_mut().insert(Negotiable(Box::new(self.1)));
 
         res
     }
 }
-
-#[derive(Debug, Clone, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Status {
-    #[serde(serialize_with = "se_status_code_as_u16")]
-    pub code: StatusCode,
-    pub message: String,
-}
-
-impl Status {
-    pub fn new<S>(code: StatusCode, message: S) -> Self
-    where
-        S: ToString,
-    {
-        Self {
-            code,
-            message: message.to_string(),
-        }
-    }
-}
-
-impl axum::response::IntoResponse for Status {
-    fn into_response(self) -> axum::response::Response {
-        let mut res = axum::Json(&self).into_response();
-        *res.status_mut() = self.code;
-
-        res
-    }
-}
-
-fn se_status_code_as_u16<S>(x: &StatusCode, s: S) -> Result<S::Ok, S::Error>
-where
-    S: Serializer,
-{
-    s.serialize_u16(x.as_u16())
-}
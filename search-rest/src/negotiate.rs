@@ -0,0 +1,261 @@
+use crate::model::{Envelope, Status};
+
+use axum::{
+    body::{boxed, Full},
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use hyper::{
+    header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE, VARY},
+    http::HeaderValue,
+    HeaderMap, StatusCode,
+};
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EncodeError {
+    #[error("JSON encoding error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("MessagePack encoding error: {0}")]
+    MessagePack(#[from] rmp_serde::encode::Error),
+}
+
+/// Serialization format of response bodies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    /// One JSON document per line for each element of list responses
+    Ndjson,
+    /// One record for each element of list responses
+    Csv,
+    MessagePack,
+}
+
+impl Format {
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "application/json" | "application/*" | "*/*" => Some(Self::Json),
+            "application/x-ndjson" | "application/ndjson" => Some(Self::Ndjson),
+            "text/csv" => Some(Self::Csv),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(Self::MessagePack)
+            }
+            _ => None,
+        }
+    }
+
+    /// Selects the supported format with the highest quality of the `Accept`
+    /// header, JSON is the default
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let mut best: Option<(Self, f32)> = None;
+
+        let ranges = headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','));
+
+        for range in ranges {
+            let mut params = range.split(';');
+            let media_type = params
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_ascii_lowercase();
+            let quality = params
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())
+                .unwrap_or_default();
+
+            if let Some(format) = Self::from_media_type(&media_type) {
+                if quality > 0.0 && best.map_or(true, |(_, q)| quality > q) {
+                    best = Some((format, quality));
+                }
+            }
+        }
+
+        best.map_or(Self::Json, |(f, _)| f)
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Ndjson => "application/x-ndjson",
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::MessagePack => "application/msgpack",
+        }
+    }
+}
+
+/// Requested envelope, `envelope=false` or the `bare` profile of the `Accept`
+/// header select bare responses
+fn envelope<B>(req: &Request<B>) -> Envelope {
+    let by_query = req
+        .uri()
+        .query()
+        .is_some_and(|q| q.split('&').any(|p| p == "envelope=false"));
+
+    let by_accept = req
+        .headers()
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .any(|p| matches!(p.trim(), "profile=bare" | "profile=\"bare\""));
+
+    if by_query || by_accept {
+        Envelope::Bare
+    } else {
+        Envelope::Object
+    }
+}
+
+/// Response body which can be encoded in any format
+pub trait Encode: Send + Sync {
+    fn encode(&self, format: Format, envelope: Envelope) -> Result<Vec<u8>, EncodeError>;
+}
+
+impl<T> Encode for T
+where
+    T: Serialize + Send + Sync,
+{
+    fn encode(&self, format: Format, envelope: Envelope) -> Result<Vec<u8>, EncodeError> {
+        let body = match (format, envelope) {
+            (Format::Json, Envelope::Object) => return Ok(serde_json::to_vec(self)?),
+            (Format::MessagePack, Envelope::Object) => return Ok(rmp_serde::to_vec_named(self)?),
+            _ => serde_json::to_value(self)?,
+        };
+
+        let body = match (envelope, body) {
+            (Envelope::Bare, Value::Object(mut map)) if map.contains_key("data") => {
+                map.remove("data").unwrap_or_default()
+            }
+            (_, body) => body,
+        };
+
+        let encoded = match format {
+            Format::Json => serde_json::to_vec(&body)?,
+            Format::MessagePack => rmp_serde::to_vec_named(&body)?,
+            Format::Ndjson => {
+                let mut buf = Vec::new();
+                for row in rows(body) {
+                    serde_json::to_writer(&mut buf, &row)?;
+                    buf.push(b'\n');
+                }
+                buf
+            }
+            Format::Csv => write_csv(&rows(body)),
+        };
+
+        Ok(encoded)
+    }
+}
+
+/// Body of a `model::Response`, attached to the response extensions for
+/// the negotiation
+pub struct Negotiable(pub Box<dyn Encode>);
+
+/// Returns the elements of list responses or the body as single element
+fn rows(body: Value) -> Vec<Value> {
+    match body {
+        Value::Object(mut map) if matches!(map.get("data"), Some(Value::Array(_))) => {
+            match map.remove("data") {
+                Some(Value::Array(rows)) => rows,
+                _ => unreachable!(),
+            }
+        }
+        Value::Array(rows) => rows,
+        body => vec![body],
+    }
+}
+
+fn write_csv(rows: &[Value]) -> Vec<u8> {
+    let mut columns: Vec<&str> = Vec::new();
+    for row in rows {
+        if let Value::Object(map) = row {
+            for key in map.keys() {
+                if !columns.contains(&key.as_str()) {
+                    columns.push(key);
+                }
+            }
+        }
+    }
+
+    let mut out = String::new();
+    let mut write_record = |fields: Vec<String>| {
+        let record = fields
+            .iter()
+            .map(|f| csv_field(f))
+            .collect::<Vec<_>>()
+            .join(",");
+        out.push_str(&record);
+        out.push_str("\r\n");
+    };
+
+    if columns.is_empty() {
+        write_record(vec!["value".to_string()]);
+        for row in rows {
+            write_record(vec![csv_value(Some(row))]);
+        }
+    } else {
+        write_record(columns.iter().map(|c| c.to_string()).collect());
+        for row in rows {
+            write_record(
+                columns
+                    .iter()
+                    .map(|c| csv_value(row.as_object().and_then(|m| m.get(*c))))
+                    .collect(),
+            );
+        }
+    }
+
+    out.into_bytes()
+}
+
+fn csv_value(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(v) => v.to_string(),
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Middleware encoding response bodies in the format and envelope requested
+/// by the client
+pub async fn negotiate<B>(req: Request<B>, next: Next<B>) -> Response {
+    let format = Format::from_headers(req.headers());
+    let envelope = envelope(&req);
+
+    let mut res = next.run(req).await;
+    res.headers_mut()
+        .append(VARY, HeaderValue::from_static("accept"));
+
+    let body = match res.extensions_mut().remove::<Negotiable>() {
+        Some(b) if format != Format::Json || envelope != Envelope::Object => b,
+        _ => return res,
+    };
+
+    let encoded = match body.0.encode(format, envelope) {
+        Ok(e) => e,
+        Err(e) => return Status::new(StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
+
+    let (mut parts, _) = res.into_parts();
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(format.content_type()),
+    );
+
+    Response::from_parts(parts, boxed(Full::from(encoded)))
+}
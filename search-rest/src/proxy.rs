@@ -0,0 +1,145 @@
+use crate::connection::{RemoteAddr, SetupIncoming};
+
+use std::{
+    io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use hyper::server::accept::Accept;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+
+/// Signature of PROXY protocol v2 headers
+const SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Accepts connections preceded by a PROXY protocol v2 header if enabled,
+/// otherwise connections are passed through
+pub fn incoming<A>(incoming: A, enabled: bool) -> SetupIncoming<A, ProxiedStream<A::Conn>>
+where
+    A: Accept,
+    A::Conn: AsyncRead + Unpin + Send + 'static,
+{
+    SetupIncoming::new(incoming, move |mut conn| async move {
+        let source = if enabled {
+            read_header(&mut conn).await?
+        } else {
+            None
+        };
+
+        Ok(Some(ProxiedStream {
+            inner: conn,
+            source,
+        }))
+    })
+}
+
+/// Reads a PROXY protocol v2 header and returns the source address of the
+/// proxied connection
+async fn read_header<R>(io: &mut R) -> io::Result<Option<SocketAddr>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut head = [0u8; 16];
+    io.read_exact(&mut head).await?;
+
+    if head[..12] != SIGNATURE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing PROXY protocol header",
+        ));
+    }
+    if head[12] >> 4 != 2 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unsupported PROXY protocol version",
+        ));
+    }
+
+    let len = u16::from_be_bytes([head[14], head[15]]) as usize;
+    let mut payload = vec![0u8; len];
+    io.read_exact(&mut payload).await?;
+
+    // Connections of the LOCAL command are established by the proxy itself
+    if head[12] & 0x0F == 0 {
+        return Ok(None);
+    }
+
+    let addr = match head[13] >> 4 {
+        0x1 if len >= 12 => {
+            let ip = Ipv4Addr::new(payload[0], payload[1], payload[2], payload[3]);
+            let port = u16::from_be_bytes([payload[8], payload[9]]);
+            Some(SocketAddr::from((ip, port)))
+        }
+        0x2 if len >= 36 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&payload[..16]);
+            let port = u16::from_be_bytes([payload[32], payload[33]]);
+            Some(SocketAddr::from((Ipv6Addr::from(octets), port)))
+        }
+        _ => None,
+    };
+
+    Ok(addr)
+}
+
+/// Connection with the source address given by the proxy
+pub struct ProxiedStream<C> {
+    inner: C,
+    source: Option<SocketAddr>,
+}
+
+impl<C> RemoteAddr for ProxiedStream<C>
+where
+    C: RemoteAddr,
+{
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.source.or_else(|| self.inner.remote_addr())
+    }
+}
+
+impl<C> AsyncRead for ProxiedStream<C>
+where
+    C: AsyncRead + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<C> AsyncWrite for ProxiedStream<C>
+where
+    C: AsyncWrite + Unpin,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
@@ -0,0 +1,310 @@
+use crate::{
+    audience::{AudienceTier, AudienceTiers},
+    error,
+    extract::SearchClaims,
+    model::Status,
+};
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    body::HttpBody,
+    extract::State,
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use hyper::{header::HeaderName, HeaderMap, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+static RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+static RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+static RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+const BUCKET_SECS: i64 = 60 * 60;
+const DAY_BUCKETS: i64 = 24;
+const MONTH_BUCKETS: i64 = 30 * DAY_BUCKETS;
+
+#[derive(Debug, thiserror::Error)]
+pub enum QuotaError {
+    #[error("Daily request quota exceeded")]
+    DailyRequests,
+    #[error("Monthly request quota exceeded")]
+    MonthlyRequests,
+    #[error("Daily data volume quota exceeded")]
+    DailyBytes,
+    #[error("Monthly data volume quota exceeded")]
+    MonthlyBytes,
+}
+
+impl error::ErrorResponse for QuotaError {
+    type Response = Status;
+
+    fn status_code(&self) -> StatusCode {
+        StatusCode::TOO_MANY_REQUESTS
+    }
+
+    fn error_response(&self) -> Self::Response {
+        Status::new(self.status_code(), self.to_string())
+    }
+}
+
+/// Quotas per subject, a monthly window is 30 days
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaLimits {
+    pub daily_requests: Option<u64>,
+    pub monthly_requests: Option<u64>,
+    pub daily_bytes: Option<u64>,
+    pub monthly_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    hour: i64,
+    requests: u64,
+    bytes: u64,
+}
+
+/// Hourly usage buckets of a subject covering the monthly window
+#[derive(Debug, Default)]
+struct SubjectUsage(VecDeque<Bucket>);
+
+impl SubjectUsage {
+    fn prune(&mut self, hour: i64) {
+        while matches!(self.0.front(), Some(b) if b.hour <= hour - MONTH_BUCKETS) {
+            self.0.pop_front();
+        }
+    }
+
+    fn current(&mut self, hour: i64) -> &mut Bucket {
+        self.prune(hour);
+
+        if !matches!(self.0.back(), Some(b) if b.hour == hour) {
+            self.0.push_back(Bucket {
+                hour,
+                requests: 0,
+                bytes: 0,
+            });
+        }
+
+        self.0.back_mut().unwrap()
+    }
+
+    /// Hour at which the oldest bucket leaves the window
+    fn window_reset(&self, hour: i64, buckets: i64) -> i64 {
+        self.0
+            .iter()
+            .find(|b| b.hour > hour - buckets && b.requests > 0)
+            .map_or(hour + 1, |b| b.hour + buckets)
+    }
+
+    fn sum(&self, hour: i64, buckets: i64) -> (u64, u64) {
+        self.0
+            .iter()
+            .filter(|b| b.hour > hour - buckets)
+            .fold((0, 0), |(r, d), b| (r + b.requests, d + b.bytes))
+    }
+}
+
+/// Usage of a subject within a window
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowUsage {
+    requests: u64,
+    bytes: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remaining_requests: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remaining_bytes: Option<u64>,
+}
+
+impl WindowUsage {
+    fn new(
+        (requests, bytes): (u64, u64),
+        max_requests: Option<u64>,
+        max_bytes: Option<u64>,
+    ) -> Self {
+        Self {
+            requests,
+            bytes,
+            remaining_requests: max_requests.map(|m| m.saturating_sub(requests)),
+            remaining_bytes: max_bytes.map(|m| m.saturating_sub(bytes)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Usage {
+    daily: WindowUsage,
+    monthly: WindowUsage,
+}
+
+/// Request quota of the most restrictive window
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub limit: u64,
+    pub remaining: u64,
+    /// Seconds until requests become available again
+    pub reset: i64,
+}
+
+impl RateLimit {
+    fn add_headers(&self, headers: &mut HeaderMap) {
+        headers.insert(RATELIMIT_LIMIT.clone(), self.limit.into());
+        headers.insert(RATELIMIT_REMAINING.clone(), self.remaining.into());
+        headers.insert(RATELIMIT_RESET.clone(), self.reset.into());
+    }
+}
+
+/// Tracks request counts and data volume per token subject in sliding windows
+#[derive(Debug, Default)]
+pub struct UsageTracker {
+    limits: QuotaLimits,
+    subjects: Mutex<HashMap<String, SubjectUsage>>,
+}
+
+impl UsageTracker {
+    pub fn new(limits: QuotaLimits) -> Self {
+        Self {
+            limits,
+            subjects: Mutex::default(),
+        }
+    }
+
+    /// Returns the quotas of the tier or the global quotas
+    fn limits<'a>(&'a self, tier: Option<&'a AudienceTier>) -> &'a QuotaLimits {
+        tier.and_then(|t| t.quota.as_ref()).unwrap_or(&self.limits)
+    }
+
+    fn current_hour() -> i64 {
+        Utc::now().timestamp() / BUCKET_SECS
+    }
+
+    /// Counts a request of the subject, fails if a quota is exhausted
+    pub fn acquire(&self, subject: &str, tier: Option<&AudienceTier>) -> Result<(), QuotaError> {
+        let hour = Self::current_hour();
+        let mut subjects = self.subjects.lock().unwrap();
+        let usage = subjects.entry(subject.to_owned()).or_default();
+        usage.prune(hour);
+
+        let (daily_requests, daily_bytes) = usage.sum(hour, DAY_BUCKETS);
+        let (monthly_requests, monthly_bytes) = usage.sum(hour, MONTH_BUCKETS);
+
+        let limits = self.limits(tier);
+        if limits.daily_requests.is_some_and(|l| daily_requests >= l) {
+            return Err(QuotaError::DailyRequests);
+        }
+        if limits
+            .monthly_requests
+            .is_some_and(|l| monthly_requests >= l)
+        {
+            return Err(QuotaError::MonthlyRequests);
+        }
+        if limits.daily_bytes.is_some_and(|l| daily_bytes >= l) {
+            return Err(QuotaError::DailyBytes);
+        }
+        if limits.monthly_bytes.is_some_and(|l| monthly_bytes >= l) {
+            return Err(QuotaError::MonthlyBytes);
+        }
+
+        usage.current(hour).requests += 1;
+
+        Ok(())
+    }
+
+    /// Adds the response size to the data volume of the subject
+    pub fn record_bytes(&self, subject: &str, bytes: u64) {
+        let hour = Self::current_hour();
+        let mut subjects = self.subjects.lock().unwrap();
+        let usage = subjects.entry(subject.to_owned()).or_default();
+
+        usage.current(hour).bytes += bytes;
+    }
+
+    /// Returns the request quota of the window with the least remaining requests
+    pub fn rate_limit(&self, subject: &str, tier: Option<&AudienceTier>) -> Option<RateLimit> {
+        let hour = Self::current_hour();
+        let limits = self.limits(tier);
+        let subjects = self.subjects.lock().unwrap();
+        let usage = subjects.get(subject);
+
+        [
+            (limits.daily_requests, DAY_BUCKETS),
+            (limits.monthly_requests, MONTH_BUCKETS),
+        ]
+        .into_iter()
+        .filter_map(|(limit, buckets)| {
+            let limit = limit?;
+            let (requests, reset) = usage
+                .map(|u| (u.sum(hour, buckets).0, u.window_reset(hour, buckets)))
+                .unwrap_or((0, hour + 1));
+
+            Some(RateLimit {
+                limit,
+                remaining: limit.saturating_sub(requests),
+                reset: (reset * BUCKET_SECS - Utc::now().timestamp()).max(0),
+            })
+        })
+        .min_by_key(|r| r.remaining)
+    }
+
+    pub fn usage(&self, subject: &str, tier: Option<&AudienceTier>) -> Usage {
+        let hour = Self::current_hour();
+        let subjects = self.subjects.lock().unwrap();
+        let (daily, monthly) = subjects
+            .get(subject)
+            .map(|u| (u.sum(hour, DAY_BUCKETS), u.sum(hour, MONTH_BUCKETS)))
+            .unwrap_or_default();
+
+        let limits = self.limits(tier);
+        Usage {
+            daily: WindowUsage::new(daily, limits.daily_requests, limits.daily_bytes),
+            monthly: WindowUsage::new(monthly, limits.monthly_requests, limits.monthly_bytes),
+        }
+    }
+}
+
+/// Middleware enforcing the quotas of the token subject.
+///
+/// The resolved audience tier is attached to the request extensions.
+pub async fn enforce<B>(
+    State(tracker): State<Arc<UsageTracker>>,
+    State(tiers): State<Arc<AudienceTiers>>,
+    SearchClaims(claims): SearchClaims,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let tier = tiers.resolve(&claims);
+
+    if let Err(e) = tracker.acquire(claims.subject(), tier) {
+        debug!(subject = ?claims.subject(), tier = ?tier.map(|t| &t.name), error = %e, "Quota exceeded");
+        let mut res = crate::Error::from(e).into_response();
+        if let Some(limit) = tracker.rate_limit(claims.subject(), tier) {
+            limit.add_headers(res.headers_mut());
+        }
+
+        return res;
+    }
+    let rate_limit = tracker.rate_limit(claims.subject(), tier);
+
+    if let Some(tier) = tier {
+        req.extensions_mut().insert(tier.clone());
+    }
+
+    let mut res = next.run(req).await;
+    if let Some(limit) = rate_limit {
+        limit.add_headers(res.headers_mut());
+    }
+
+    let size = res.body().size_hint();
+    tracker.record_bytes(claims.subject(), size.exact().unwrap_or(size.lower()));
+
+    res
+}
@@ -0,0 +1,61 @@
+use std::collections::{BTreeMap, HashMap};
+
+/// Aliases of item kinds, resolved when parsing the `kind` parameter
+#[derive(Debug, Default)]
+pub struct KindAliases(HashMap<String, Vec<String>>);
+
+impl KindAliases {
+    /// Parses aliases in the form of `alias:kind`, an alias may be repeated
+    /// to resolve to multiple kinds
+    pub fn parse<S>(values: &[S]) -> Result<Self, String>
+    where
+        S: AsRef<str>,
+    {
+        let mut aliases: HashMap<String, Vec<String>> = HashMap::new();
+
+        for value in values {
+            let (alias, kind) = value
+                .as_ref()
+                .split_once(':')
+                .map(|(a, k)| (a.trim(), k.trim()))
+                .filter(|(a, k)| !a.is_empty() && !k.is_empty())
+                .ok_or_else(|| format!("invalid kind alias \"{}\"", value.as_ref()))?;
+
+            aliases
+                .entry(alias.to_lowercase())
+                .or_default()
+                .push(kind.to_string());
+        }
+
+        Ok(Self(aliases))
+    }
+
+    /// Replaces aliases by their kinds, returns the kinds and the resolved
+    /// aliases
+    pub fn resolve(&self, kinds: Vec<String>) -> (Vec<String>, BTreeMap<String, Vec<String>>) {
+        if self.0.is_empty() {
+            return (kinds, BTreeMap::new());
+        }
+
+        let mut resolved_kinds = Vec::with_capacity(kinds.len());
+        let mut resolved = BTreeMap::new();
+
+        for kind in kinds {
+            let targets = match self.0.get(&kind.to_lowercase()) {
+                Some(targets) => {
+                    resolved.insert(kind, targets.clone());
+                    targets.clone()
+                }
+                None => vec![kind],
+            };
+
+            for target in targets {
+                if !resolved_kinds.contains(&target) {
+                    resolved_kinds.push(target);
+                }
+            }
+        }
+
+        (resolved_kinds, resolved)
+    }
+}
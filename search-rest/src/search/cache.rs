@@ -0,0 +1,267 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use metrics::increment_counter;
+use search_index::{IndexDoc, QueryOutput};
+use serde::Serialize;
+
+const DEFAULT_CAPACITY: usize = 1000;
+
+#[derive(Debug)]
+struct Entry {
+    output: QueryOutput,
+    /// Generation of the index the output was computed on
+    generation: u64,
+    inserted: Instant,
+    refreshing: bool,
+}
+
+/// Result of a cache lookup
+pub enum Lookup {
+    Fresh(QueryOutput),
+    /// Stale output of the given index generation, `refresh` is set for the
+    /// first caller that should recompute it in the background
+    Stale {
+        output: QueryOutput,
+        generation: u64,
+        refresh: bool,
+    },
+    Miss,
+}
+
+/// Statistics of a cache since the start or the last clear
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub entries: usize,
+    pub capacity: usize,
+    pub hits: u64,
+    pub stale_hits: u64,
+    pub misses: u64,
+    pub hit_ratio: f64,
+    /// Estimated from the encoded size of the documents
+    pub approximate_bytes: usize,
+}
+
+impl CacheStats {
+    pub(super) fn new(entries: usize, capacity: usize, hits: [u64; 3], bytes: usize) -> Self {
+        let [hits, stale_hits, misses] = hits;
+        let total = hits + stale_hits + misses;
+
+        Self {
+            entries,
+            capacity,
+            hits,
+            stale_hits,
+            misses,
+            hit_ratio: if total > 0 {
+                (hits + stale_hits) as f64 / total as f64
+            } else {
+                0.0
+            },
+            approximate_bytes: bytes,
+        }
+    }
+}
+
+/// Approximate memory of cached documents
+pub(super) fn approximate_size(key: &str, docs: &[IndexDoc]) -> usize {
+    key.len() + serde_json::to_vec(docs).map_or(0, |v| v.len())
+}
+
+/// Lookup counts of a cache
+#[derive(Debug, Default)]
+pub(super) struct HitCounter {
+    hits: AtomicU64,
+    stale_hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl HitCounter {
+    pub(super) fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn stale_hit(&self) {
+        self.stale_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn get(&self) -> [u64; 3] {
+        [
+            self.hits.load(Ordering::Relaxed),
+            self.stale_hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        ]
+    }
+
+    pub(super) fn reset(&self) {
+        self.hits.store(0, Ordering::Relaxed);
+        self.stale_hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Cache of search outputs served stale while they're refreshed
+#[derive(Debug)]
+pub struct ResponseCache {
+    ttl: Option<Duration>,
+    max_staleness: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<String, Entry>>,
+    counter: HitCounter,
+}
+
+impl ResponseCache {
+    pub fn new(ttl: Option<Duration>, max_staleness: Duration) -> Self {
+        Self {
+            ttl,
+            max_staleness,
+            capacity: DEFAULT_CAPACITY,
+            entries: Mutex::default(),
+            counter: HitCounter::default(),
+        }
+    }
+
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.ttl.is_some() && self.capacity > 0
+    }
+
+    /// Looks up the output of the key, entries of another index generation
+    /// are stale since the modification time of the index
+    pub fn get(&self, key: &str, generation: u64, modified: DateTime<Utc>) -> Lookup {
+        let ttl = match self.ttl {
+            Some(t) => t,
+            None => return Lookup::Miss,
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        let entry = match entries.get_mut(key) {
+            Some(e) => e,
+            None => {
+                increment_counter!("search_response_cache_total", "result" => "miss");
+                self.counter.miss();
+                return Lookup::Miss;
+            }
+        };
+
+        let age = entry.inserted.elapsed();
+        if entry.generation == generation && age < ttl {
+            increment_counter!("search_response_cache_total", "result" => "hit");
+            self.counter.hit();
+            return Lookup::Fresh(entry.output.clone());
+        }
+
+        // Staleness counts from the point the entry became outdated
+        let stale_for = if entry.generation != generation {
+            (Utc::now() - modified).to_std().unwrap_or_default()
+        } else {
+            age - ttl
+        };
+        if stale_for > self.max_staleness {
+            entries.remove(key);
+            increment_counter!("search_response_cache_total", "result" => "miss");
+            self.counter.miss();
+            return Lookup::Miss;
+        }
+
+        increment_counter!("search_response_cache_total", "result" => "stale");
+        self.counter.stale_hit();
+
+        let refresh = !entry.refreshing;
+        entry.refreshing = true;
+
+        Lookup::Stale {
+            output: entry.output.clone(),
+            generation: entry.generation,
+            refresh,
+        }
+    }
+
+    pub fn insert(&self, key: String, output: QueryOutput, generation: u64) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, e)| e.inserted)
+                .map(|(k, _)| k.clone());
+            if let Some(k) = oldest {
+                entries.remove(&k);
+            }
+        }
+
+        entries.insert(
+            key,
+            Entry {
+                output,
+                generation,
+                inserted: Instant::now(),
+                refreshing: false,
+            },
+        );
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.entries.lock().unwrap();
+        let bytes = entries
+            .iter()
+            .map(|(k, e)| approximate_size(k, &e.output.docs))
+            .sum();
+
+        CacheStats::new(entries.len(), self.capacity, self.counter.get(), bytes)
+    }
+
+    /// Removes all entries and resets the statistics, returns the number of
+    /// removed entries
+    pub fn clear(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let count = entries.len();
+        entries.clear();
+        self.counter.reset();
+
+        count
+    }
+
+    /// Removes the older half of the entries, returns the number of removed
+    /// entries
+    pub fn shrink(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+
+        let count = entries.len();
+
+        let mut inserted = entries.values().map(|e| e.inserted).collect::<Vec<_>>();
+        inserted.sort_unstable();
+        if let Some(newest_removed) = (count / 2).checked_sub(1).map(|i| inserted[i]) {
+            entries.retain(|_, e| e.inserted > newest_removed);
+        }
+
+        count - entries.len()
+    }
+
+    /// Allows another refresh of the entry after a failed one
+    pub fn refresh_failed(&self, key: &str) {
+        if let Some(e) = self.entries.lock().unwrap().get_mut(key) {
+            e.refreshing = false;
+        }
+    }
+}
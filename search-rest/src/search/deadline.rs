@@ -0,0 +1,57 @@
+use crate::model::Response as ModelResponse;
+
+use std::{future::Future, time::Duration};
+
+use axum::response::{IntoResponse, Response};
+use hyper::StatusCode;
+use metrics::increment_counter;
+use search_models::AppliedOptions;
+use serde::Serialize;
+use tokio::time::Instant;
+
+/// Body of searches aborted by the deadline of the client
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeadlineExceeded {
+    code: u16,
+    message: &'static str,
+    query: String,
+    options: AppliedOptions,
+    index_generation: u64,
+    budget_ms: f64,
+    took_ms: f64,
+}
+
+/// Runs the future until the deadline, returns `None` if it's exceeded
+pub async fn within_deadline<F>(deadline: Option<Instant>, future: F) -> Option<F::Output>
+where
+    F: Future,
+{
+    match deadline {
+        Some(d) => tokio::time::timeout_at(d, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+/// Response of a search aborted by the deadline, with the metadata known so far
+pub fn deadline_exceeded(
+    query: String,
+    options: AppliedOptions,
+    index_generation: u64,
+    budget: Duration,
+    took: Duration,
+) -> Response {
+    increment_counter!("search_deadline_exceeded_total");
+
+    let body = DeadlineExceeded {
+        code: StatusCode::GATEWAY_TIMEOUT.as_u16(),
+        message: "the deadline of the request was exceeded",
+        query,
+        options,
+        index_generation,
+        budget_ms: budget.as_secs_f64() * 1000.0,
+        took_ms: took.as_secs_f64() * 1000.0,
+    };
+
+    ModelResponse::with_status(StatusCode::GATEWAY_TIMEOUT, body).into_response()
+}
@@ -0,0 +1,113 @@
+use std::{fs, path::Path};
+
+use search_index::{FieldBoosts, NameAnalyzer};
+use serde::{Deserialize, Serialize};
+
+const fn default_weight() -> u32 {
+    1
+}
+
+/// Ranking variant of an experiment
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Variant {
+    pub name: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    #[serde(default)]
+    pub boosts: Boosts,
+    /// Used unless the request sets the conjunction itself
+    pub conjunction: Option<bool>,
+    /// Analyzer the query is matched against the name with
+    pub analyzer: Option<NameAnalyzer>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct Boosts {
+    pub name: f32,
+    pub description: f32,
+    pub compatible: f32,
+}
+
+impl Default for Boosts {
+    fn default() -> Self {
+        let boosts = FieldBoosts::default();
+
+        Self {
+            name: boosts.name,
+            description: boosts.description,
+            compatible: boosts.compatible,
+        }
+    }
+}
+
+impl From<&Boosts> for FieldBoosts {
+    fn from(boosts: &Boosts) -> Self {
+        Self {
+            name: boosts.name,
+            description: boosts.description,
+            compatible: boosts.compatible,
+        }
+    }
+}
+
+/// Ranking experiment with weighted variants
+#[derive(Debug, Default)]
+pub struct Experiments {
+    variants: Vec<Variant>,
+    total_weight: u64,
+}
+
+impl Experiments {
+    pub fn new(variants: Vec<Variant>) -> Self {
+        let variants: Vec<Variant> = variants.into_iter().filter(|v| v.weight > 0).collect();
+        let total_weight = variants.iter().map(|v| u64::from(v.weight)).sum();
+
+        Self {
+            variants,
+            total_weight,
+        }
+    }
+
+    pub fn from_file<P>(path: P) -> crate::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = fs::read(path)?;
+        let variants = serde_json::from_slice(&file)?;
+
+        Ok(Self::new(variants))
+    }
+
+    /// Assigns a variant to the given subject.
+    ///
+    /// The assignment is stable as long as the variant configuration doesn't change.
+    pub fn assign(&self, subject: &str) -> Option<&Variant> {
+        if self.total_weight == 0 {
+            return None;
+        }
+
+        let mut bucket = fnv1a(subject.as_bytes()) % self.total_weight;
+
+        for variant in self.variants.iter() {
+            let weight = u64::from(variant.weight);
+            if bucket < weight {
+                return Some(variant);
+            }
+            bucket -= weight;
+        }
+
+        None
+    }
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    bytes.iter().fold(OFFSET_BASIS, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(PRIME)
+    })
+}
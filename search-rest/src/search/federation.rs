@@ -0,0 +1,70 @@
+use std::str::FromStr;
+
+use search_index::{DocType, Index, QueryBuilder, QueryOptions, QueryOutput};
+use tokio::task::JoinSet;
+
+/// Maximum number of results per doc type in federated searches
+#[derive(Debug, Clone, Default)]
+pub struct TypeQuotas(Vec<(DocType, usize)>);
+
+impl TypeQuotas {
+    /// Parses quotas in the form of `type:quota`
+    pub fn parse<S>(values: &[S]) -> Result<Self, String>
+    where
+        S: AsRef<str>,
+    {
+        let quotas = values
+            .iter()
+            .map(|v| {
+                let (t, q) = v
+                    .as_ref()
+                    .split_once(':')
+                    .ok_or_else(|| format!("invalid quota \"{}\"", v.as_ref()))?;
+                let t = DocType::from_str(t.trim()).map_err(|e| e.to_string())?;
+                let q = q
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|e| format!("invalid quota \"{}\": {}", v.as_ref(), e))?;
+
+                Ok((t, q))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Self(quotas))
+    }
+
+    pub fn get(&self, r#type: &DocType) -> Option<usize> {
+        self.0.iter().find(|(t, _)| t == r#type).map(|(_, q)| *q)
+    }
+}
+
+/// Searches the doc types concurrently and merges the results by score
+pub async fn federated_search(
+    index: &Index,
+    query: &str,
+    types: &[DocType],
+    opts: QueryOptions,
+    quotas: &TypeQuotas,
+) -> crate::Result<search_index::Result<QueryOutput>> {
+    let limit = opts.limit;
+
+    let mut set = JoinSet::new();
+    for t in types.iter().cloned() {
+        let index = index.clone();
+        let builder = QueryBuilder::new(query).with_type(t.clone());
+        let mut opts = opts.clone();
+        opts.limit = quotas.get(&t).map_or(limit, |q| q.min(limit));
+
+        set.spawn_blocking(move || index.search(&builder, opts));
+    }
+
+    let mut outputs = Vec::with_capacity(types.len());
+    while let Some(result) = set.join_next().await {
+        match result? {
+            Ok(o) => outputs.push(o),
+            Err(e) => return Ok(Err(e)),
+        }
+    }
+
+    Ok(Ok(QueryOutput::merge(outputs, limit)))
+}
@@ -0,0 +1,121 @@
+use std::{ops::Bound, str::FromStr};
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use search_index::{NumericField, NumericFilter};
+use serde::{de, Deserialize, Deserializer};
+
+/// Numeric range in the form of `min..max`, `min..`, `..max` or an exact value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumericRange {
+    lower: Bound<f64>,
+    upper: Bound<f64>,
+}
+
+impl NumericRange {
+    pub fn at_least(value: f64) -> Self {
+        Self {
+            lower: Bound::Included(value),
+            upper: Bound::Unbounded,
+        }
+    }
+
+    pub fn at_most(value: f64) -> Self {
+        Self {
+            lower: Bound::Unbounded,
+            upper: Bound::Included(value),
+        }
+    }
+
+    pub fn into_filter(self, field: NumericField) -> NumericFilter {
+        NumericFilter {
+            field,
+            lower: self.lower,
+            upper: self.upper,
+        }
+    }
+}
+
+impl FromStr for NumericRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |v: &str| -> Result<Bound<f64>, String> {
+            let v = v.trim();
+            if v.is_empty() {
+                return Ok(Bound::Unbounded);
+            }
+
+            v.parse::<f64>()
+                .map(Bound::Included)
+                .map_err(|_| format!("invalid number \"{}\"", v))
+        };
+
+        let range = match s.split_once("..") {
+            Some((lower, upper)) => Self {
+                lower: parse(lower)?,
+                upper: parse(upper)?,
+            },
+            None => {
+                let value = parse(s)?;
+                Self {
+                    lower: value,
+                    upper: value,
+                }
+            }
+        };
+
+        if range.lower == Bound::Unbounded && range.upper == Bound::Unbounded {
+            return Err("range must have at least one bound".to_string());
+        }
+
+        Ok(range)
+    }
+}
+
+impl<'de> Deserialize<'de> for NumericRange {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Point in time as date (`2024-01-01`), which is midnight UTC, or RFC 3339 timestamp
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DateParam(DateTime<Utc>);
+
+impl DateParam {
+    /// Returns the range of documents modified at or after this time
+    pub fn since(self) -> NumericRange {
+        NumericRange::at_least(self.0.timestamp() as f64)
+    }
+}
+
+impl FromStr for DateParam {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            let time = Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap());
+            return Ok(Self(time));
+        }
+
+        DateTime::parse_from_rfc3339(s)
+            .map(|t| Self(t.with_timezone(&Utc)))
+            .map_err(|_| format!("invalid date \"{}\"", s))
+    }
+}
+
+impl<'de> Deserialize<'de> for DateParam {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
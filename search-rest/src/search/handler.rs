@@ -1,16 +1,57 @@
 use crate::{
-    extract::{Query, TokenData},
-    model::Response,
-    token::Claims,
+    audience::AudienceTier,
+    authentication::{AuthenticationError, TokenConfig},
+    deprecation::DeprecationNotice,
+    extract::{AcceptLanguage, Json, Query, RequestDeadline, SearchClaims, TokenData},
+    model::{Response, Status},
+    token::{Claims, Scope},
 };
 
-use super::SearchError;
+use super::{
+    cache::{Lookup, ResponseCache},
+    deadline::{deadline_exceeded, within_deadline},
+    federation::federated_search,
+    filter::{DateParam, NumericRange},
+    normalize::normalize_query,
+    refine::{Refinement, RefinementStore},
+    Experiments, ResultLimits, SearchContext, SearchError, SearchLinks, TypeQuotas,
+};
+
+use std::{collections::BTreeMap, str::FromStr, sync::Arc, time::Instant};
 
-use axum::extract::State;
-use search_index::{DocType, IndexDoc, QueryOptions};
+use axum::{
+    extract::{Extension, OriginalUri, Path, State},
+    response::IntoResponse,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use hyper::{
+    header::{HeaderName, ETAG, IF_NONE_MATCH},
+    http::HeaderValue,
+    HeaderMap, StatusCode,
+};
+use metrics::increment_counter;
+use ring::digest::{digest, SHA256};
+use search_index::{
+    detect_language, language_code, language_from_code, DocSummary, DocType, FieldBoosts, Index,
+    IndexDoc, Language, NumericField, NumericFilter, PatternQuery, QueryBuilder, QueryOptions,
+    QueryOutput, QueryTimings,
+};
+use search_models::{AppliedOptions, SearchMeta};
 use search_state::IndexState;
-use serde::{Deserialize, Serialize};
-use tracing::error;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use tracing::{debug, error, info};
+
+static SERVER_TIMING: HeaderName = HeaderName::from_static("server-timing");
+
+/// Length of the hash of entity tags in bytes before encoding
+const ETAG_HASH_LEN: usize = 12;
+
+/// Target of the feedback logs
+const FEEDBACK_TARGET: &str = "search_feedback";
+
+/// Maximum number of terms a pattern may match in all segments
+const MAX_PATTERN_TERMS: usize = 1000;
 
 const fn default_limit() -> usize {
     30
@@ -18,32 +59,197 @@ const fn default_limit() -> usize {
 
 #[derive(Debug, Deserialize)]
 pub struct QueryParams {
-    #[serde(alias = "q")]
+    #[serde(default, alias = "q")]
     query: String,
-    r#type: Option<DocType>,
-    kind: Option<String>,
+    /// Fingerprint of a previous result, which is filtered instead of
+    /// executing the query again
+    refine: Option<String>,
+    /// Doc types, either repeated (`type=item&type=preset`) or comma-separated
+    #[serde(default, deserialize_with = "de_list")]
+    r#type: Vec<DocType>,
+    /// Item kinds, either repeated or comma-separated
+    #[serde(default, deserialize_with = "de_list")]
+    kind: Vec<String>,
     #[serde(default = "default_limit")]
     limit: usize,
+    /// Requires all terms, defaults to the min-should-match of the ranking config
+    conjunction: Option<bool>,
+    lang: Option<String>,
+    /// Retry with a spell-corrected query if there are no hits
+    #[serde(default)]
+    autocorrect: bool,
+    /// Search invalid query syntax as plain terms instead of failing
     #[serde(default)]
-    conjunction: bool,
+    lenient: bool,
+    /// Maximum edit distance of name terms, e.g. 1 to match misspelled names
+    fuzzy: Option<u8>,
+    // Ballistics filters, either as range (`pen=40..60`) or comparison (`pen>=40`, `pen<=60`)
+    pen: Option<NumericRange>,
+    #[serde(rename = "pen>")]
+    pen_min: Option<f64>,
+    #[serde(rename = "pen<")]
+    pen_max: Option<f64>,
+    damage: Option<NumericRange>,
+    #[serde(rename = "damage>")]
+    damage_min: Option<f64>,
+    #[serde(rename = "damage<")]
+    damage_max: Option<f64>,
+    // Price filters in roubles
+    price: Option<NumericRange>,
+    #[serde(rename = "price>")]
+    price_min: Option<f64>,
+    #[serde(rename = "price<")]
+    price_max: Option<f64>,
+    /// Only items modified upstream at or after the date
+    modified_since: Option<DateParam>,
+}
+
+/// Deserializes repeated and comma-separated values of a parameter
+fn de_list<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    let values = Vec::<String>::deserialize(deserializer)?;
+
+    values
+        .iter()
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| v.parse().map_err(de::Error::custom))
+        .collect()
+}
+
+impl QueryParams {
+    fn numeric_filters(&self) -> Vec<NumericFilter> {
+        let ranges = [
+            (NumericField::Penetration, self.pen),
+            (
+                NumericField::Penetration,
+                self.pen_min.map(NumericRange::at_least),
+            ),
+            (
+                NumericField::Penetration,
+                self.pen_max.map(NumericRange::at_most),
+            ),
+            (NumericField::Damage, self.damage),
+            (
+                NumericField::Damage,
+                self.damage_min.map(NumericRange::at_least),
+            ),
+            (
+                NumericField::Damage,
+                self.damage_max.map(NumericRange::at_most),
+            ),
+            (NumericField::Price, self.price),
+            (
+                NumericField::Price,
+                self.price_min.map(NumericRange::at_least),
+            ),
+            (
+                NumericField::Price,
+                self.price_max.map(NumericRange::at_most),
+            ),
+            (
+                NumericField::Modified,
+                self.modified_since.map(DateParam::since),
+            ),
+        ];
+
+        ranges
+            .into_iter()
+            .filter_map(|(field, range)| range.map(|r| r.into_filter(field)))
+            .collect()
+    }
 }
 
+pub type SearchResult = search_models::SearchResult<ResultDoc>;
+
+/// Result document, reduced for tokens without the search scope
 #[derive(Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct SearchResult {
-    count: usize,
-    data: Vec<IndexDoc>,
+#[serde(untagged)]
+pub enum ResultDoc {
+    Full(IndexDoc),
+    Summary(DocSummary),
+}
+
+fn project(docs: Vec<IndexDoc>, claims: &Claims) -> Vec<ResultDoc> {
+    if claims.has_scope(&Scope::Search) {
+        docs.into_iter().map(ResultDoc::Full).collect()
+    } else {
+        docs.into_iter()
+            .map(|d| ResultDoc::Summary(d.into()))
+            .collect()
+    }
 }
 
 pub async fn get(
-    TokenData(_claims): TokenData<Claims, true>,
-    Query(opts): Query<QueryParams>,
-    State(state): State<IndexState>,
-) -> crate::Result<Response<SearchResult>> {
-    let query = &opts.query;
+    SearchClaims(claims): SearchClaims,
+    Query(mut opts): Query<QueryParams>,
+    State(ctx): State<SearchContext>,
+    tier: Option<Extension<AudienceTier>>,
+    notice: Option<Extension<DeprecationNotice>>,
+    AcceptLanguage(accepted): AcceptLanguage,
+    RequestDeadline(deadline): RequestDeadline,
+    req_headers: HeaderMap,
+) -> crate::Result<axum::response::Response> {
+    let SearchContext {
+        index: state,
+        experiments,
+        load,
+        quotas,
+        slow_queries,
+        detection,
+        cache,
+        limits,
+        refinements,
+        kind_boosts,
+        kind_aliases,
+        ranking,
+    } = ctx;
+
+    limits.check(opts.limit, &claims, tier.as_deref())?;
+
+    let warnings = notice.map(|n| n.0 .0).unwrap_or_default();
+
+    let (kind, resolved_aliases) = kind_aliases.resolve(std::mem::take(&mut opts.kind));
+    opts.kind = kind;
+
+    if let Some(fingerprint) = opts.refine.as_deref() {
+        let res = refine(
+            &refinements,
+            fingerprint,
+            &opts,
+            resolved_aliases,
+            &claims,
+            warnings,
+        )?;
+        return Ok(res.into_response());
+    }
+
+    let start = Instant::now();
+    let mut query = normalize_query(&opts.query);
+    let normalized_query = (query != opts.query).then(|| query.clone());
+    let variant = experiments.assign(claims.subject());
+    let ranking = ranking.current();
     let options = QueryOptions {
         limit: opts.limit,
-        conjunction: opts.conjunction,
+        // An explicit parameter takes precedence over the experiment
+        conjunction: opts
+            .conjunction
+            .or(variant.and_then(|v| v.conjunction))
+            .unwrap_or_else(|| ranking.conjunction()),
+        boosts: variant
+            .map(|v| FieldBoosts::from(&v.boosts))
+            .unwrap_or_else(|| ranking.field_boosts()),
+        filters: opts.numeric_filters(),
+        lenient: opts.lenient,
+        kind_boosts: kind_boosts.to_options(),
+        type_boosts: ranking.type_boosts.clone(),
+        fuzzy: opts.fuzzy,
+        name_analyzer: variant.and_then(|v| v.analyzer).unwrap_or_default(),
     };
 
     match query.len() {
@@ -52,22 +258,631 @@ pub async fn get(
         _ => {}
     }
 
-    let kinds = opts.kind.as_ref().map(|v| v.split(',').collect::<Vec<_>>());
+    let kinds =
+        (!opts.kind.is_empty()).then(|| opts.kind.iter().map(String::as_str).collect::<Vec<_>>());
 
-    let index = state.get_index();
+    let detected_language = match opts.lang {
+        None if detection.enabled => detect_language(&query)
+            .filter(|l| state.get_index_by_lang(*l).is_some())
+            .map(language_code),
+        _ => None,
+    };
 
-    match if let Some(t) = opts.r#type {
-        index.search_by_type(query, t, kinds.as_deref(), options)
-    } else {
-        index.query_top(query, options)
-    } {
-        Ok(d) => Ok(Response::new(SearchResult {
+    let lang = opts.lang.as_deref().or(detected_language);
+    let index = resolve_index(&state, lang, &accepted)?;
+    let index_modified = state
+        .get_modified_by_lang(index.language())
+        .await
+        .unwrap_or_default();
+    let index_generation = state
+        .get_generation_by_lang(index.language())
+        .unwrap_or_default();
+
+    let applied = AppliedOptions {
+        r#type: opts.r#type.clone(),
+        kind: kinds
+            .as_ref()
+            .map(|k| k.iter().map(|v| v.to_string()).collect()),
+        kind_aliases: resolved_aliases,
+        limit: options.limit,
+        conjunction: options.conjunction,
+        language: language_code(index.language()).to_string(),
+        sort: "relevance".to_string(),
+    };
+
+    let refinement_key = format!(
+        "{}|{:?}|{:?}|{:?}|{}|{}",
+        applied.language, opts.r#type, kinds, options, index_generation, ranking.hash
+    );
+
+    let etag_key = format!(
+        "{}|{}|{}|{:?}|{}",
+        refinement_key,
+        query,
+        opts.autocorrect,
+        variant.map(|v| &v.name),
+        claims.has_scope(&Scope::Search)
+    );
+    let etag = entity_tag(&etag_key, index_generation);
+    if is_not_modified(&req_headers, &etag) {
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, etag);
+        return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+    }
+
+    let exceeded = |query: &str, applied: &AppliedOptions| {
+        let budget = deadline
+            .map(|d| d.into_std().saturating_duration_since(start))
+            .unwrap_or_default();
+        debug!(query = ?query, budget_ms = budget.as_millis() as u64, "Query deadline exceeded");
+
+        deadline_exceeded(
+            query.to_owned(),
+            applied.clone(),
+            index_generation,
+            budget,
+            start.elapsed(),
+        )
+    };
+
+    let search_start = Instant::now();
+    let search = cached_execute(
+        &cache,
+        &index,
+        index_generation,
+        index_modified,
+        &ranking.synonyms.expand(&query),
+        &opts.r#type,
+        kinds.as_deref(),
+        options.clone(),
+        &quotas,
+    );
+    let (mut result, mut output_generation) = match within_deadline(deadline, search).await {
+        Some(r) => r?,
+        None => return Ok(exceeded(&query, &applied)),
+    };
+
+    let mut original_query = None;
+    if opts.autocorrect && matches!(&result, Ok(o) if o.docs.is_empty()) {
+        if let Some(corrected) = index.suggest(&query) {
+            debug!(query = ?query, corrected = ?corrected, "Query autocorrected");
+
+            let search = cached_execute(
+                &cache,
+                &index,
+                index_generation,
+                index_modified,
+                &ranking.synonyms.expand(&corrected),
+                &opts.r#type,
+                kinds.as_deref(),
+                options,
+                &quotas,
+            );
+            (result, output_generation) = match within_deadline(deadline, search).await {
+                Some(r) => r?,
+                None => return Ok(exceeded(&corrected, &applied)),
+            };
+            original_query = Some(std::mem::replace(&mut query, corrected));
+        }
+    }
+    let search_took = search_start.elapsed();
+    increment_counter!("search_queries_total");
+    load.record(search_took);
+    slow_queries.record(&query, search_took);
+
+    match result {
+        Ok(output) => {
+            let d = output.docs;
+
+            debug!(
+                query = ?query,
+                variant = ?variant.map(|v| &v.name),
+                tier = ?tier.as_ref().map(|t| &t.name),
+                count = d.len(),
+                "Query executed"
+            );
+
+            let took = start.elapsed();
+
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                SERVER_TIMING.clone(),
+                server_timing(&output.timings, took.as_secs_f64() * 1000.0),
+            );
+            // Stale outputs are tagged with their own generation, so clients
+            // holding them get the refreshed output once it's available
+            let etag = if output_generation == index_generation {
+                etag
+            } else {
+                entity_tag(&etag_key, output_generation)
+            };
+            headers.insert(ETAG, etag);
+
+            let fingerprint = refinements.insert(
+                &format!("{}|{}", refinement_key, query),
+                Refinement {
+                    docs: d.clone(),
+                    query: query.clone(),
+                    language: applied.language.clone(),
+                    modified: index_modified,
+                    generation: index_generation,
+                    ranking_hash: ranking.hash.clone(),
+                },
+            );
+
+            Ok((
+                headers,
+                Response::new(SearchResult {
+                    count: d.len(),
+                    data: project(d, &claims),
+                    meta: SearchMeta {
+                        query,
+                        original_query,
+                        normalized_query,
+                        options: applied,
+                        variant: variant.map(|v| v.name.clone()),
+                        detected_language: detected_language.map(String::from),
+                        warnings,
+                        fingerprint,
+                        index_modified,
+                        index_generation,
+                        ranking_hash: Some(ranking.hash.clone()),
+                        took_ms: took.as_secs_f64() * 1000.0,
+                    },
+                }),
+            )
+                .into_response())
+        }
+        Err(e) => {
+            error!(query = ?query, variant = ?variant.map(|v| &v.name), error = %e, "Query error");
+            Err(SearchError::IndexError(e).into())
+        }
+    }
+}
+
+/// Filters the result of a previous search by the types, kinds and numeric
+/// filters of the request
+fn refine(
+    refinements: &RefinementStore,
+    fingerprint: &str,
+    opts: &QueryParams,
+    kind_aliases: BTreeMap<String, Vec<String>>,
+    claims: &Claims,
+    warnings: Vec<String>,
+) -> crate::Result<Response<SearchResult>> {
+    let start = Instant::now();
+    let source = refinements
+        .get(fingerprint)
+        .ok_or(SearchError::UnknownRefinement)?;
+
+    let kinds = opts.kind.iter().map(String::as_str).collect::<Vec<_>>();
+    let filters = opts.numeric_filters();
+    let docs = source
+        .docs
+        .iter()
+        .filter(|d| d.matches(&opts.r#type, &kinds, &filters))
+        .take(opts.limit)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let refined = refinements.insert(
+        &format!(
+            "{}|{:?}|{:?}|{:?}|{}",
+            fingerprint, opts.r#type, kinds, filters, opts.limit
+        ),
+        Refinement {
+            docs: docs.clone(),
+            query: source.query.clone(),
+            language: source.language.clone(),
+            modified: source.modified,
+            generation: source.generation,
+            ranking_hash: source.ranking_hash.clone(),
+        },
+    );
+
+    Ok(Response::new(SearchResult {
+        count: docs.len(),
+        data: project(docs, claims),
+        meta: SearchMeta {
+            query: source.query.clone(),
+            original_query: None,
+            normalized_query: None,
+            options: AppliedOptions {
+                r#type: opts.r#type.clone(),
+                kind: (!kinds.is_empty()).then(|| opts.kind.clone()),
+                kind_aliases,
+                limit: opts.limit,
+                conjunction: opts.conjunction.unwrap_or_default(),
+                language: source.language.clone(),
+                sort: "relevance".to_string(),
+            },
+            variant: None,
+            detected_language: None,
+            warnings,
+            fingerprint: refined,
+            index_modified: source.modified,
+            index_generation: source.generation,
+            ranking_hash: Some(source.ranking_hash.clone()),
+            took_ms: start.elapsed().as_secs_f64() * 1000.0,
+        },
+    }))
+}
+
+/// Executes the query, using the cached output if available.
+///
+/// Stale outputs are served while they're recomputed in the background. Returns
+/// the index generation the output was computed on.
+#[allow(clippy::too_many_arguments)]
+async fn cached_execute(
+    cache: &Arc<ResponseCache>,
+    index: &Index,
+    generation: u64,
+    modified: DateTime<Utc>,
+    query: &str,
+    types: &[DocType],
+    kinds: Option<&[&str]>,
+    options: QueryOptions,
+    quotas: &Arc<TypeQuotas>,
+) -> crate::Result<(search_index::Result<QueryOutput>, u64)> {
+    if !cache.is_enabled() {
+        let result = execute(index, query, types, kinds, options, quotas).await?;
+        return Ok((result, generation));
+    }
+
+    let key = format!(
+        "{}|{:?}|{:?}|{}|{:?}",
+        language_code(index.language()),
+        types,
+        kinds,
+        query,
+        options
+    );
+
+    match cache.get(&key, generation, modified) {
+        Lookup::Fresh(output) => return Ok((Ok(output), generation)),
+        Lookup::Stale {
+            output,
+            generation: stale_generation,
+            refresh,
+        } => {
+            if refresh {
+                let cache = cache.clone();
+                let index = index.clone();
+                let query = query.to_owned();
+                let types = types.to_vec();
+                let kinds = kinds.map(|k| k.iter().map(|v| v.to_string()).collect::<Vec<_>>());
+                let quotas = quotas.clone();
+
+                tokio::spawn(async move {
+                    let kinds = kinds
+                        .as_ref()
+                        .map(|k| k.iter().map(|v| v.as_str()).collect::<Vec<_>>());
+
+                    match execute(&index, &query, &types, kinds.as_deref(), options, &quotas).await
+                    {
+                        Ok(Ok(output)) => cache.insert(key, output, generation),
+                        _ => cache.refresh_failed(&key),
+                    }
+                });
+            }
+
+            return Ok((Ok(output), stale_generation));
+        }
+        Lookup::Miss => {}
+    }
+
+    let result = execute(index, query, types, kinds, options, quotas).await?;
+    if let Ok(output) = &result {
+        cache.insert(key, output.clone(), generation);
+    }
+
+    Ok((result, generation))
+}
+
+async fn execute(
+    index: &Index,
+    query: &str,
+    types: &[DocType],
+    kinds: Option<&[&str]>,
+    options: QueryOptions,
+    quotas: &TypeQuotas,
+) -> crate::Result<search_index::Result<QueryOutput>> {
+    let result = match types {
+        [] => federated_search(index, query, &DocType::ALL, options, quotas).await?,
+        [t] => {
+            // Blocking search, which is left behind if the deadline is exceeded
+            let index = index.clone();
+            let builder = QueryBuilder::new(query)
+                .with_type(t.clone())
+                .with_kinds(kinds.unwrap_or_default().iter().copied());
+
+            tokio::task::spawn_blocking(move || index.search(&builder, options)).await?
+        }
+        types => federated_search(index, query, types, options, quotas).await?,
+    };
+
+    Ok(result)
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchLink {
+    /// Path and query of the search, accepted without a bearer token
+    url: String,
+    expires: DateTime<Utc>,
+}
+
+/// Signs a short-lived link to the search with the parameters, so it can be
+/// shared with anonymous visitors
+pub async fn link(
+    TokenData(claims): TokenData<Claims, true>,
+    OriginalUri(uri): OriginalUri,
+    // Rejects parameters the search wouldn't accept
+    Query(_): Query<QueryParams>,
+    Query(params): Query<Vec<(String, String)>>,
+    State(links): State<SearchLinks>,
+    State(config): State<TokenConfig>,
+) -> crate::Result<Response<SearchLink>> {
+    if !links.enabled() {
+        return Err(SearchError::LinksDisabled.into());
+    }
+
+    let (query, expires) = links.sign(params, &claims, &config);
+    let path = uri.path().strip_suffix("/link").unwrap_or("/search");
+
+    debug!(subject = ?claims.subject(), expires = %expires, "Search link created");
+
+    Ok(Response::new(SearchLink {
+        url: format!("{}?{}", path, query),
+        expires,
+    }))
+}
+
+/// Result a user selected for a query
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Feedback {
+    query: String,
+    /// ID of the selected document, none if no result was selected
+    selected: Option<String>,
+    /// Position of the selected document in the results
+    position: Option<usize>,
+}
+
+/// Logs the feedback with the experiment variant of the subject
+pub async fn feedback(
+    TokenData(claims): TokenData<Claims, true>,
+    State(experiments): State<Arc<Experiments>>,
+    Json(body): Json<Feedback>,
+) -> crate::Result<Status> {
+    let variant = experiments
+        .assign(claims.subject())
+        .map(|v| v.name.as_str());
+
+    info!(
+        target: FEEDBACK_TARGET,
+        variant = ?variant,
+        query = %body.query,
+        selected = ?body.selected,
+        position = ?body.position,
+        "Search feedback"
+    );
+    increment_counter!(
+        "search_feedback_total",
+        "variant" => variant.unwrap_or("none").to_owned(),
+        "selected" => body.selected.is_some().to_string()
+    );
+
+    Ok(Status::new(StatusCode::ACCEPTED, "feedback recorded"))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PatternParams {
+    #[serde(alias = "q")]
+    query: String,
+    #[serde(default = "default_limit")]
+    limit: usize,
+    lang: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PatternResult {
+    count: usize,
+    data: Vec<IndexDoc>,
+}
+
+pub async fn pattern(
+    TokenData(claims): TokenData<Claims, true>,
+    Query(opts): Query<PatternParams>,
+    State(state): State<IndexState>,
+    State(limits): State<ResultLimits>,
+    tier: Option<Extension<AudienceTier>>,
+    AcceptLanguage(accepted): AcceptLanguage,
+) -> crate::Result<(HeaderMap, Response<PatternResult>)> {
+    if !claims.has_scope(&Scope::Pattern) {
+        return Err(AuthenticationError::InsufficientPermission.into());
+    }
+    limits.check(opts.limit, &claims, tier.as_deref())?;
+
+    let start = Instant::now();
+    let pattern = PatternQuery::from_str(&opts.query).map_err(SearchError::IndexError)?;
+    let index = resolve_index(&state, opts.lang.as_deref(), &accepted)?;
+
+    let result = tokio::task::spawn_blocking(move || {
+        index.search_pattern(&pattern, opts.limit, MAX_PATTERN_TERMS)
+    })
+    .await?;
+
+    match result {
+        Ok(output) => {
+            let d = output.docs;
+
+            debug!(subject = ?claims.subject(), query = ?opts.query, count = d.len(), "Pattern query executed");
+
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                SERVER_TIMING.clone(),
+                server_timing(&output.timings, start.elapsed().as_secs_f64() * 1000.0),
+            );
+
+            Ok((
+                headers,
+                Response::new(PatternResult {
+                    count: d.len(),
+                    data: d,
+                }),
+            ))
+        }
+        Err(e) => {
+            error!(query = ?opts.query, error = %e, "Pattern query error");
+            Err(SearchError::IndexError(e).into())
+        }
+    }
+}
+
+const fn default_suggest_limit() -> usize {
+    10
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SuggestParams {
+    #[serde(alias = "q")]
+    query: String,
+    #[serde(default = "default_suggest_limit")]
+    limit: usize,
+    lang: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuggestResult {
+    count: usize,
+    data: Vec<DocSummary>,
+}
+
+pub async fn suggest(
+    TokenData(claims): TokenData<Claims, true>,
+    Query(opts): Query<SuggestParams>,
+    State(state): State<IndexState>,
+    State(limits): State<ResultLimits>,
+    tier: Option<Extension<AudienceTier>>,
+    AcceptLanguage(accepted): AcceptLanguage,
+) -> crate::Result<Response<SuggestResult>> {
+    limits.check(opts.limit, &claims, tier.as_deref())?;
+
+    if opts.query.trim().is_empty() {
+        return Err(SearchError::TermTooShort.into());
+    }
+
+    let index = resolve_index(&state, opts.lang.as_deref(), &accepted)?;
+
+    match index.complete(&opts.query, opts.limit) {
+        Ok(d) => Ok(Response::new(SuggestResult {
+            count: d.len(),
+            data: d.into_iter().map(DocSummary::from).collect(),
+        })),
+        Err(e) => {
+            error!(query = ?opts.query, error = %e, "Suggest query error");
+            Err(SearchError::IndexError(e).into())
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RelatedParams {
+    #[serde(default = "default_limit")]
+    limit: usize,
+    lang: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelatedResult {
+    count: usize,
+    data: Vec<ResultDoc>,
+}
+
+pub async fn related(
+    TokenData(claims): TokenData<Claims, true>,
+    Path(id): Path<String>,
+    Query(opts): Query<RelatedParams>,
+    State(state): State<IndexState>,
+    State(limits): State<ResultLimits>,
+    tier: Option<Extension<AudienceTier>>,
+    AcceptLanguage(accepted): AcceptLanguage,
+) -> crate::Result<Response<RelatedResult>> {
+    limits.check(opts.limit, &claims, tier.as_deref())?;
+
+    let index = resolve_index(&state, opts.lang.as_deref(), &accepted)?;
+
+    match index.related(&id, opts.limit) {
+        Ok(d) => Ok(Response::new(RelatedResult {
             count: d.len(),
-            data: d,
+            data: project(d, &claims),
         })),
         Err(e) => {
-            error!(query = ?query, error = %e, "Query error");
+            error!(id = ?id, error = %e, "Related query error");
             Err(SearchError::IndexError(e).into())
         }
     }
 }
+
+/// Selects the index by the explicit language, the accepted languages or English as fallback
+fn resolve_index(
+    state: &IndexState,
+    lang: Option<&str>,
+    accepted: &[String],
+) -> Result<Index, SearchError> {
+    let index = match lang {
+        Some(code) => language_from_code(code)
+            .and_then(|l| state.get_index_by_lang(l))
+            .ok_or(SearchError::UnsupportedLanguage)?,
+        None => accepted
+            .iter()
+            .filter_map(|code| language_from_code(code))
+            .find_map(|l| state.get_index_by_lang(l))
+            .or_else(|| state.get_index_by_lang(Language::English))
+            .unwrap_or_else(|| state.get_index()),
+    };
+
+    Ok(index)
+}
+
+/// Weak entity tag of a search response, derived from the request key and
+/// the index generation
+fn entity_tag(key: &str, generation: u64) -> HeaderValue {
+    let hash = digest(&SHA256, key.as_bytes());
+    let tag = format!(
+        "W/\"{}-{}\"",
+        generation,
+        URL_SAFE_NO_PAD.encode(&hash.as_ref()[..ETAG_HASH_LEN])
+    );
+
+    HeaderValue::from_str(&tag).unwrap()
+}
+
+/// Returns true if the `If-None-Match` header of the request matches the tag
+fn is_not_modified(headers: &HeaderMap, etag: &HeaderValue) -> bool {
+    let etag = etag.to_str().unwrap_or_default();
+
+    headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .any(|v| v == "*" || v.trim_start_matches("W/") == etag.trim_start_matches("W/"))
+}
+
+fn server_timing(timings: &QueryTimings, total_ms: f64) -> HeaderValue {
+    let value = format!(
+        "parse;dur={:.3}, search;dur={:.3}, fetch;dur={:.3}, total;dur={:.3}",
+        timings.parse.as_secs_f64() * 1000.0,
+        timings.search.as_secs_f64() * 1000.0,
+        timings.fetch.as_secs_f64() * 1000.0,
+        total_ms,
+    );
+
+    HeaderValue::from_str(&value).unwrap()
+}
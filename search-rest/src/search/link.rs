@@ -0,0 +1,141 @@
+use crate::{
+    authentication::{TokenConfig, TokenError},
+    token::{Claims, Scope},
+};
+
+use std::time::Duration;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Query parameter of the encoded link claims
+const LINK_PARAM: &str = "link";
+
+/// Query parameter of the signature, it's not part of the signed message
+const SIGNATURE_PARAM: &str = "signature";
+
+/// Search links signed with the JWT secret, which are accepted without a
+/// bearer token
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchLinks {
+    /// Validity of created links, zero disables links
+    pub ttl: Duration,
+}
+
+/// Claims of the token a link was created with, searches of the link are
+/// accounted to its subject
+#[derive(Debug, Serialize, Deserialize)]
+struct LinkClaims {
+    sub: String,
+    aud: Vec<String>,
+    scope: Vec<Scope>,
+    #[serde(with = "ts_seconds")]
+    iat: DateTime<Utc>,
+    #[serde(with = "ts_seconds")]
+    exp: DateTime<Utc>,
+}
+
+impl SearchLinks {
+    pub fn enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+
+    /// Returns true if the query string carries a link signature
+    pub fn is_signed(query: &str) -> bool {
+        parse(query).iter().any(|(k, _)| k == SIGNATURE_PARAM)
+    }
+
+    /// Returns the signed query string of a search with the parameters and
+    /// its expiration time.
+    ///
+    /// Only the search scopes of the claims are passed on to the link.
+    pub fn sign(
+        &self,
+        mut params: Vec<(String, String)>,
+        claims: &Claims,
+        config: &TokenConfig,
+    ) -> (String, DateTime<Utc>) {
+        let now = Utc::now();
+        let link = LinkClaims {
+            sub: claims.subject().to_owned(),
+            aud: claims.audience().to_vec(),
+            scope: claims
+                .scopes()
+                .iter()
+                .filter(|s| matches!(s, Scope::Search | Scope::SearchSummary))
+                .cloned()
+                .collect(),
+            iat: now,
+            exp: now + chrono::Duration::seconds(self.ttl.as_secs() as i64),
+        };
+
+        let encoded = serde_json::to_vec(&link).unwrap_or_default();
+        params.retain(|(k, _)| k != LINK_PARAM && k != SIGNATURE_PARAM);
+        params.push((LINK_PARAM.to_string(), URL_SAFE_NO_PAD.encode(encoded)));
+
+        let message = canonical(params);
+        // The signature is URL-safe base64, so it isn't encoded
+        let signature = config.sign(message.as_bytes());
+        let query = format!("{}&{}={}", message, SIGNATURE_PARAM, signature);
+
+        (query, link.exp)
+    }
+
+    /// Returns the claims of the link if the signature of the query string is
+    /// valid and the link isn't expired
+    pub fn verify(&self, query: &str, config: &TokenConfig) -> Result<Claims, TokenError> {
+        if !self.enabled() {
+            return Err(TokenError::Invalid);
+        }
+
+        let mut params = parse(query);
+        let signature = match params.iter().position(|(k, _)| k == SIGNATURE_PARAM) {
+            Some(i) => params.swap_remove(i).1,
+            None => return Err(TokenError::Invalid),
+        };
+        if params.iter().any(|(k, _)| k == SIGNATURE_PARAM) {
+            return Err(TokenError::Invalid);
+        }
+
+        let link = params
+            .iter()
+            .find(|(k, _)| k == LINK_PARAM)
+            .ok_or(TokenError::Invalid)?
+            .1
+            .clone();
+
+        if !config.verify(canonical(params).as_bytes(), &signature) {
+            return Err(TokenError::Invalid);
+        }
+
+        let link: LinkClaims = URL_SAFE_NO_PAD
+            .decode(link)
+            .ok()
+            .and_then(|v| serde_json::from_slice(&v).ok())
+            .ok_or(TokenError::Invalid)?;
+
+        if link.exp <= Utc::now() {
+            return Err(TokenError::Expired);
+        }
+        if config.revocations.is_revoked(&link.sub, link.iat) {
+            return Err(TokenError::Revoked);
+        }
+
+        let mut claims = Claims::new(link.aud, &link.sub, link.scope);
+        claims.set_expiration(link.exp);
+
+        Ok(claims)
+    }
+}
+
+fn parse(query: &str) -> Vec<(String, String)> {
+    serde_html_form::from_str(query).unwrap_or_default()
+}
+
+/// Returns the parameters in a stable order, so the signature doesn't depend
+/// on the order the parameters are sent in
+fn canonical(mut params: Vec<(String, String)>) -> String {
+    params.sort();
+    serde_html_form::to_string(params).unwrap_or_default()
+}
@@ -1,11 +1,92 @@
+mod alias;
+mod cache;
+mod deadline;
+mod experiment;
+mod federation;
+mod filter;
 mod handler;
+mod link;
+mod normalize;
+mod populated;
+mod ranking;
+mod refine;
 mod routes;
+mod slow;
+mod synonyms;
 
-use crate::{error::ErrorResponse, model::Status};
+use crate::{
+    audience::AudienceTier,
+    error::ErrorResponse,
+    model::Status,
+    token::{Claims, Scope},
+};
+
+use std::sync::Arc;
 
 use hyper::StatusCode;
+use search_state::{IndexState, LoadMonitor};
 
+pub use alias::KindAliases;
+pub use cache::{CacheStats, ResponseCache};
+pub use experiment::Experiments;
+pub use federation::TypeQuotas;
+pub use link::SearchLinks;
+pub use populated::require_populated;
+pub use ranking::{KindBoostMap, KindBoosts, Ranking};
+pub use refine::RefinementStore;
 pub use routes::routes;
+pub use slow::{SlowQuery, SlowQueryLog};
+
+/// State used by the search handler, bundled into one extractor
+#[derive(Clone)]
+pub struct SearchContext {
+    pub index: IndexState,
+    pub experiments: Arc<Experiments>,
+    pub load: Arc<LoadMonitor>,
+    pub quotas: Arc<TypeQuotas>,
+    pub slow_queries: Arc<SlowQueryLog>,
+    pub detection: LanguageDetection,
+    pub cache: Arc<ResponseCache>,
+    pub limits: ResultLimits,
+    pub refinements: Arc<RefinementStore>,
+    pub kind_boosts: Arc<KindBoosts>,
+    pub kind_aliases: Arc<KindAliases>,
+    pub ranking: Arc<Ranking>,
+}
+
+/// Detection of the query language if none is requested
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LanguageDetection {
+    pub enabled: bool,
+}
+
+/// Maximum result counts, the audience tier of the token takes precedence
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResultLimits {
+    pub max: Option<usize>,
+    /// Maximum for tokens without the search scope
+    pub max_summary: Option<usize>,
+}
+
+impl ResultLimits {
+    pub fn check(
+        &self,
+        limit: usize,
+        claims: &Claims,
+        tier: Option<&AudienceTier>,
+    ) -> Result<(), SearchError> {
+        let max = match tier.and_then(|t| t.max_limit) {
+            Some(m) => Some(m),
+            None if !claims.has_scope(&Scope::Search) => self.max_summary.or(self.max),
+            None => self.max,
+        };
+
+        match max {
+            Some(max) if limit > max => Err(SearchError::LimitTooHigh(max)),
+            _ => Ok(()),
+        }
+    }
+}
 
 #[derive(Debug, thiserror::Error)]
 pub enum SearchError {
@@ -13,6 +94,16 @@ pub enum SearchError {
     TermTooLong,
     #[error("The given term is too short")]
     TermTooShort,
+    #[error("The given language is not supported")]
+    UnsupportedLanguage,
+    #[error("The given limit exceeds the maximum of {}", _0)]
+    LimitTooHigh(usize),
+    #[error("Invalid kind boost: {}", _0)]
+    InvalidKindBoost(String),
+    #[error("The refined result is unknown or expired")]
+    UnknownRefinement,
+    #[error("Search links are disabled")]
+    LinksDisabled,
     #[error("Index error: {}", _0)]
     IndexError(#[from] search_index::Error),
     #[error("API error: {}", _0)]
@@ -26,11 +117,18 @@ impl ErrorResponse for SearchError {
 
     fn status_code(&self) -> StatusCode {
         match self {
-            Self::TermTooShort | Self::TermTooLong => StatusCode::BAD_REQUEST,
+            Self::TermTooShort
+            | Self::TermTooLong
+            | Self::UnsupportedLanguage
+            | Self::LimitTooHigh(_)
+            | Self::InvalidKindBoost(_) => StatusCode::BAD_REQUEST,
+            Self::UnknownRefinement | Self::LinksDisabled => StatusCode::NOT_FOUND,
             Self::IndexError(e) => match e {
-                search_index::Error::BadQuery(_) | search_index::Error::ParseError(_) => {
-                    StatusCode::BAD_REQUEST
-                }
+                search_index::Error::BadQuery(_)
+                | search_index::Error::ParseError(_)
+                | search_index::Error::QueryTooComplex(_)
+                | search_index::Error::UnknownKind { .. } => StatusCode::BAD_REQUEST,
+                search_index::Error::DocumentNotFound(_) => StatusCode::NOT_FOUND,
                 search_index::Error::IndexError(_) | search_index::Error::UnhealthyIndex(_) => {
                     StatusCode::INTERNAL_SERVER_ERROR
                 }
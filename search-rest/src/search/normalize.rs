@@ -0,0 +1,26 @@
+use unicode_normalization::UnicodeNormalization;
+
+/// Characters without a visible glyph, which are commonly carried along by
+/// copy-pasting from chat clients
+const INVISIBLE: [char; 6] = [
+    '\u{00AD}', // soft hyphen
+    '\u{200B}', // zero width space
+    '\u{200C}', // zero width non-joiner
+    '\u{200D}', // zero width joiner
+    '\u{2060}', // word joiner
+    '\u{FEFF}', // zero width no-break space
+];
+
+/// Normalizes a query before validation and parsing.
+///
+/// The query is NFKC normalized, stripped of invisible characters and
+/// lowercased. Whitespace is trimmed and collapsed to single spaces.
+pub fn normalize_query(query: &str) -> String {
+    let normalized = query
+        .nfkc()
+        .filter(|c| !INVISIBLE.contains(c))
+        .collect::<String>()
+        .to_lowercase();
+
+    normalized.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,54 @@
+use crate::model::Response as ModelResponse;
+
+use std::time::Duration;
+
+use axum::{
+    extract::State,
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use hyper::{header::RETRY_AFTER, http::HeaderValue, StatusCode};
+use metrics::increment_counter;
+use search_state::IndexState;
+use serde::Serialize;
+
+/// Suggested delay of retries while the indexes are built
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Body of responses rejected before the first index build
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NotPopulatedResponse {
+    code: u16,
+    message: &'static str,
+    populated: bool,
+    /// Seconds after which the request should be retried
+    retry_after: u64,
+}
+
+/// Middleware rejecting searches until the indexes were built once
+pub async fn require_populated<B>(
+    State(state): State<IndexState>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if state.is_populated() {
+        return next.run(req).await;
+    }
+
+    increment_counter!("search_not_populated_rejected_requests_total");
+
+    let retry_after = RETRY_DELAY.as_secs();
+    let body = NotPopulatedResponse {
+        code: StatusCode::SERVICE_UNAVAILABLE.as_u16(),
+        message: "index is not yet populated, try again later",
+        populated: false,
+        retry_after,
+    };
+    let mut res = ModelResponse::with_status(StatusCode::SERVICE_UNAVAILABLE, body).into_response();
+    res.headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+
+    res
+}
@@ -0,0 +1,318 @@
+use crate::error::Error;
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use arc_swap::ArcSwap;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ring::digest::{Context, SHA256};
+use search_index::{DocType, FieldBoosts, NgramRange};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::Receiver;
+use tracing::{error, info, warn};
+
+use super::{experiment::Boosts, synonyms::Synonyms, SearchError};
+
+/// Maximum number of kinds with a boost
+const MAX_KIND_BOOSTS: usize = 32;
+
+/// Upper bound of a kind boost
+const MAX_KIND_BOOST: f32 = 10.0;
+
+/// Upper bound of field and type boosts of the ranking file
+const MAX_RANKING_BOOST: f32 = 10.0;
+
+/// Upper bound of the ngram lengths
+const MAX_NGRAM_LEN: usize = 10;
+
+/// Length of the ranking hash in bytes before encoding
+const RANKING_HASH_LEN: usize = 12;
+
+pub type KindBoostMap = BTreeMap<String, f32>;
+
+/// Score boosts by item kind, tunable at runtime
+#[derive(Debug, Default)]
+pub struct KindBoosts {
+    boosts: ArcSwap<KindBoostMap>,
+}
+
+impl KindBoosts {
+    pub fn current(&self) -> Arc<KindBoostMap> {
+        self.boosts.load_full()
+    }
+
+    /// Replaces all boosts, returns the previous ones
+    pub fn replace(&self, boosts: KindBoostMap) -> Result<Arc<KindBoostMap>, SearchError> {
+        if boosts.len() > MAX_KIND_BOOSTS {
+            return Err(SearchError::InvalidKindBoost(format!(
+                "more than {} kinds",
+                MAX_KIND_BOOSTS
+            )));
+        }
+
+        if let Some((kind, boost)) = boosts
+            .iter()
+            .find(|(k, b)| k.is_empty() || !(b.is_finite() && **b > 0.0 && **b <= MAX_KIND_BOOST))
+        {
+            return Err(SearchError::InvalidKindBoost(format!(
+                "boost {} of kind \"{}\" isn't within (0, {}]",
+                boost, kind, MAX_KIND_BOOST
+            )));
+        }
+
+        let boosts = boosts
+            .into_iter()
+            .map(|(k, b)| (k.to_lowercase(), b))
+            .collect();
+
+        Ok(self.boosts.swap(Arc::new(boosts)))
+    }
+
+    /// Boosts as query options, a neutral boost is left out
+    pub fn to_options(&self) -> Vec<(String, f32)> {
+        self.boosts
+            .load()
+            .iter()
+            .filter(|(_, b)| **b != 1.0)
+            .map(|(k, b)| (k.clone(), *b))
+            .collect()
+    }
+}
+
+/// Terms of a query which must match, if the query doesn't say otherwise.
+///
+/// Partial matches like a share of the terms need minimum required clauses,
+/// which tantivy 0.21 doesn't support.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MinShouldMatch {
+    #[default]
+    Any,
+    All,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NgramParams {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Default for NgramParams {
+    fn default() -> Self {
+        let range = NgramRange::default();
+
+        Self {
+            min: range.min,
+            max: range.max,
+        }
+    }
+}
+
+impl From<NgramParams> for NgramRange {
+    fn from(params: NgramParams) -> Self {
+        Self {
+            min: params.min,
+            max: params.max,
+        }
+    }
+}
+
+/// Relevance settings of the ranking file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct RankingConfig {
+    pub field_boosts: Boosts,
+    /// Score factors by doc type
+    pub type_boosts: BTreeMap<String, f32>,
+    /// Ngram lengths, which only take effect when the index is created
+    pub ngram: NgramParams,
+    pub min_should_match: MinShouldMatch,
+    /// File of synonym groups, relative to the ranking file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub synonyms_file: Option<PathBuf>,
+}
+
+impl RankingConfig {
+    fn validate(&self) -> Result<Vec<(DocType, f32)>, String> {
+        let in_range = |b: f32| b.is_finite() && b >= 0.0 && b <= MAX_RANKING_BOOST;
+
+        let boosts = &self.field_boosts;
+        if ![boosts.name, boosts.description, boosts.compatible]
+            .into_iter()
+            .all(in_range)
+        {
+            return Err(format!(
+                "field boosts must be within [0, {}]",
+                MAX_RANKING_BOOST
+            ));
+        }
+
+        let ngram = &self.ngram;
+        if ngram.min == 0 || ngram.min > ngram.max || ngram.max > MAX_NGRAM_LEN {
+            return Err(format!(
+                "ngram lengths must satisfy 1 <= min <= max <= {}",
+                MAX_NGRAM_LEN
+            ));
+        }
+
+        self.type_boosts
+            .iter()
+            .map(|(t, b)| {
+                let r#type = DocType::from_str(t).map_err(|_| format!("unknown type \"{}\"", t))?;
+                if !(in_range(*b) && *b > 0.0) {
+                    return Err(format!(
+                        "boost {} of type \"{}\" isn't within (0, {}]",
+                        b, t, MAX_RANKING_BOOST
+                    ));
+                }
+                Ok((r#type, *b))
+            })
+            .collect()
+    }
+}
+
+/// Ranking settings applied to new queries
+#[derive(Debug)]
+pub struct ActiveRanking {
+    pub config: RankingConfig,
+    pub type_boosts: Vec<(DocType, f32)>,
+    pub synonyms: Synonyms,
+    /// Hash of the settings and synonyms, reported with every result
+    pub hash: String,
+}
+
+impl ActiveRanking {
+    fn new(config: RankingConfig, synonyms: &str) -> crate::Result<Self> {
+        let type_boosts = config.validate().map_err(invalid)?;
+        let parsed = Synonyms::parse(synonyms).map_err(invalid)?;
+
+        let mut ctx = Context::new(&SHA256);
+        ctx.update(&serde_json::to_vec(&config)?);
+        ctx.update(synonyms.as_bytes());
+        let hash = URL_SAFE_NO_PAD.encode(&ctx.finish().as_ref()[..RANKING_HASH_LEN]);
+
+        Ok(Self {
+            config,
+            type_boosts,
+            synonyms: parsed,
+            hash,
+        })
+    }
+
+    pub fn field_boosts(&self) -> FieldBoosts {
+        FieldBoosts::from(&self.config.field_boosts)
+    }
+
+    pub fn conjunction(&self) -> bool {
+        self.config.min_should_match == MinShouldMatch::All
+    }
+}
+
+fn invalid(e: String) -> Error {
+    Error::InvalidConfigVar("SEARCH_RANKING_FILE", e)
+}
+
+/// Reads the ranking file and the content of its synonyms file
+fn read(path: &Path) -> crate::Result<(RankingConfig, String)> {
+    let config: RankingConfig = serde_json::from_slice(&fs::read(path)?)?;
+
+    let synonyms = match &config.synonyms_file {
+        Some(file) => {
+            let dir = path.parent().unwrap_or_else(|| Path::new("."));
+            fs::read_to_string(dir.join(file))?
+        }
+        None => String::new(),
+    };
+
+    Ok((config, synonyms))
+}
+
+/// Ranking settings of the ranking file, replaced atomically on reload
+#[derive(Debug)]
+pub struct Ranking {
+    path: Option<PathBuf>,
+    /// Ngram lengths the indexes were created with
+    ngram: NgramParams,
+    active: ArcSwap<ActiveRanking>,
+}
+
+impl Ranking {
+    /// Loads the ranking file, the defaults apply without a file
+    pub fn load(path: Option<PathBuf>) -> crate::Result<Self> {
+        let (config, synonyms) = match &path {
+            Some(p) => read(p)?,
+            None => Default::default(),
+        };
+        let active = ActiveRanking::new(config, &synonyms)?;
+
+        Ok(Self {
+            path,
+            ngram: active.config.ngram,
+            active: ArcSwap::from_pointee(active),
+        })
+    }
+
+    pub fn current(&self) -> Arc<ActiveRanking> {
+        self.active.load_full()
+    }
+
+    pub fn ngram_range(&self) -> NgramRange {
+        self.ngram.into()
+    }
+
+    /// Reads the ranking file again and applies changed settings
+    fn reload(&self, path: &Path) -> crate::Result<()> {
+        let (mut config, synonyms) = read(path)?;
+
+        if config.ngram != self.ngram {
+            warn!("Changed ngram lengths of the ranking file only take effect on restart");
+            config.ngram = self.ngram;
+        }
+
+        let active = ActiveRanking::new(config, &synonyms)?;
+        if active.hash == self.active.load().hash {
+            return Ok(());
+        }
+
+        info!(
+            hash = %active.hash,
+            synonyms = active.synonyms.len(),
+            "Ranking config reloaded"
+        );
+        self.active.store(Arc::new(active));
+
+        Ok(())
+    }
+
+    /// Checks the ranking file for changes periodically.
+    ///
+    /// Invalid changes are logged and the current settings are kept.
+    pub async fn watch(self: Arc<Self>, interval: Duration, mut shutdown: Receiver<()>) {
+        let path = match &self.path {
+            Some(p) => p.clone(),
+            None => return,
+        };
+
+        let mut interval = tokio::time::interval(interval);
+        interval.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.recv() => break,
+            }
+
+            if let Err(e) = self.reload(&path) {
+                error!(error = %e, path = %path.display(), "Couldn't reload the ranking config");
+            }
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use ring::digest::{digest, SHA256};
+use search_index::IndexDoc;
+
+use super::cache::{approximate_size, CacheStats, HitCounter};
+
+const CAPACITY: usize = 1000;
+
+/// Length of fingerprints in bytes before encoding
+const FINGERPRINT_LEN: usize = 12;
+
+/// Result of a previous search, which can be refined by further filters
+#[derive(Debug)]
+pub struct Refinement {
+    pub docs: Vec<IndexDoc>,
+    pub query: String,
+    pub language: String,
+    /// Modification time of the index the result was computed on
+    pub modified: DateTime<Utc>,
+    pub generation: u64,
+    pub ranking_hash: String,
+}
+
+/// Results of recent searches, addressed by the fingerprint of the query
+#[derive(Debug)]
+pub struct RefinementStore {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Arc<Refinement>, Instant)>>,
+    counter: HitCounter,
+}
+
+impl RefinementStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::default(),
+            counter: HitCounter::default(),
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+
+    /// Stores the result and returns its fingerprint, which is derived from
+    /// the key of the query
+    pub fn insert(&self, key: &str, refinement: Refinement) -> Option<String> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let hash = digest(&SHA256, key.as_bytes());
+        let fingerprint = URL_SAFE_NO_PAD.encode(&hash.as_ref()[..FINGERPRINT_LEN]);
+
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, (_, inserted)| inserted.elapsed() < self.ttl);
+
+        if entries.len() >= CAPACITY && !entries.contains_key(&fingerprint) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (_, inserted))| *inserted)
+                .map(|(k, _)| k.clone());
+            if let Some(k) = oldest {
+                entries.remove(&k);
+            }
+        }
+
+        entries.insert(fingerprint.clone(), (Arc::new(refinement), Instant::now()));
+
+        Some(fingerprint)
+    }
+
+    pub fn get(&self, fingerprint: &str) -> Option<Arc<Refinement>> {
+        let refinement = self
+            .entries
+            .lock()
+            .unwrap()
+            .get(fingerprint)
+            .filter(|(_, inserted)| inserted.elapsed() < self.ttl)
+            .map(|(r, _)| r.clone());
+
+        match refinement {
+            Some(_) => self.counter.hit(),
+            None => self.counter.miss(),
+        }
+
+        refinement
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let entries = self.entries.lock().unwrap();
+        let bytes = entries
+            .iter()
+            .map(|(k, (r, _))| approximate_size(k, &r.docs))
+            .sum();
+
+        CacheStats::new(entries.len(), CAPACITY, self.counter.get(), bytes)
+    }
+
+    /// Removes the older half of the entries, returns the number of removed
+    /// entries
+    pub fn shrink(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+
+        let count = entries.len();
+
+        let mut inserted = entries.values().map(|(_, i)| *i).collect::<Vec<_>>();
+        inserted.sort_unstable();
+        if let Some(newest_removed) = (count / 2).checked_sub(1).map(|i| inserted[i]) {
+            entries.retain(|_, (_, i)| *i > newest_removed);
+        }
+
+        count - entries.len()
+    }
+
+    /// Removes all entries and resets the statistics, returns the number of
+    /// removed entries
+    pub fn clear(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let count = entries.len();
+        entries.clear();
+        self.counter.reset();
+
+        count
+    }
+}
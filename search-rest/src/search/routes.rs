@@ -2,9 +2,15 @@ use crate::AppState;
 
 use super::handler;
 
-use axum::routing::get;
+use axum::routing::{get, post};
 
 /// Search routes
 pub fn routes() -> axum::Router<AppState> {
-    axum::Router::new().route("/", get(handler::get))
+    axum::Router::new()
+        .route("/", get(handler::get))
+        .route("/feedback", post(handler::feedback))
+        .route("/link", get(handler::link))
+        .route("/pattern", get(handler::pattern))
+        .route("/suggest", get(handler::suggest))
+        .route("/:id/related", get(handler::related))
 }
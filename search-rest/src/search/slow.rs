@@ -0,0 +1,52 @@
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+const CAPACITY: usize = 50;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlowQuery {
+    query: String,
+    took_ms: f64,
+    executed_at: DateTime<Utc>,
+}
+
+/// Keeps the most recent queries exceeding the threshold
+#[derive(Debug)]
+pub struct SlowQueryLog {
+    threshold: Duration,
+    entries: Mutex<VecDeque<SlowQuery>>,
+}
+
+impl SlowQueryLog {
+    pub fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            entries: Mutex::new(VecDeque::with_capacity(CAPACITY)),
+        }
+    }
+
+    pub fn record(&self, query: &str, took: Duration) {
+        if took < self.threshold {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == CAPACITY {
+            entries.pop_front();
+        }
+
+        entries.push_back(SlowQuery {
+            query: query.to_owned(),
+            took_ms: took.as_secs_f64() * 1000.0,
+            executed_at: Utc::now(),
+        });
+    }
+
+    /// Returns the recorded queries, most recent first
+    pub fn recent(&self) -> Vec<SlowQuery> {
+        self.entries.lock().unwrap().iter().rev().cloned().collect()
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+/// Maximum number of synonyms a term is expanded by
+const MAX_SYNONYMS: usize = 5;
+
+/// Equivalent terms, expanded at query time
+#[derive(Debug, Default)]
+pub struct Synonyms(HashMap<String, Vec<String>>);
+
+impl Synonyms {
+    /// Parses groups of equivalent terms, one comma-separated group per line.
+    ///
+    /// Empty lines and lines starting with `#` are ignored. Terms of several
+    /// words are only added to expansions, as queries are expanded by word.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut synonyms = HashMap::<String, Vec<String>>::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let group = line
+                .split(',')
+                .map(|t| t.split_whitespace().collect::<Vec<_>>().join(" "))
+                .filter(|t| !t.is_empty())
+                .map(|t| t.to_lowercase())
+                .collect::<Vec<_>>();
+
+            if group.len() < 2 {
+                return Err(format!("line {} has less than two terms", i + 1));
+            }
+            if let Some(t) = group.iter().find(|t| t.contains('"')) {
+                return Err(format!("term \"{}\" on line {} contains a quote", t, i + 1));
+            }
+
+            for term in group.iter().filter(|t| is_plain(t)) {
+                let entry = synonyms.entry(term.clone()).or_default();
+                for other in group.iter().filter(|o| *o != term) {
+                    if !entry.contains(other) {
+                        entry.push(other.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(Self(synonyms))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Replaces the plain words of a normalized query by a disjunction of the
+    /// word and its synonyms.
+    ///
+    /// Words with query syntax and words within phrases are left as they are.
+    pub fn expand(&self, query: &str) -> String {
+        if self.0.is_empty() {
+            return query.to_string();
+        }
+
+        let mut in_phrase = false;
+
+        query
+            .split(' ')
+            .map(|word| {
+                let quoted = in_phrase;
+                if word.matches('"').count() % 2 == 1 {
+                    in_phrase = !in_phrase;
+                }
+
+                match self.0.get(word) {
+                    Some(synonyms) if !quoted && is_plain(word) => {
+                        let mut terms = vec![word.to_string()];
+                        terms.extend(synonyms.iter().take(MAX_SYNONYMS).map(|s| {
+                            if s.contains(' ') {
+                                format!("\"{}\"", s)
+                            } else {
+                                s.clone()
+                            }
+                        }));
+                        format!("({})", terms.join(" OR "))
+                    }
+                    _ => word.to_string(),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+fn is_plain(word: &str) -> bool {
+    !word.is_empty() && word.chars().all(char::is_alphanumeric)
+}
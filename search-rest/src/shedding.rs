@@ -0,0 +1,128 @@
+use crate::model::Status;
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use axum::{
+    extract::State,
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use hyper::{header::AUTHORIZATION, StatusCode};
+use metrics::{gauge, increment_counter};
+
+/// Share of the capacity available to low priority requests
+const LOW_PRIORITY_SHARE: f64 = 0.75;
+
+/// Paths of the operational endpoints which are never shed
+const CRITICAL_PATHS: [&str; 4] = ["/health", "/admin", "/metrics", "/version"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Priority {
+    /// Health, admin and metrics endpoints
+    Critical,
+    /// Authenticated requests
+    Normal,
+    /// Requests without credentials
+    Low,
+}
+
+impl Priority {
+    fn classify<B>(req: &Request<B>) -> Self {
+        let path = req.uri().path();
+        if path == "/"
+            || CRITICAL_PATHS
+                .iter()
+                .any(|p| path == *p || path.starts_with(&format!("{}/", p)))
+        {
+            return Priority::Critical;
+        }
+
+        if req.headers().contains_key(AUTHORIZATION) {
+            Priority::Normal
+        } else {
+            Priority::Low
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Critical => "critical",
+            Priority::Normal => "normal",
+            Priority::Low => "low",
+        }
+    }
+}
+
+/// Sheds requests by priority once the number of in-flight requests exceeds
+/// the capacity of their class
+#[derive(Debug)]
+pub struct LoadShedder {
+    capacity: usize,
+    in_flight: AtomicUsize,
+}
+
+impl LoadShedder {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    fn limit(&self, priority: Priority) -> Option<usize> {
+        match priority {
+            Priority::Critical => None,
+            Priority::Normal => Some(self.capacity),
+            Priority::Low => Some((self.capacity as f64 * LOW_PRIORITY_SHARE) as usize),
+        }
+    }
+
+    fn try_acquire(self: &Arc<Self>, priority: Priority) -> Option<InFlight> {
+        let current = self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let guard = InFlight(self.clone());
+
+        if self.limit(priority).is_some_and(|l| current >= l) {
+            return None;
+        }
+
+        gauge!("search_requests_in_flight", (current + 1) as f64);
+
+        Some(guard)
+    }
+}
+
+/// Counts a request as in-flight until dropped
+struct InFlight(Arc<LoadShedder>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Middleware shedding requests by priority
+pub async fn shed<B>(
+    State(shedder): State<Arc<LoadShedder>>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let priority = Priority::classify(&req);
+
+    let _guard = match shedder.try_acquire(priority) {
+        Some(g) => g,
+        None => {
+            increment_counter!("search_requests_shed_total", "priority" => priority.as_str());
+            return Status::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "service is overloaded, try again later",
+            )
+            .into_response();
+        }
+    };
+
+    next.run(req).await
+}
@@ -0,0 +1,165 @@
+use crate::connection::SetupIncoming;
+
+use std::sync::Arc;
+
+use hyper::server::accept::Accept;
+use rustls::{
+    server::{Acceptor, WantsServerCert},
+    version::{TLS12, TLS13},
+    Certificate, ConfigBuilder, PrivateKey, ServerConfig, SupportedCipherSuite,
+    SupportedProtocolVersion, ALL_CIPHER_SUITES,
+};
+use rustls_acme::is_tls_alpn_challenge;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio_rustls::{server::TlsStream, LazyConfigAcceptor};
+use tracing::debug;
+
+/// Protocol settings of the server, the safe defaults of rustls are used for
+/// everything not set
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    /// Protocol versions, `1.2` or `1.3`
+    pub versions: Vec<String>,
+    /// Accepts only TLS 1.3, regardless of the versions
+    pub tls13_only: bool,
+    /// Names of the cipher suites in order of preference, e.g. `TLS13_AES_256_GCM_SHA384`
+    pub cipher_suites: Vec<String>,
+    /// ALPN protocols in order of preference
+    pub alpn: Vec<String>,
+}
+
+impl TlsOptions {
+    pub fn protocol_versions(&self) -> Result<Vec<&'static SupportedProtocolVersion>, String> {
+        if self.tls13_only {
+            return Ok(vec![&TLS13]);
+        }
+        if self.versions.is_empty() {
+            return Ok(vec![&TLS13, &TLS12]);
+        }
+
+        self.versions
+            .iter()
+            .map(|v| match v.trim().trim_start_matches("TLS").trim() {
+                "1.2" => Ok(&TLS12),
+                "1.3" => Ok(&TLS13),
+                _ => Err(format!("unsupported TLS version \"{}\"", v)),
+            })
+            .collect()
+    }
+
+    /// Returns the cipher suites of the protocol versions
+    pub fn cipher_suites(&self) -> Result<Vec<SupportedCipherSuite>, String> {
+        let versions = self.protocol_versions()?;
+        let usable =
+            |s: &SupportedCipherSuite| versions.iter().any(|v| v.version == s.version().version);
+
+        if self.cipher_suites.is_empty() {
+            return Ok(ALL_CIPHER_SUITES.iter().copied().filter(usable).collect());
+        }
+
+        let mut suites = Vec::with_capacity(self.cipher_suites.len());
+        for name in self.cipher_suites.iter() {
+            let suite = ALL_CIPHER_SUITES
+                .iter()
+                .find(|s| format!("{:?}", s.suite()).eq_ignore_ascii_case(name.trim()))
+                .ok_or_else(|| format!("unknown cipher suite \"{}\"", name))?;
+            if !usable(suite) {
+                return Err(format!(
+                    "cipher suite \"{}\" isn't usable with the TLS versions",
+                    name
+                ));
+            }
+            suites.push(*suite);
+        }
+
+        Ok(suites)
+    }
+
+    pub fn alpn_protocols(&self) -> Vec<Vec<u8>> {
+        if self.alpn.is_empty() {
+            return alpn_protocols();
+        }
+
+        self.alpn.iter().map(|p| p.as_bytes().to_vec()).collect()
+    }
+
+    /// Returns the problems of the options
+    pub fn check(&self) -> Vec<(&'static str, String)> {
+        let mut problems = Vec::new();
+
+        if let Err(e) = self.protocol_versions() {
+            problems.push(("server_tls_versions", e));
+        } else if let Err(e) = self.cipher_suites() {
+            problems.push(("server_tls_cipher_suites", e));
+        }
+        if let Some(p) = self.alpn.iter().find(|p| p.is_empty() || p.len() > 255) {
+            problems.push(("server_tls_alpn", format!("invalid protocol \"{}\"", p)));
+        }
+
+        problems
+    }
+
+    /// Returns a config builder with the versions and cipher suites
+    pub fn builder(&self) -> Result<ConfigBuilder<ServerConfig, WantsServerCert>, rustls::Error> {
+        let suites = self.cipher_suites().map_err(rustls::Error::General)?;
+        let versions = self.protocol_versions().map_err(rustls::Error::General)?;
+
+        Ok(ServerConfig::builder()
+            .with_cipher_suites(&suites)
+            .with_safe_default_kx_groups()
+            .with_protocol_versions(&versions)?
+            .with_no_client_auth())
+    }
+}
+
+/// Server config with a static certificate
+pub fn server_config(
+    certs: Vec<Certificate>,
+    key: PrivateKey,
+    opts: &TlsOptions,
+) -> Result<Arc<ServerConfig>, rustls::Error> {
+    let mut config = opts.builder()?.with_single_cert(certs, key)?;
+    config.alpn_protocols = opts.alpn_protocols();
+
+    Ok(Arc::new(config))
+}
+
+/// Default ALPN protocols
+pub fn alpn_protocols() -> Vec<Vec<u8>> {
+    vec![b"h2".to_vec(), b"http/1.1".to_vec(), b"http/1.0".to_vec()]
+}
+
+/// Accepts TLS connections of the incoming connections.
+///
+/// If a challenge config is given, TLS-ALPN-01 challenge connections are
+/// answered with it and closed.
+pub fn incoming<A>(
+    incoming: A,
+    config: Arc<ServerConfig>,
+    challenge: Option<Arc<ServerConfig>>,
+) -> SetupIncoming<A, TlsStream<A::Conn>>
+where
+    A: Accept,
+    A::Conn: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    SetupIncoming::new(incoming, move |conn| {
+        let config = config.clone();
+        let challenge = challenge.clone();
+
+        async move {
+            let start = LazyConfigAcceptor::new(Acceptor::default(), conn).await?;
+
+            if let Some(challenge) = challenge {
+                if is_tls_alpn_challenge(&start.client_hello()) {
+                    debug!("TLS-ALPN-01 challenge received");
+                    let mut tls = start.into_stream(challenge).await?;
+                    tls.shutdown().await?;
+
+                    return Ok(None);
+                }
+            }
+
+            start.into_stream(config).await.map(Some)
+        }
+    })
+}
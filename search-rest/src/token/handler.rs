@@ -1,105 +1,133 @@
 use crate::{
-    authentication::{AuthenticationError, TokenClaims, TokenConfig},
+    audience::AudienceTiers,
+    authentication::{AuthenticationError, TokenClaims, TokenConfig, TokenError},
     extract::{Json, TokenData},
     model::Response,
+    quota::{Usage, UsageTracker},
 };
 
-use super::{Claims, Scope};
+use super::{Claims, ReplayGuard, Scope, ScopePolicy, UserCache};
 
-use std::time;
+use std::{iter::once, sync::Arc};
 
 use axum::extract::State;
-use chrono::{serde::ts_seconds, DateTime, Duration, Utc};
+use chrono::{Duration, Utc};
 use hyper::StatusCode;
-use serde::{Deserialize, Serialize};
-use tarkov_database_rs::{client::Client, model::user::User};
-
-#[derive(Debug, Clone, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct TokenResponse {
-    token: String,
-    #[serde(with = "ts_seconds")]
-    expires_at: DateTime<Utc>,
-}
+use search_models::{CreateTokenRequest, TokenResponse};
+use tarkov_database_rs::client::Client;
+
+/// Maximum length of the client name and version
+const MAX_CLIENT_LEN: usize = 64;
 
 pub async fn get(
     TokenData(mut claims): TokenData<Claims, false>,
     State(mut client): State<Client>,
     State(config): State<TokenConfig>,
+    State(users): State<Arc<UserCache>>,
 ) -> crate::Result<Response<TokenResponse>> {
-    let user = get_user(&claims.sub, &mut client).await?;
-
-    if user.locked {
-        return Err(AuthenticationError::LockedUser.into());
+    // A renewed one-time token would keep its ID and outlive the replay guard
+    if claims.one_time_id().is_some() {
+        return Err(TokenError::NotRenewable.into());
     }
 
+    users.check_renewal(claims.subject(), &mut client).await?;
+
     claims.set_expiration(Utc::now() + Duration::minutes(Claims::DEFAULT_EXP_MINUTES));
 
     let token = claims.encode(&config)?;
+    config
+        .revocations
+        .track(claims.subject(), claims.expiration());
 
     let response = TokenResponse {
         token,
-        expires_at: claims.exp,
+        expires_at: claims.expiration(),
+        issuer: claims.issuer().map(str::to_string),
     };
 
     Ok(Response::with_status(StatusCode::CREATED, response))
 }
 
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct CreateRequest {
-    sub: String,
-    scope: Vec<Scope>,
-    #[serde(default)]
-    #[serde(with = "humantime_serde")]
-    valid_for: Option<time::Duration>,
-}
-
 pub async fn create(
-    TokenData(_claims): TokenData<Claims, true>,
+    TokenData(auth): TokenData<Claims, true>,
     State(mut client): State<Client>,
     State(config): State<TokenConfig>,
-    Json(body): Json<CreateRequest>,
+    State(tiers): State<Arc<AudienceTiers>>,
+    State(replay): State<Arc<ReplayGuard>>,
+    State(policy): State<Arc<ScopePolicy>>,
+    State(users): State<Arc<UserCache>>,
+    Json(body): Json<CreateTokenRequest>,
 ) -> crate::Result<Response<TokenResponse>> {
-    let user = get_user(&body.sub, &mut client).await?;
-
-    if user.locked {
-        return Err(AuthenticationError::LockedUser.into());
+    if !auth.has_scope(&Scope::Token) {
+        return Err(AuthenticationError::InsufficientPermission.into());
     }
 
-    let audience = config.validation.aud.clone().unwrap();
-    let mut claims = Claims::new(audience, &body.sub, body.scope);
+    replay.consume(&auth)?;
+
+    users.check(&body.sub, &mut client).await?;
+
+    let mut claims = match body.audience {
+        Some(name) => {
+            let tier = tiers.get(&name).ok_or(TokenError::UnknownAudience)?;
+            let scope = if body.scope.is_empty() {
+                tier.scopes.clone()
+            } else {
+                body.scope
+            };
+
+            Claims::new(once(name), &body.sub, scope)
+        }
+        None => {
+            let audience = config
+                .validation
+                .aud
+                .clone()
+                .unwrap()
+                .into_iter()
+                .filter(|a| tiers.get(a).is_none());
+            Claims::new(audience, &body.sub, body.scope)
+        }
+    };
+
+    policy.check(&auth, claims.scopes())?;
 
     if let Some(d) = body.valid_for {
         if let Ok(d) = Duration::from_std(d) {
-            claims.set_expiration(claims.iat + d);
+            claims.set_expiration(claims.issued_at() + d);
         }
     }
 
+    if body.one_time {
+        claims.set_one_time();
+    }
+
+    let too_long = |v: &Option<String>| v.as_ref().is_some_and(|v| v.len() > MAX_CLIENT_LEN);
+    if too_long(&body.client_name) || too_long(&body.client_version) {
+        return Err(TokenError::InvalidClientMetadata.into());
+    }
+    claims.set_client(body.client_name, body.client_version);
+    claims.set_issuer(config.issuer.clone());
+
     let token = claims.encode(&config)?;
+    config
+        .revocations
+        .track(claims.subject(), claims.expiration());
 
     let response = TokenResponse {
         token,
-        expires_at: claims.exp,
+        expires_at: claims.expiration(),
+        issuer: claims.issuer().map(str::to_string),
     };
 
     Ok(Response::with_status(StatusCode::CREATED, response))
 }
 
-async fn get_user(user_id: &str, client: &mut Client) -> crate::Result<User> {
-    if !client.token_is_valid().await {
-        client.refresh_token().await?;
-    }
-
-    let user = match client.get_user_by_id(user_id).await {
-        Ok(u) => u,
-        Err(e) => match e {
-            tarkov_database_rs::Error::ResourceNotFound => {
-                return Err(AuthenticationError::UnknownUser.into())
-            }
-            _ => return Err(e.into()),
-        },
-    };
+pub async fn usage(
+    TokenData(claims): TokenData<Claims, true>,
+    State(tracker): State<Arc<UsageTracker>>,
+    State(tiers): State<Arc<AudienceTiers>>,
+) -> crate::Result<Response<Usage>> {
+    let tier = tiers.resolve(&claims);
 
-    Ok(user)
+    Ok(Response::new(tracker.usage(claims.subject(), tier)))
 }
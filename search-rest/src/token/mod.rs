@@ -1,59 +1,31 @@
 mod handler;
+mod policy;
+mod replay;
+mod revocation;
 mod routes;
+mod users;
 
 use crate::authentication::TokenClaims;
 
-use chrono::{serde::ts_seconds, DateTime, Duration, Utc};
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
 
+pub use policy::ScopePolicy;
+pub use replay::ReplayGuard;
+pub use revocation::RevocationList;
 pub use routes::routes;
+pub use search_models::{Claims, Scope};
+pub use users::UserCache;
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub enum Scope {
-    Search,
-    Stats,
-    Token,
-}
-
-impl Default for Scope {
-    fn default() -> Self {
-        Self::Search
+impl TokenClaims for Claims {
+    fn issued(&self) -> Option<(&str, DateTime<Utc>)> {
+        Some((self.subject(), self.issued_at()))
     }
-}
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Claims {
-    aud: Vec<String>,
-    #[serde(with = "ts_seconds")]
-    exp: DateTime<Utc>,
-    #[serde(with = "ts_seconds")]
-    iat: DateTime<Utc>,
-    sub: String,
-    scope: Vec<Scope>,
-}
-
-impl Claims {
-    pub const DEFAULT_EXP_MINUTES: i64 = 60;
-
-    pub fn new<A, S>(aud: A, sub: &str, scope: S) -> Self
-    where
-        A: IntoIterator<Item = String>,
-        S: IntoIterator<Item = Scope>,
-    {
-        Self {
-            aud: aud.into_iter().collect(),
-            exp: Utc::now() + Duration::minutes(Self::DEFAULT_EXP_MINUTES),
-            iat: Utc::now(),
-            sub: sub.into(),
-            scope: scope.into_iter().collect(),
-        }
+    fn client(&self) -> (Option<&str>, Option<&str>) {
+        (self.client_name(), self.client_version())
     }
 
-    pub fn set_expiration(&mut self, date: DateTime<Utc>) {
-        self.exp = date;
+    fn authorization_claims(&self) -> Option<&Claims> {
+        Some(self)
     }
 }
-
-impl TokenClaims for Claims {}
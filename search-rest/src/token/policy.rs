@@ -0,0 +1,29 @@
+use crate::authentication::TokenError;
+
+use super::{Claims, Scope};
+
+/// Bounds the scopes of created tokens by the scopes of the creating token
+#[derive(Debug, Default)]
+pub struct ScopePolicy {
+    /// Subjects allowed to issue any scope
+    trusted_subjects: Vec<String>,
+}
+
+impl ScopePolicy {
+    pub fn new(trusted_subjects: Vec<String>) -> Self {
+        Self { trusted_subjects }
+    }
+
+    /// Checks if the creator may issue tokens with the scopes
+    pub fn check(&self, creator: &Claims, scopes: &[Scope]) -> Result<(), TokenError> {
+        if self.trusted_subjects.iter().any(|s| s == creator.subject()) {
+            return Ok(());
+        }
+
+        if scopes.iter().all(|s| creator.has_scope(s)) {
+            Ok(())
+        } else {
+            Err(TokenError::ScopeEscalation)
+        }
+    }
+}
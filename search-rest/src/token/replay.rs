@@ -0,0 +1,49 @@
+use crate::authentication::TokenError;
+
+use super::Claims;
+
+use std::{collections::HashMap, sync::Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+
+/// Tracks the IDs of used one-time tokens until they expire
+///
+/// IDs are kept for one renewal period past the expiration, so a token renewed
+/// right before it expired can't be replayed either.
+#[derive(Debug, Default)]
+pub struct ReplayGuard {
+    require_jti: bool,
+    used: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl ReplayGuard {
+    pub fn new(require_jti: bool) -> Self {
+        Self {
+            require_jti,
+            used: Mutex::default(),
+        }
+    }
+
+    /// Marks the token as used, fails if it was already used.
+    ///
+    /// Tokens without ID are only accepted if one-time tokens aren't required.
+    pub fn consume(&self, claims: &Claims) -> Result<(), TokenError> {
+        let jti = match claims.one_time_id() {
+            Some(v) => v,
+            None if self.require_jti => return Err(TokenError::NotOneTime),
+            None => return Ok(()),
+        };
+
+        let now = Utc::now();
+        let mut used = self.used.lock().unwrap();
+        used.retain(|_, exp| *exp > now);
+
+        if used.contains_key(jti) {
+            return Err(TokenError::Replayed);
+        }
+        let retain_until = claims.expiration() + Duration::minutes(Claims::DEFAULT_EXP_MINUTES);
+        used.insert(jti.to_owned(), retain_until);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use metrics::increment_counter;
+use search_state::record_upstream_call;
+use tarkov_database_rs::client::Client;
+use tokio::sync::broadcast::Receiver;
+use tracing::{debug, error, info};
+
+/// Subjects whose tokens issued before their revocation are rejected.
+///
+/// Subjects of issued tokens are tracked until their tokens expire, so they
+/// can be checked against the API by the sweep.
+#[derive(Debug, Default)]
+pub struct RevocationList {
+    revoked: Mutex<HashMap<String, DateTime<Utc>>>,
+    issued: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl RevocationList {
+    /// Tracks the subject of a token until it expires
+    pub fn track(&self, subject: &str, expires: DateTime<Utc>) {
+        let mut issued = self.issued.lock().unwrap();
+        let exp = issued.entry(subject.to_owned()).or_insert(expires);
+        if *exp < expires {
+            *exp = expires;
+        }
+    }
+
+    /// Rejects all tokens of the subject issued until now
+    pub fn revoke(&self, subject: &str) {
+        self.revoked
+            .lock()
+            .unwrap()
+            .insert(subject.to_owned(), Utc::now());
+    }
+
+    pub fn is_revoked(&self, subject: &str, issued: DateTime<Utc>) -> bool {
+        self.revoked
+            .lock()
+            .unwrap()
+            .get(subject)
+            .is_some_and(|at| issued <= *at)
+    }
+
+    /// Returns the subjects with unexpired tokens which aren't revoked yet
+    fn pending(&self) -> Vec<String> {
+        let now = Utc::now();
+        let mut issued = self.issued.lock().unwrap();
+        issued.retain(|_, exp| *exp > now);
+
+        let revoked = self.revoked.lock().unwrap();
+        issued
+            .keys()
+            .filter(|s| !revoked.contains_key(*s))
+            .cloned()
+            .collect()
+    }
+
+    /// Periodically checks the subjects of unexpired tokens against the API and
+    /// revokes the tokens of locked or deleted users
+    pub async fn sweep(
+        &self,
+        shared: Arc<RwLock<Client>>,
+        interval: Duration,
+        mut shutdown: Receiver<()>,
+    ) {
+        let mut interval = tokio::time::interval(interval);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {},
+                _ = shutdown.recv() => break,
+            }
+
+            // The client is replaced when the API token is reloaded
+            let mut client = shared.read().unwrap().clone();
+            if !client.token_is_valid().await {
+                if let Err(e) = client.refresh_token().await {
+                    error!(error = %e, "Revocation sweep failed: error while refreshing API token");
+                    continue;
+                }
+            }
+
+            let subjects = self.pending();
+            debug!(subjects = subjects.len(), "Revocation sweep started");
+
+            for subject in subjects {
+                let start = Instant::now();
+                let result = client.get_user_by_id(&subject).await;
+                record_upstream_call("user", start.elapsed(), None);
+
+                let revoke = match result {
+                    Ok(u) => u.locked,
+                    Err(tarkov_database_rs::Error::ResourceNotFound) => true,
+                    Err(e) => {
+                        error!(error = %e, "Revocation sweep failed: error while getting user");
+                        break;
+                    }
+                };
+
+                if revoke {
+                    info!(subject = ?subject, "Tokens of locked user revoked");
+                    increment_counter!("search_token_revocations_total");
+                    self.revoke(&subject);
+                }
+            }
+        }
+    }
+}
@@ -6,5 +6,7 @@ use axum::routing::get;
 
 /// Token routes
 pub fn routes() -> axum::Router<AppState> {
-    axum::Router::new().route("/", get(handler::get).post(handler::create))
+    axum::Router::new()
+        .route("/", get(handler::get).post(handler::create))
+        .route("/usage", get(handler::usage))
 }
@@ -0,0 +1,146 @@
+use crate::authentication::AuthenticationError;
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use metrics::increment_counter;
+use search_state::record_upstream_call;
+use tarkov_database_rs::client::Client;
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy)]
+enum Lookup {
+    Found { locked: bool },
+    Unknown,
+}
+
+#[derive(Debug)]
+struct Entry {
+    lookup: Lookup,
+    fetched: Instant,
+}
+
+/// Cache of upstream user lookups.
+///
+/// Known users are cached for the TTL, unknown users for the negative TTL.
+/// Locked users are always looked up again, so unlocking takes effect
+/// immediately.
+#[derive(Debug)]
+pub struct UserCache {
+    ttl: Duration,
+    negative_ttl: Duration,
+    max_staleness: Duration,
+    grace: bool,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl UserCache {
+    pub fn new(ttl: Duration, negative_ttl: Duration, max_staleness: Duration) -> Self {
+        Self {
+            ttl,
+            negative_ttl,
+            max_staleness,
+            grace: false,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Enables the grace mode, in which renewals succeed with the cached state
+    /// of valid and unlocked users up to the maximum staleness if the API is
+    /// unavailable
+    pub fn with_grace(mut self, enabled: bool) -> Self {
+        self.grace = enabled;
+        self
+    }
+
+    /// Checks that the user exists and isn't locked
+    pub async fn check(&self, user_id: &str, client: &mut Client) -> crate::Result<()> {
+        self.lookup(user_id, client, false).await
+    }
+
+    /// Checks the user of a token renewal, soft-fails in grace mode
+    pub async fn check_renewal(&self, user_id: &str, client: &mut Client) -> crate::Result<()> {
+        self.lookup(user_id, client, self.grace).await
+    }
+
+    async fn lookup(&self, user_id: &str, client: &mut Client, grace: bool) -> crate::Result<()> {
+        let cached = self.cached(user_id);
+
+        let lookup = match cached {
+            Some((Lookup::Found { locked: false }, age)) if age < self.ttl => {
+                increment_counter!("search_user_cache_total", "result" => "hit");
+                Lookup::Found { locked: false }
+            }
+            Some((Lookup::Unknown, age)) if age < self.negative_ttl => {
+                increment_counter!("search_user_cache_total", "result" => "negative_hit");
+                Lookup::Unknown
+            }
+            _ => {
+                increment_counter!("search_user_cache_total", "result" => "miss");
+                match fetch_user(user_id, client).await {
+                    Ok(lookup) => {
+                        self.store(user_id, lookup);
+                        lookup
+                    }
+                    Err(e) => match cached {
+                        Some((lookup @ Lookup::Found { locked: false }, age))
+                            if grace && age < self.max_staleness =>
+                        {
+                            warn!(error = %e, user = user_id, "Renewing with cached user state");
+                            increment_counter!("search_token_grace_renewals_total");
+                            lookup
+                        }
+                        _ => return Err(e),
+                    },
+                }
+            }
+        };
+
+        match lookup {
+            Lookup::Found { locked: false } => Ok(()),
+            Lookup::Found { locked: true } => Err(AuthenticationError::LockedUser.into()),
+            Lookup::Unknown => Err(AuthenticationError::UnknownUser.into()),
+        }
+    }
+
+    fn cached(&self, user_id: &str) -> Option<(Lookup, Duration)> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(user_id)
+            .map(|e| (e.lookup, e.fetched.elapsed()))
+    }
+
+    fn store(&self, user_id: &str, lookup: Lookup) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, e| e.fetched.elapsed() < self.max_staleness.max(self.ttl));
+        entries.insert(
+            user_id.to_owned(),
+            Entry {
+                lookup,
+                fetched: Instant::now(),
+            },
+        );
+    }
+}
+
+/// Upstream calls are traced as children of the request span
+#[tracing::instrument(name = "upstream", level = "debug", skip(client))]
+async fn fetch_user(user_id: &str, client: &mut Client) -> crate::Result<Lookup> {
+    if !client.token_is_valid().await {
+        client.refresh_token().await?;
+    }
+
+    let start = Instant::now();
+    let result = client.get_user_by_id(user_id).await;
+    record_upstream_call("user", start.elapsed(), None);
+
+    match result {
+        Ok(u) => Ok(Lookup::Found { locked: u.locked }),
+        Err(tarkov_database_rs::Error::ResourceNotFound) => Ok(Lookup::Unknown),
+        Err(e) => Err(e.into()),
+    }
+}
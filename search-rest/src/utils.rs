@@ -1,4 +1,7 @@
-use std::{io, iter};
+use std::{fs, io, iter, path::Path};
+
+use secrecy::SecretString;
+use zeroize::Zeroizing;
 
 pub fn read_certs(mut rd: impl io::BufRead) -> Result<Vec<Vec<u8>>, io::Error> {
     let certs = rustls_pemfile::certs(&mut rd)?;
@@ -43,3 +46,12 @@ pub fn read_key(mut rd: impl io::BufRead) -> Result<Vec<u8>, io::Error> {
 
     Err(io::Error::new(io::ErrorKind::InvalidData, "no keys found"))
 }
+
+/// Reads a secret from a file, ignoring the trailing line break
+pub fn read_secret(path: &Path) -> Result<SecretString, io::Error> {
+    let secret = Zeroizing::new(fs::read_to_string(path)?);
+
+    Ok(SecretString::new(
+        secret.trim_end_matches(['\r', '\n']).to_string(),
+    ))
+}
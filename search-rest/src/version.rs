@@ -0,0 +1,55 @@
+use crate::{model::Response, search::Ranking};
+
+use std::sync::Arc;
+
+use axum::extract::State;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Features {
+    jemalloc: bool,
+    tls: bool,
+}
+
+/// Build information populated at compile time
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionInfo {
+    version: &'static str,
+    git_commit: &'static str,
+    build_timestamp: DateTime<Utc>,
+    tantivy_version: &'static str,
+    features: Features,
+    /// Hash of the active ranking config
+    ranking_hash: Option<String>,
+}
+
+impl VersionInfo {
+    pub fn new(tls: bool) -> Self {
+        let timestamp = env!("BUILD_TIMESTAMP").parse().unwrap_or_default();
+
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            git_commit: env!("BUILD_GIT_COMMIT"),
+            build_timestamp: Utc.timestamp_opt(timestamp, 0).single().unwrap_or_default(),
+            tantivy_version: env!("BUILD_TANTIVY_VERSION"),
+            features: Features {
+                jemalloc: cfg!(feature = "jemalloc"),
+                tls,
+            },
+            ranking_hash: None,
+        }
+    }
+}
+
+pub async fn get(
+    State(info): State<Arc<VersionInfo>>,
+    State(ranking): State<Arc<Ranking>>,
+) -> Response<VersionInfo> {
+    Response::new(VersionInfo {
+        ranking_hash: Some(ranking.current().hash.clone()),
+        ..info.as_ref().clone()
+    })
+}
@@ -0,0 +1,202 @@
+use crate::{health::service_status, maintenance::Maintenance};
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use metrics::increment_counter;
+use search_models::ServiceStatus;
+use search_state::{HandlerStatus, HealthService, IndexState};
+use serde::Serialize;
+use tokio::sync::broadcast::Receiver;
+use tracing::{debug, info, warn};
+
+/// Interval of comparing the health with the last notified one
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Message understood by Slack (`text`) and Discord (`content`) webhooks
+#[derive(Debug, Serialize)]
+struct WebhookMessage<'a> {
+    text: &'a str,
+    content: &'a str,
+}
+
+/// Health of a service, a change is notified once it persisted for the
+/// debounce time
+#[derive(Debug, Default)]
+struct Tracked {
+    notified: Option<ServiceStatus>,
+    pending: Option<(ServiceStatus, Instant)>,
+}
+
+impl Tracked {
+    /// Returns the previous and the current status if a change is due
+    fn observe(
+        &mut self,
+        current: ServiceStatus,
+        debounce: Duration,
+    ) -> Option<(ServiceStatus, ServiceStatus)> {
+        // The initial status isn't a transition
+        let notified = *self.notified.get_or_insert(current);
+        if current == notified {
+            self.pending = None;
+            return None;
+        }
+
+        let since = match self.pending {
+            Some((s, since)) if s == current => since,
+            _ => *self.pending.insert((current, Instant::now())).1,
+        };
+        if since.elapsed() < debounce {
+            return None;
+        }
+
+        self.notified = Some(current);
+        self.pending = None;
+
+        Some((notified, current))
+    }
+}
+
+/// Outbound webhooks notified about health transitions and repeatedly
+/// failing syncs
+pub struct HealthNotifier {
+    client: reqwest::Client,
+    urls: Vec<String>,
+    debounce: Duration,
+    /// Consecutive failed syncs notified about
+    max_failed_syncs: usize,
+    status: Arc<HandlerStatus>,
+    state: IndexState,
+    maintenance: Arc<Maintenance>,
+}
+
+impl HealthNotifier {
+    pub fn new(
+        urls: Vec<String>,
+        status: Arc<HandlerStatus>,
+        state: IndexState,
+        maintenance: Arc<Maintenance>,
+    ) -> reqwest::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+
+        Ok(Self {
+            client,
+            urls,
+            debounce: Duration::ZERO,
+            max_failed_syncs: usize::MAX,
+            status,
+            state,
+            maintenance,
+        })
+    }
+
+    /// Notifies a transition only once it persisted for the given time
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Notifies once the given number of syncs failed in a row
+    pub fn with_max_failed_syncs(mut self, count: usize) -> Self {
+        self.max_failed_syncs = count.max(1);
+        self
+    }
+
+    pub async fn run(self, mut shutdown: Receiver<()>) {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        let mut index = Tracked::default();
+        let mut api = Tracked::default();
+        let mut syncs_notified = false;
+
+        debug!(
+            webhooks = self.urls.len(),
+            "notifying about health transitions"
+        );
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown.recv() => break,
+                _ = interval.tick() => {},
+            }
+
+            let maintenance = self.maintenance.is_active();
+            let (_, services) = service_status(&self.status, &self.state, maintenance);
+
+            let services = [
+                (HealthService::Index, &mut index, services.index),
+                (HealthService::Api, &mut api, services.api),
+            ];
+            for (service, tracked, current) in services {
+                if let Some((from, to)) = tracked.observe(current, self.debounce) {
+                    let mut message = format!(
+                        "Search {} health changed from {:?} to {:?}",
+                        service.name(),
+                        from,
+                        to
+                    );
+                    if let Some(reason) = self.last_reason(service) {
+                        message.push_str(&format!(": {}", reason));
+                    }
+                    self.notify(&message).await;
+                }
+            }
+
+            let failed = self.status.failed_syncs();
+            if failed >= self.max_failed_syncs && !syncs_notified {
+                self.notify(&format!(
+                    "Search index sync failed {} times in a row",
+                    failed
+                ))
+                .await;
+                syncs_notified = true;
+            } else if failed == 0 && syncs_notified {
+                self.notify("Search index sync succeeded again").await;
+                syncs_notified = false;
+            }
+        }
+    }
+
+    /// Returns the message of the last failure of the service, if it's failed
+    fn last_reason(&self, service: HealthService) -> Option<String> {
+        self.status
+            .health_history()
+            .into_iter()
+            .find(|t| t.service == service)
+            .filter(|t| t.failed)
+            .and_then(|t| t.message)
+    }
+
+    async fn notify(&self, message: &str) {
+        info!(message, "Sending health notification");
+
+        let body = WebhookMessage {
+            text: message,
+            content: message,
+        };
+
+        for url in self.urls.iter() {
+            let result = self
+                .client
+                .post(url)
+                .json(&body)
+                .send()
+                .await
+                .and_then(|r| r.error_for_status());
+
+            match result {
+                Ok(_) => increment_counter!("search_health_webhooks_total", "result" => "ok"),
+                Err(e) => {
+                    increment_counter!("search_health_webhooks_total", "result" => "failed");
+                    warn!(error = %e, "Couldn't send health notification");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,394 @@
+//! End-to-end tests running the server over TLS against a mock of the Tarkov
+//! Database API.
+//!
+//! The mock implements only the endpoints used by the server and serves an
+//! empty item index.
+
+use std::{
+    collections::HashSet,
+    net::{SocketAddr, TcpListener},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{extract::Path as UrlPath, routing::get, Json, Router};
+use chrono::{Duration as ChronoDuration, Utc};
+use hyper::{header::AUTHORIZATION, HeaderMap};
+use jsonwebtoken::{EncodingKey, Header};
+use search_client::{
+    conformance::{Conformance, ConformanceOptions},
+    Client, CreateTokenRequest, Scope, SearchQuery, ServiceStatus,
+};
+use search_models::Claims;
+use search_rest::{build_app, serve, AppConfig};
+use serde_json::json;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::broadcast::{self, Sender},
+    task::JoinHandle,
+};
+
+const JWT_SECRET: &str = "e2e-secret";
+const JWT_AUDIENCE: &str = "e2e";
+const API_TOKEN: &str = "e2e-api-token";
+
+/// Time to wait for a condition before a test fails
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Server running in the test runtime, shut down on drop
+struct Server {
+    shutdown: Sender<()>,
+    base_url: String,
+}
+
+impl Server {
+    async fn start(api_addr: SocketAddr, dir: &Path, cert: &rcgen::Certificate) -> Self {
+        Self::start_with(api_addr, dir, cert, Vec::new()).await
+    }
+
+    /// Starts the server with additional configuration variables
+    async fn start_with(
+        api_addr: SocketAddr,
+        dir: &Path,
+        cert: &rcgen::Certificate,
+        extra_vars: Vec<(&str, String)>,
+    ) -> Self {
+        let cert_path = dir.join("cert.pem");
+        let key_path = dir.join("key.pem");
+        std::fs::write(&cert_path, cert.serialize_pem().unwrap()).unwrap();
+        std::fs::write(&key_path, cert.serialize_private_key_pem()).unwrap();
+
+        let port = free_port();
+
+        let mut vars = vec![
+            ("SEARCH_SERVER_PORT", port.to_string()),
+            ("SEARCH_SERVER_TLS", "true".to_string()),
+            ("SEARCH_SERVER_TLS_CERT", cert_path.display().to_string()),
+            ("SEARCH_SERVER_TLS_KEY", key_path.display().to_string()),
+            ("SEARCH_JWT_SECRET", JWT_SECRET.to_string()),
+            ("SEARCH_JWT_AUDIENCE", JWT_AUDIENCE.to_string()),
+            ("SEARCH_API_ORIGIN", format!("http://{api_addr}")),
+            ("SEARCH_API_TOKEN", API_TOKEN.to_string()),
+            ("SEARCH_UPDATE_INTERVAL", "1h".to_string()),
+        ];
+        vars.extend(extra_vars);
+        let config: AppConfig = envy::prefixed("SEARCH_")
+            .from_iter(vars.into_iter().map(|(k, v)| (k.to_string(), v)))
+            .unwrap();
+
+        let (shutdown, _) = broadcast::channel(2);
+        let (routes, index_handler) = build_app(&config, &shutdown).await.unwrap();
+
+        let signal = shutdown.subscribe();
+        tokio::spawn(async move { index_handler.run(signal).await.unwrap() });
+
+        let signal = shutdown.clone();
+        tokio::spawn(async move { serve(config, routes, &signal).await.unwrap() });
+
+        Self {
+            shutdown,
+            base_url: format!("https://localhost:{port}"),
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.shutdown.send(()).ok();
+    }
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// Authorization headers of the token requests received by the mock API
+type SeenTokens = Arc<Mutex<HashSet<String>>>;
+
+/// Starts the mock API, aborting the task stops it
+fn start_mock_api(seen: SeenTokens) -> (SocketAddr, JoinHandle<()>) {
+    let token = move |headers: HeaderMap| {
+        if let Some(auth) = headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok()) {
+            seen.lock().unwrap().insert(auth.to_string());
+        }
+
+        let claims = json!({
+            "sub": "search",
+            "iat": Utc::now().timestamp(),
+            "exp": (Utc::now() + ChronoDuration::hours(1)).timestamp(),
+        });
+        let token = jsonwebtoken::encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(API_TOKEN.as_bytes()),
+        )
+        .unwrap();
+
+        Json(json!({ "token": token }))
+    };
+
+    let routes = Router::new()
+        .route(
+            "/v2/token",
+            get({
+                let token = token.clone();
+                move |headers: HeaderMap| async move { token(headers) }
+            })
+            .post(move |headers: HeaderMap| async move { token(headers) }),
+        )
+        .route(
+            "/v2/item",
+            get(|| async {
+                Json(json!({
+                    "total": 0,
+                    "modified": Utc::now().to_rfc3339(),
+                    "kinds": {},
+                }))
+            }),
+        )
+        .route(
+            "/v2/user/:id",
+            get(|UrlPath(id): UrlPath<String>| async move {
+                Json(json!({
+                    "_id": id,
+                    "username": id,
+                    "email": format!("{id}@example.com"),
+                    "locked": false,
+                }))
+            }),
+        );
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = axum::Server::from_tcp(listener)
+        .unwrap()
+        .serve(routes.into_make_service());
+
+    let handle = tokio::spawn(async move {
+        server.await.unwrap();
+    });
+
+    (addr, handle)
+}
+
+/// Token signed with the server secret, standing in for one of an operator
+fn bootstrap_token() -> String {
+    let claims = Claims::new(
+        [JWT_AUDIENCE.to_string()],
+        "e2e-admin",
+        [Scope::Token, Scope::Admin],
+    );
+
+    jsonwebtoken::encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+    )
+    .unwrap()
+}
+
+fn http_client(cert: &rcgen::Certificate) -> reqwest::Client {
+    let cert = reqwest::Certificate::from_pem(cert.serialize_pem().unwrap().as_bytes()).unwrap();
+
+    reqwest::Client::builder()
+        .add_root_certificate(cert)
+        .build()
+        .unwrap()
+}
+
+/// Polls until the condition is met or fails after the timeout
+async fn wait_for<F, Fut>(what: &str, mut condition: F)
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    let poll = async {
+        while !condition().await {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    };
+
+    if tokio::time::timeout(TIMEOUT, poll).await.is_err() {
+        panic!("timed out waiting for {what}");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tokens_search_and_health() {
+    let dir = tempfile::tempdir().unwrap();
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+
+    let (api_addr, api) = start_mock_api(SeenTokens::default());
+    let server = Server::start(api_addr, dir.path(), &cert).await;
+    let http = http_client(&cert);
+
+    wait_for("the server to listen", || {
+        let req = http.get(&server.base_url).send();
+        async move { req.await.is_ok_and(|r| r.status().is_success()) }
+    })
+    .await;
+
+    let admin =
+        Client::with_http_client(http.clone(), &server.base_url).with_token(bootstrap_token());
+
+    wait_for("the initial index update", || {
+        let admin = admin.clone();
+        async move {
+            admin
+                .health()
+                .await
+                .is_ok_and(|h| h.service.api == ServiceStatus::Ok)
+        }
+    })
+    .await;
+
+    let token = admin
+        .create_token(&CreateTokenRequest {
+            sub: "e2e-user".to_string(),
+            scope: vec![Scope::Search],
+            ..Default::default()
+        })
+        .await
+        .expect("token should be created");
+
+    let one_time = admin
+        .create_token(&CreateTokenRequest {
+            sub: "e2e-issuer".to_string(),
+            scope: vec![Scope::Token, Scope::Search],
+            one_time: true,
+            ..Default::default()
+        })
+        .await
+        .expect("one-time token should be created");
+    let mut issuer =
+        Client::with_http_client(http.clone(), &server.base_url).with_token(one_time.token);
+    let request = CreateTokenRequest {
+        sub: "e2e-user".to_string(),
+        scope: vec![Scope::Search],
+        ..Default::default()
+    };
+    issuer
+        .create_token(&request)
+        .await
+        .expect("one-time token should be usable once");
+    assert!(
+        issuer.renew_token().await.is_err(),
+        "one-time token shouldn't be renewable"
+    );
+    assert!(
+        issuer.create_token(&request).await.is_err(),
+        "one-time token shouldn't be reusable"
+    );
+
+    let user =
+        Client::with_http_client(http.clone(), &server.base_url).with_token(token.token.clone());
+    let result = user
+        .search(&SearchQuery::new("bullet"))
+        .await
+        .expect("search should succeed");
+    assert!(result.data.is_empty());
+
+    let link: serde_json::Value = http
+        .get(format!("{}/search/link", server.base_url))
+        .query(&[("query", "bullet")])
+        .bearer_auth(&token.token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let url = format!("{}{}", server.base_url, link["url"].as_str().unwrap());
+    let res = http.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::OK);
+    let res = http
+        .get(url.replace("query=bullet", "query=bullets"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let report = Conformance::with_http_client(http.clone(), &server.base_url, token.token)
+        .run(&ConformanceOptions::default())
+        .await;
+    assert!(report.passed(), "{}", report);
+
+    api.abort();
+    let res = http
+        .post(format!("{}/admin/reindex", server.base_url))
+        .bearer_auth(bootstrap_token())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), reqwest::StatusCode::ACCEPTED);
+
+    wait_for("the API failure to be reported", || {
+        let admin = admin.clone();
+        async move {
+            admin
+                .health()
+                .await
+                .is_ok_and(|h| !h.ok && h.service.api == ServiceStatus::Failure)
+        }
+    })
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn api_token_reloaded_on_hangup() {
+    const RELOADED_TOKEN: &str = "e2e-reloaded-api-token";
+
+    let dir = tempfile::tempdir().unwrap();
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+
+    // Installs the handler of the process, so the signal doesn't terminate it
+    let _hangup = signal(SignalKind::hangup()).unwrap();
+
+    let token_path = dir.path().join("api-token");
+    std::fs::write(&token_path, format!("{API_TOKEN}\n")).unwrap();
+
+    let seen = SeenTokens::default();
+    let (api_addr, _api) = start_mock_api(seen.clone());
+    let server = Server::start_with(
+        api_addr,
+        dir.path(),
+        &cert,
+        vec![("SEARCH_API_TOKEN_FILE", token_path.display().to_string())],
+    )
+    .await;
+    let http = http_client(&cert);
+
+    wait_for("the server to listen", || {
+        let req = http.get(&server.base_url).send();
+        async move { req.await.is_ok_and(|r| r.status().is_success()) }
+    })
+    .await;
+
+    std::fs::write(&token_path, format!("{RELOADED_TOKEN}\n")).unwrap();
+    let status = std::process::Command::new("kill")
+        .args(["-HUP", &std::process::id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // Reindexes refresh the API token of the replaced clients
+    let expected = format!("Bearer {RELOADED_TOKEN}");
+    wait_for("the reloaded API token to be used", || {
+        let req = http
+            .post(format!("{}/admin/reindex", server.base_url))
+            .bearer_auth(bootstrap_token())
+            .send();
+        let seen = seen.clone();
+        let expected = expected.clone();
+        async move {
+            req.await.ok();
+            seen.lock().unwrap().contains(&expected)
+        }
+    })
+    .await;
+}
@@ -0,0 +1,176 @@
+use crate::{IndexState, Result};
+
+use std::time::{Duration, Instant};
+
+use search_index::{
+    BatchSummary, DocType, FieldBoosts, Index, Language, NameAnalyzer, QueryOptions, Supplements,
+};
+use tarkov_database_rs::model::item::common::Item;
+
+/// Results fetched per smoke query
+const SMOKE_LIMIT: usize = 10;
+
+/// Documents of a type before and after the dry run
+#[derive(Debug, Clone)]
+pub struct TypeCount {
+    pub doc_type: DocType,
+    pub before: u64,
+    pub after: u64,
+}
+
+/// Hits of a smoke query on the served and the throwaway index
+#[derive(Debug, Clone)]
+pub struct SmokeResult {
+    pub query: String,
+    pub hits_before: usize,
+    pub hits_after: usize,
+    pub error: Option<String>,
+}
+
+impl SmokeResult {
+    /// Returns true if the query still has hits, or never had any
+    pub fn passed(&self) -> bool {
+        self.error.is_none() && (self.hits_after > 0 || self.hits_before == 0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LanguageDryRun {
+    pub language: Language,
+    pub documents_before: u64,
+    pub documents_after: u64,
+    pub types: Vec<TypeCount>,
+    pub batch: BatchSummary,
+    /// Reason the throwaway index is unhealthy
+    pub health_error: Option<String>,
+    pub smoke_queries: Vec<SmokeResult>,
+}
+
+impl LanguageDryRun {
+    pub fn passed(&self) -> bool {
+        self.health_error.is_none() && self.smoke_queries.iter().all(SmokeResult::passed)
+    }
+}
+
+/// What a reindex would change, without touching the served indexes
+#[derive(Debug, Clone)]
+pub struct DryRunReport {
+    pub items: usize,
+    pub languages: Vec<LanguageDryRun>,
+    pub duration: Duration,
+}
+
+impl DryRunReport {
+    pub fn passed(&self) -> bool {
+        self.languages.iter().all(LanguageDryRun::passed)
+    }
+}
+
+impl IndexState {
+    /// Writes the items to throwaway copies of the indexes, then checks their
+    /// health and compares the smoke queries with the served indexes.
+    ///
+    /// The copies are kept in memory unless `on_disk` is set.
+    pub fn dry_run(
+        &self,
+        items: &[Item],
+        supplements: &Supplements,
+        smoke_queries: &[String],
+        on_disk: bool,
+    ) -> Result<DryRunReport> {
+        let start = Instant::now();
+
+        let languages = self
+            .indexes
+            .iter()
+            .map(
+                |entry| match supplements.localize(entry.index.language(), items) {
+                    Some((items, supplements)) => {
+                        dry_run_index(&entry.index, &items, &supplements, smoke_queries, on_disk)
+                    }
+                    None => dry_run_index(&entry.index, items, supplements, smoke_queries, on_disk),
+                },
+            )
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(DryRunReport {
+            items: items.len(),
+            languages,
+            duration: start.elapsed(),
+        })
+    }
+}
+
+fn dry_run_index(
+    served: &Index,
+    items: &[Item],
+    supplements: &Supplements,
+    smoke_queries: &[String],
+    on_disk: bool,
+) -> Result<LanguageDryRun> {
+    let scratch = if on_disk {
+        served.empty_on_disk()?
+    } else {
+        served.empty_in_memory()?
+    };
+    let stats = scratch.write_index(items, supplements)?;
+
+    let before = served.num_docs_by_type()?;
+    let after = scratch.num_docs_by_type()?;
+    let types = before
+        .into_iter()
+        .zip(after)
+        .map(|((doc_type, before), (_, after))| TypeCount {
+            doc_type,
+            before,
+            after,
+        })
+        .collect();
+
+    let smoke_queries = smoke_queries
+        .iter()
+        .map(|query| {
+            let hits_before = smoke_query(served, query).unwrap_or_default();
+            match smoke_query(&scratch, query) {
+                Ok(hits_after) => SmokeResult {
+                    query: query.clone(),
+                    hits_before,
+                    hits_after,
+                    error: None,
+                },
+                Err(e) => SmokeResult {
+                    query: query.clone(),
+                    hits_before,
+                    hits_after: 0,
+                    error: Some(e.to_string()),
+                },
+            }
+        })
+        .collect();
+
+    Ok(LanguageDryRun {
+        language: served.language(),
+        documents_before: served.num_docs(),
+        documents_after: scratch.num_docs(),
+        types,
+        batch: stats.last_batch,
+        health_error: scratch.check_health().err().map(|e| e.to_string()),
+        smoke_queries,
+    })
+}
+
+fn smoke_query(index: &Index, query: &str) -> search_index::Result<usize> {
+    let opts = QueryOptions {
+        limit: SMOKE_LIMIT,
+        conjunction: false,
+        boosts: FieldBoosts::default(),
+        filters: Vec::new(),
+        lenient: false,
+        kind_boosts: Vec::new(),
+        type_boosts: Vec::new(),
+        fuzzy: None,
+        name_analyzer: NameAnalyzer::default(),
+    };
+
+    Ok(index.query_top(query, opts)?.docs.len())
+}
@@ -0,0 +1,60 @@
+use crate::{HandlerStatus, IndexState};
+
+use std::{sync::Arc, time::Duration};
+
+use metrics::increment_counter;
+use tokio::sync::broadcast::Receiver;
+use tracing::{debug, error, info};
+
+/// Periodic health check of the indexes, independent of the index updates
+pub struct HealthChecker {
+    state: IndexState,
+    status: Arc<HandlerStatus>,
+    interval: Duration,
+}
+
+impl HealthChecker {
+    pub fn new(state: IndexState, status: Arc<HandlerStatus>, interval: Duration) -> Self {
+        Self {
+            state,
+            status,
+            interval,
+        }
+    }
+
+    pub async fn run(self, mut shutdown: Receiver<()>) {
+        let mut interval = tokio::time::interval(self.interval);
+        // Only errors set by the check itself are cleared again, failed updates
+        // are resolved by the next successful update
+        let mut failed = false;
+
+        debug!(interval_secs = ?self.interval.as_secs_f64(), "checking index health");
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown.recv() => break,
+                _ = interval.tick() => {},
+            }
+
+            match self.state.check_health() {
+                Ok(()) => {
+                    increment_counter!("search_index_health_checks_total", "result" => "ok");
+                    if failed {
+                        info!("Index is healthy again");
+                        self.status.set_index_error(false);
+                        failed = false;
+                    }
+                }
+                Err(e) => {
+                    increment_counter!("search_index_health_checks_total", "result" => "failed");
+                    if !self.status.is_index_error() {
+                        error!(error = %e, "Periodic index health check failed");
+                        self.status.index_failed(&e);
+                        failed = true;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+
+/// Number of health transitions kept in memory
+const HISTORY_LEN: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthService {
+    Index,
+    Api,
+}
+
+impl HealthService {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HealthService::Index => "index",
+            HealthService::Api => "api",
+        }
+    }
+}
+
+/// Change of the health state of a service
+#[derive(Debug, Clone)]
+pub struct HealthTransition {
+    pub time: DateTime<Utc>,
+    pub service: HealthService,
+    pub failed: bool,
+    pub message: Option<String>,
+}
+
+/// Ring buffer of the last health transitions
+#[derive(Debug)]
+pub(crate) struct HealthHistory {
+    started: DateTime<Utc>,
+    transitions: VecDeque<HealthTransition>,
+}
+
+impl HealthHistory {
+    pub(crate) fn push(&mut self, service: HealthService, failed: bool, message: Option<String>) {
+        if self.transitions.len() == HISTORY_LEN {
+            self.transitions.pop_front();
+        }
+
+        self.transitions.push_back(HealthTransition {
+            time: Utc::now(),
+            service,
+            failed,
+            message,
+        });
+    }
+
+    pub(crate) fn started(&self) -> DateTime<Utc> {
+        self.started
+    }
+
+    /// Returns the transitions, newest first
+    pub(crate) fn transitions(&self) -> Vec<HealthTransition> {
+        self.transitions.iter().rev().cloned().collect()
+    }
+
+    /// Time of the last transition of the service or the start time
+    pub(crate) fn since(&self, service: HealthService) -> DateTime<Utc> {
+        self.transitions
+            .iter()
+            .rev()
+            .find(|t| t.service == service)
+            .map_or(self.started, |t| t.time)
+    }
+}
+
+impl Default for HealthHistory {
+    fn default() -> Self {
+        Self {
+            started: Utc::now(),
+            transitions: VecDeque::with_capacity(HISTORY_LEN),
+        }
+    }
+}
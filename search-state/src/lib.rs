@@ -1,18 +1,51 @@
+mod dry_run;
+mod health;
+mod history;
+mod load;
+mod optimize;
+mod origin;
+mod ratelimit;
+mod snapshot;
+mod upstream;
+
 use std::{
+    fmt,
+    path::PathBuf,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use chrono::{DateTime, TimeZone, Utc};
+use history::HealthHistory;
+use metrics::{counter, gauge, increment_counter};
+use origin::Origins;
+use ratelimit::TokenBucket;
+use snapshot::ItemSnapshot;
 use tarkov_database_rs::{client::Client, model::item::common::Item};
 use thiserror::Error;
-use tokio::sync::{broadcast::Receiver, RwLock};
-use tracing::{error, info};
+use tokio::sync::{broadcast::Receiver, mpsc, oneshot, RwLock};
+use tracing::{debug, error, info, warn};
+use upstream::{SchemaDrift, StatsCache};
+
+use search_index::{language_code, BatchSummary, GenerationDiff, Index, Language, Supplements};
+
+pub use dry_run::{DryRunReport, LanguageDryRun, SmokeResult, TypeCount};
+pub use health::HealthChecker;
+pub use history::{HealthService, HealthTransition};
+pub use load::{LoadMonitor, LoadThresholds, MemoryLimit};
+pub use optimize::OptimizeSchedule;
+pub use origin::FailoverPolicy;
+pub use ratelimit::RateLimit;
+pub use upstream::record_upstream_call;
 
-use search_index::Index;
+/// Target of the structured index sync lifecycle events
+pub const SYNC_TARGET: &str = "index_sync";
+
+/// Share of the documents of a type whose removal by a sync is logged as a warning
+const MASS_REMOVAL_RATIO: f64 = 0.1;
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -20,112 +53,810 @@ pub enum Error {
     IndexError(#[from] search_index::Error),
     #[error("API error: {0}")]
     ApiError(#[from] tarkov_database_rs::Error),
+    #[error("Handler is shutting down")]
+    ShuttingDown,
 }
 
 type Result<T> = std::result::Result<T, Error>;
 
+struct LanguageIndex {
+    index: Index,
+    modified: RwLock<DateTime<Utc>>,
+    healthy: AtomicBool,
+    /// Set by the first successful write, or if the index has documents already
+    populated: AtomicBool,
+}
+
+/// Registry of per-language indexes
 #[derive(Clone)]
 pub struct IndexState {
-    index: Index,
-    modified: Arc<RwLock<DateTime<Utc>>>,
+    indexes: Arc<Vec<LanguageIndex>>,
 }
 
 impl IndexState {
     pub fn new(index: Index) -> Self {
+        Self::with_indexes(vec![index])
+    }
+
+    /// Creates a registry from the given indexes, the first one is used as default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `indexes` is empty.
+    pub fn with_indexes(indexes: Vec<Index>) -> Self {
+        assert!(!indexes.is_empty(), "at least one index is required");
+
+        let indexes = indexes
+            .into_iter()
+            .map(|index| LanguageIndex {
+                // Indexes opened from disk are served until the first write
+                populated: AtomicBool::new(index.num_docs() > 0),
+                index,
+                modified: RwLock::new(Utc.timestamp(0, 0)),
+                healthy: AtomicBool::new(true),
+            })
+            .collect();
+
         Self {
-            index,
-            modified: Arc::new(RwLock::new(Utc.timestamp(0, 0))),
+            indexes: Arc::new(indexes),
         }
     }
 
+    fn entry(&self, lang: Language) -> Option<&LanguageIndex> {
+        self.indexes.iter().find(|e| e.index.language() == lang)
+    }
+
     pub fn get_index(&self) -> Index {
-        self.index.clone()
+        self.indexes[0].index.clone()
+    }
+
+    pub fn get_index_by_lang(&self, lang: Language) -> Option<Index> {
+        self.entry(lang).map(|e| e.index.clone())
     }
 
+    pub fn default_language(&self) -> Language {
+        self.indexes[0].index.language()
+    }
+
+    pub fn languages(&self) -> Vec<Language> {
+        self.indexes.iter().map(|e| e.index.language()).collect()
+    }
+
+    /// Returns the oldest modification time of all indexes
     pub async fn get_modified(&self) -> DateTime<Utc> {
-        *self.modified.read().await
+        let mut modified = None;
+
+        for entry in self.indexes.iter() {
+            let m = *entry.modified.read().await;
+            if modified.map_or(true, |v| m < v) {
+                modified = Some(m);
+            }
+        }
+
+        modified.unwrap()
+    }
+
+    pub async fn get_modified_by_lang(&self, lang: Language) -> Option<DateTime<Utc>> {
+        match self.entry(lang) {
+            Some(e) => Some(*e.modified.read().await),
+            None => None,
+        }
+    }
+
+    /// Returns the generation of the index, which changes with every update
+    pub fn get_generation_by_lang(&self, lang: Language) -> Option<u64> {
+        self.entry(lang).map(|e| e.index.generation())
+    }
+
+    /// Returns true once all indexes were written successfully
+    pub fn is_populated(&self) -> bool {
+        self.indexes
+            .iter()
+            .all(|e| e.populated.load(Ordering::SeqCst))
+    }
+
+    pub fn is_populated_by_lang(&self, lang: Language) -> Option<bool> {
+        self.entry(lang).map(|e| e.populated.load(Ordering::SeqCst))
     }
 
-    pub async fn update_items(&self, items: Vec<Item>) -> Result<()> {
-        let mut c_modified = self.modified.write().await;
+    pub fn is_healthy_by_lang(&self, lang: Language) -> Option<bool> {
+        self.entry(lang).map(|e| e.healthy.load(Ordering::SeqCst))
+    }
 
-        self.index.write_index(items)?;
+    /// Writes the items to all indexes, returns the summary of the batch
+    pub async fn update_items(
+        &self,
+        items: Vec<Item>,
+        supplements: &Supplements,
+    ) -> Result<BatchSummary> {
+        self.write_items(&items, supplements, Utc::now(), false)
+            .await
+    }
 
-        *c_modified = Utc::now();
+    /// Writes the items to all indexes like [`IndexState::update_items`], but
+    /// items modified upstream before the last write are kept as they are
+    pub async fn upsert_items(
+        &self,
+        items: Vec<Item>,
+        supplements: &Supplements,
+    ) -> Result<BatchSummary> {
+        self.write_items(&items, supplements, Utc::now(), true)
+            .await
+    }
+
+    /// Writes the items to all indexes and sets their modification time.
+    ///
+    /// The modification time is only advanced after all indexes are
+    /// committed and healthy, so a failed write is retried by the next
+    /// update.
+    async fn write_items(
+        &self,
+        items: &[Item],
+        supplements: &Supplements,
+        modified: DateTime<Utc>,
+        upsert: bool,
+    ) -> Result<BatchSummary> {
+        // Held until the end to serialize concurrent writes
+        let mut guards = Vec::with_capacity(self.indexes.len());
+        for entry in self.indexes.iter() {
+            guards.push(entry.modified.write().await);
+        }
+
+        let mut summary = BatchSummary::default();
+        let mut diff = GenerationDiff::default();
+
+        for (i, entry) in self.indexes.iter().enumerate() {
+            let localized = supplements.localize(entry.index.language(), items);
+            let (items, supplements) = match &localized {
+                Some((items, supplements)) => (items.as_slice(), supplements),
+                None => (items, supplements),
+            };
+
+            let stats = if upsert {
+                entry.index.upsert_items(items, supplements)?
+            } else {
+                entry.index.write_index(items, supplements)?
+            };
+
+            info!(
+                target: SYNC_TARGET,
+                event = "committed",
+                language = language_code(entry.index.language()),
+                commits = stats.commits,
+                opstamp = stats.last_opstamp,
+                indexed = stats.last_batch.indexed,
+                skipped = stats.last_batch.skipped,
+                unchanged = stats.last_batch.unchanged,
+                deleted = stats.last_deleted,
+                commit_ms = stats.last_duration.as_millis() as u64,
+                segments = stats.segments,
+                "Index committed"
+            );
+
+            // The indexes only differ by the translated texts, so the default
+            // index is reported
+            if i == 0 {
+                summary = stats.last_batch;
+                diff = stats.last_diff;
+            }
+        }
+
+        self.check_health()?;
+        counter!("search_indexed_documents_total", summary.indexed as u64);
+        counter!("search_unchanged_documents_total", summary.unchanged as u64);
+
+        for mut guard in guards {
+            *guard = modified;
+        }
+        for entry in self.indexes.iter() {
+            entry.populated.store(true, Ordering::SeqCst);
+        }
+
+        for e in summary.errors.iter() {
+            warn!(target: SYNC_TARGET, id = %e.id, reason = %e.reason, "Document skipped");
+        }
+
+        log_diff(&diff);
+
+        Ok(summary)
+    }
+
+    /// Merges the segments of all indexes down to the target count
+    pub fn optimize(&self, target_segments: usize) -> Result<()> {
+        for entry in self.indexes.iter() {
+            let stats = entry.index.optimize(target_segments)?;
+
+            info!(
+                target: SYNC_TARGET,
+                event = "optimized",
+                language = language_code(entry.index.language()),
+                segments_before = stats.segments_before,
+                segments_after = stats.segments_after,
+                optimize_ms = stats.duration.as_millis() as u64,
+                "Index optimized"
+            );
+        }
 
         Ok(())
     }
+
+    /// Checks the health of all indexes and returns the first error
+    pub fn check_health(&self) -> Result<()> {
+        let mut result = Ok(());
+
+        for entry in self.indexes.iter() {
+            let health = entry.index.check_health();
+            entry.healthy.store(health.is_ok(), Ordering::SeqCst);
+            gauge!(
+                "search_index_healthy",
+                if health.is_ok() { 1.0 } else { 0.0 },
+                "language" => language_code(entry.index.language())
+            );
+
+            if let Err(e) = health {
+                error!(
+                    language = language_code(entry.index.language()),
+                    error = %e,
+                    "Index is unhealthy"
+                );
+                if result.is_ok() {
+                    result = Err(e.into());
+                }
+            }
+        }
+
+        result
+    }
+}
+
+type DryRunReply = oneshot::Sender<Result<DryRunReport>>;
+
+/// Logs the changes of a sync by type, mass removals as warnings
+fn log_diff(diff: &GenerationDiff) {
+    for t in diff.types.iter().filter(|t| !t.is_empty()) {
+        info!(
+            target: SYNC_TARGET,
+            event = "diff",
+            generation = diff.generation,
+            doc_type = %t.r#type,
+            added = t.added.count,
+            removed = t.removed.count,
+            modified = t.modified.count,
+            "Documents changed"
+        );
+
+        let ratio = t.removed.count as f64 / t.previous.max(1) as f64;
+        if t.removed.count > 0 && ratio >= MASS_REMOVAL_RATIO {
+            warn!(
+                target: SYNC_TARGET,
+                generation = diff.generation,
+                doc_type = %t.r#type,
+                removed = t.removed.count,
+                previous = t.previous,
+                ids = ?t.removed.ids,
+                "Many documents removed by upstream"
+            );
+        }
+    }
+}
+
+/// Handle to request an immediate index update
+#[derive(Debug, Clone)]
+pub struct UpdateTrigger {
+    reindex: mpsc::Sender<()>,
+    dry_run: mpsc::Sender<DryRunReply>,
+    clients: mpsc::Sender<Vec<Client>>,
+}
+
+impl UpdateTrigger {
+    /// Requests a forced update that bypasses load deferral and the modification check
+    pub fn reindex(&self) {
+        // A full channel means an update is already pending
+        let _ = self.reindex.try_send(());
+    }
+
+    /// Runs a reindex into throwaway indexes and returns what it would change.
+    ///
+    /// Returns `None` if the handler isn't running.
+    pub async fn dry_run(&self) -> Option<Result<DryRunReport>> {
+        let (tx, rx) = oneshot::channel();
+        self.dry_run.send(tx).await.ok()?;
+        rx.await.ok()
+    }
+
+    /// Replaces the API clients of the primary and fallback origins, e.g.
+    /// after the API token changed.
+    ///
+    /// Returns false if the handler isn't running.
+    pub async fn replace_clients(&self, clients: Vec<Client>) -> bool {
+        self.clients.send(clients).await.is_ok()
+    }
+}
+
+/// Reason the handler is woken up
+enum Wakeup {
+    Update { force: bool },
+    Optimize,
+    DryRun(DryRunReply),
+    Clients(Vec<Client>),
 }
 
 pub struct IndexStateHandler {
     state: IndexState,
-    client: Client,
+    origins: Origins,
     status: Arc<HandlerStatus>,
     interval: Duration,
+    load: Arc<LoadMonitor>,
+    thresholds: LoadThresholds,
+    memory_limit: Option<MemoryLimit>,
+    rate_limit: Option<TokenBucket>,
+    stats_cache: StatsCache,
+    snapshot: Option<ItemSnapshot>,
+    schema_drift: SchemaDrift,
+    deferred_since: Option<Instant>,
+    supplements: Supplements,
+    optimize: Option<OptimizeSchedule>,
+    smoke_queries: Vec<String>,
+    trigger_tx: mpsc::Sender<()>,
+    trigger_rx: mpsc::Receiver<()>,
+    dry_run_tx: mpsc::Sender<DryRunReply>,
+    dry_run_rx: mpsc::Receiver<DryRunReply>,
+    clients_tx: mpsc::Sender<Vec<Client>>,
+    clients_rx: mpsc::Receiver<Vec<Client>>,
+    /// Shutdown signal of the running handler, ends waits for the API budget
+    shutdown: Option<Receiver<()>>,
 }
 
 impl IndexStateHandler {
     pub fn new(index: IndexState, client: Client, interval: Duration) -> Self {
+        let (trigger_tx, trigger_rx) = mpsc::channel(1);
+        let (dry_run_tx, dry_run_rx) = mpsc::channel(1);
+        let (clients_tx, clients_rx) = mpsc::channel(1);
+
         Self {
             state: index,
-            client,
+            origins: Origins::new(client),
             interval,
             status: Arc::new(HandlerStatus::default()),
+            load: Arc::new(LoadMonitor::default()),
+            thresholds: LoadThresholds::default(),
+            memory_limit: None,
+            rate_limit: None,
+            stats_cache: StatsCache::default(),
+            snapshot: None,
+            schema_drift: SchemaDrift::default(),
+            deferred_since: None,
+            supplements: Supplements::default(),
+            optimize: None,
+            smoke_queries: Vec::new(),
+            trigger_tx,
+            trigger_rx,
+            dry_run_tx,
+            dry_run_rx,
+            clients_tx,
+            clients_rx,
+            shutdown: None,
         }
     }
 
+    pub fn with_load_thresholds(mut self, thresholds: LoadThresholds) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+
+    /// Shrinks the caches before index rebuilds started above the memory limit
+    /// and builds the dry run indexes on disk instead of in memory
+    pub fn with_memory_limit(mut self, limit: MemoryLimit) -> Self {
+        self.memory_limit = Some(limit);
+        self
+    }
+
+    /// Adds API clients of other origins used if the primary one keeps failing
+    pub fn with_fallback_origins(mut self, clients: Vec<Client>, policy: FailoverPolicy) -> Self {
+        self.origins = self.origins.with_fallbacks(clients, policy);
+        self
+    }
+
+    /// Limits the rate of calls to the upstream API
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(TokenBucket::new(limit));
+        self
+    }
+
+    /// Reuses the item index stats of the API for the given time
+    pub fn with_stats_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.stats_cache = StatsCache::new(ttl);
+        self
+    }
+
+    /// Persists the last indexed items to restore the index after a restart
+    pub fn with_snapshot(mut self, path: PathBuf) -> Self {
+        self.snapshot = Some(ItemSnapshot::new(path));
+        self
+    }
+
+    /// Sets the data indexed in addition to the API items
+    pub fn with_supplements(mut self, supplements: Supplements) -> Self {
+        self.supplements = supplements;
+        self
+    }
+
+    /// Merges the index segments daily at the scheduled time
+    pub fn with_optimize_schedule(mut self, schedule: OptimizeSchedule) -> Self {
+        self.optimize = Some(schedule);
+        self
+    }
+
+    /// Sets the queries compared by dry runs of a reindex
+    pub fn with_smoke_queries(mut self, queries: Vec<String>) -> Self {
+        self.smoke_queries = queries;
+        self
+    }
+
     pub fn status_ref(&self) -> Arc<HandlerStatus> {
         self.status.clone()
     }
 
-    async fn update_state(&mut self) {
-        if !self.client.token_is_valid().await {
-            if let Err(e) = self.client.refresh_token().await {
-                error!(error = %e, "Couldn't update index: error while refreshing API token");
-                self.status.set_client_error(true);
-                return;
-            }
+    pub fn load_monitor(&self) -> Arc<LoadMonitor> {
+        self.load.clone()
+    }
+
+    pub fn trigger(&self) -> UpdateTrigger {
+        UpdateTrigger {
+            reindex: self.trigger_tx.clone(),
+            dry_run: self.dry_run_tx.clone(),
+            clients: self.clients_tx.clone(),
         }
+    }
 
-        let stats = match self.client.get_item_index().await {
-            Ok(i) => i,
-            Err(e) => {
-                error!(error = %e, "Couldn't update index: error while getting index");
-                self.status.set_client_error(true);
-                return;
+    /// Returns true if a non-urgent update should be deferred due to high load
+    async fn should_defer(&mut self) -> bool {
+        // Never defer the initial population of the index
+        if self.state.get_modified().await == Utc.timestamp(0, 0) {
+            return false;
+        }
+
+        if !self.thresholds.is_exceeded(&self.load) {
+            return false;
+        }
+
+        let since = *self.deferred_since.get_or_insert_with(Instant::now);
+
+        match self.thresholds.max_deferral {
+            Some(max) if since.elapsed() >= max => {
+                warn!(
+                    deferred_secs = ?since.elapsed().as_secs_f64(),
+                    "Maximum deferral reached, updating despite high load"
+                );
+                false
             }
+            _ => true,
+        }
+    }
+
+    /// Takes a token for an upstream API call.
+    ///
+    /// Forced updates wait for the budget, others return false if it's exhausted.
+    /// Waits are cancelled by the shutdown of the handler, returning false.
+    async fn acquire_call(&mut self, call: &'static str, wait: bool) -> bool {
+        let bucket = match &mut self.rate_limit {
+            Some(b) => b,
+            None => return true,
         };
 
-        if self.state.get_modified().await.lt(&stats.modified) {
-            info!("Item index are out of date. Perform update...");
+        loop {
+            match bucket.try_acquire() {
+                Ok(()) => return true,
+                Err(delay) => {
+                    increment_counter!("search_upstream_throttled_total", "call" => call);
+
+                    if !wait {
+                        debug!(call, "Upstream API budget exhausted, update deferred");
+                        return false;
+                    }
+
+                    let sleep = tokio::time::sleep(delay);
+                    match &mut self.shutdown {
+                        Some(shutdown) => tokio::select! {
+                            _ = sleep => {}
+                            _ = shutdown.recv() => {
+                                debug!(call, "Wait for the upstream API budget cancelled by shutdown");
+                                return false;
+                            }
+                        },
+                        None => sleep.await,
+                    }
+                }
+            }
+        }
+    }
+
+    /// Tracks decoding failures caused by changes of the API data model
+    fn check_schema(&mut self, err: &tarkov_database_rs::Error) {
+        if !SchemaDrift::is_schema_error(err) {
+            return;
+        }
+
+        increment_counter!("search_upstream_schema_errors_total");
 
-            let items = match self.client.get_items_all().await {
-                Ok(d) => d,
-                Err(e) => {
-                    error!(error = %e, "Couldn't update index: error while getting items from API");
-                    self.status.set_client_error(true);
+        if self.schema_drift.record_failure() && !self.status.is_schema_incompatible() {
+            error!(error = %err, "Upstream API schema is incompatible");
+            self.status.set_schema_incompatible(true);
+        }
+    }
+
+    /// Resets the schema drift detection after a successful update
+    fn schema_ok(&mut self) {
+        self.schema_drift.reset();
+        self.status.set_schema_incompatible(false);
+    }
+
+    /// Counts a failed upstream call and publishes a resulting failover
+    fn origin_failed(&mut self) {
+        if self.origins.record_failure() {
+            self.status.set_active_origin(self.origins.active());
+        }
+    }
+
+    async fn update_state(&mut self, force: bool) {
+        self.origins.check_failback();
+        self.status.set_active_origin(self.origins.active());
+
+        if !self.origins.client().token_is_valid().await {
+            if !self.acquire_call("refresh_token", force).await {
+                return;
+            }
+            if let Err(e) = self.origins.client().refresh_token().await {
+                error!(error = %e, "Couldn't update index: error while refreshing API token");
+                self.status.client_failed(&e);
+                self.status.sync_failed();
+                self.origin_failed();
+                return;
+            }
+        }
+
+        let modified = match self.stats_cache.fresh() {
+            Some(m) if !force => {
+                increment_counter!("search_upstream_stats_cache_hits_total");
+                m
+            }
+            _ => {
+                if !self.acquire_call("item_index", force).await {
                     return;
                 }
-            };
 
-            if let Err(e) = self.state.update_items(items).await {
-                error!(error = %e, "Couldn't update index: error while writing item index");
-                self.status.set_index_error(true);
+                let start = Instant::now();
+                let result = self.origins.client().get_item_index().await;
+                record_upstream_call("item_index", start.elapsed(), None);
+
+                let stats = match result {
+                    Ok(i) => i,
+                    Err(e) => {
+                        error!(error = %e, "Couldn't update index: error while getting index");
+                        self.status.client_failed(&e);
+                        self.status.sync_failed();
+                        self.origin_failed();
+                        self.check_schema(&e);
+                        return;
+                    }
+                };
+
+                self.origins.record_success();
+                self.stats_cache.store(stats.modified);
+                stats.modified
+            }
+        };
+
+        if !force && self.state.get_modified().await.ge(&modified) {
+            // The items fetch is skipped as nothing changed upstream
+            counter!(
+                "search_upstream_bytes_saved_total",
+                self.stats_cache.payload_bytes()
+            );
+        } else {
+            if !force && self.should_defer().await {
+                info!("Item index are out of date, but update is deferred due to high load");
+                self.status.set_sync_deferred(true);
                 return;
             }
 
-            if let Err(e) = self.state.index.check_health() {
-                error!(error = %e, "Error while checking index health");
-                self.status.set_index_error(true);
+            self.deferred_since = None;
+            self.status.set_sync_deferred(false);
+
+            info!(
+                forced = force,
+                "Item index are out of date. Perform update..."
+            );
+
+            let start = Instant::now();
+            info!(target: SYNC_TARGET, event = "start", forced = force, "Index sync started");
+
+            if let Some(limit) = &self.memory_limit {
+                if let Some(resident) = limit.enforce() {
+                    warn!(
+                        target: SYNC_TARGET,
+                        resident_bytes = resident,
+                        limit_bytes = limit.bytes,
+                        "Memory limit exceeded before index rebuild"
+                    );
+                }
+            }
+
+            let success = self.sync_items(force).await;
+
+            info!(
+                target: SYNC_TARGET,
+                event = "end",
+                success,
+                duration_ms = start.elapsed().as_millis() as u64,
+                "Index sync finished"
+            );
+
+            if !success {
                 return;
             }
         }
 
         self.status.set_client_error(false);
         self.status.set_index_error(false);
+        self.status.set_stale(false);
+        self.status.sync_succeeded();
+        self.schema_ok();
+    }
+
+    /// Builds the indexes from the snapshot, the data is flagged as stale until
+    /// confirmed by the API
+    async fn restore_snapshot(&mut self) {
+        let snapshot = match &self.snapshot {
+            Some(s) => s,
+            None => return,
+        };
+
+        let (items, written) = match snapshot.load() {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(e) => {
+                warn!(error = %e, "Couldn't load item snapshot");
+                return;
+            }
+        };
+
+        let count = items.len();
+        let summary = match self
+            .state
+            .write_items(&items, &self.supplements, written, false)
+            .await
+        {
+            Ok(s) => s,
+            Err(e) => {
+                error!(error = %e, "Couldn't restore index from item snapshot");
+                return;
+            }
+        };
+
+        self.status.set_batch(&summary);
+        self.status.set_stale(true);
+
+        info!(
+            items = count,
+            written = %written,
+            "Index restored from item snapshot"
+        );
+    }
+
+    /// Fetches all items and writes them to the indexes, returns false on failure.
+    ///
+    /// Only forced syncs compare all items, otherwise items modified before the
+    /// last write are kept.
+    async fn sync_items(&mut self, force: bool) -> bool {
+        // Syncs are queued until the budget allows the call
+        if !self.acquire_call("items", true).await {
+            return false;
+        }
+
+        let start = Instant::now();
+        let result = self.origins.client().get_items_all().await;
+        let took = start.elapsed();
+
+        let items = match result {
+            Ok(d) => d,
+            Err(e) => {
+                record_upstream_call("items", took, None);
+                error!(error = %e, "Couldn't update index: error while getting items from API");
+                self.status.client_failed(&e);
+                self.status.sync_failed();
+                self.origin_failed();
+                self.check_schema(&e);
+                return false;
+            }
+        };
+
+        let payload = serde_json::to_vec(&items).ok();
+        let payload_bytes = payload.as_ref().map_or(0, |v| v.len() as u64);
+        self.stats_cache.set_payload_bytes(payload_bytes);
+        record_upstream_call("items", took, Some(payload_bytes));
+
+        info!(
+            target: SYNC_TARGET,
+            event = "fetched",
+            items = items.len(),
+            payload_bytes,
+            "Items fetched from API"
+        );
+
+        // The health of the written indexes is checked before the update is
+        // considered done
+        let result = if force {
+            self.state.update_items(items, &self.supplements).await
+        } else {
+            self.state.upsert_items(items, &self.supplements).await
+        };
+
+        match result {
+            Ok(summary) => self.status.set_batch(&summary),
+            Err(e) => {
+                error!(error = %e, "Couldn't update index: error while writing item index");
+                self.status.index_failed(&e);
+                self.status.sync_failed();
+                return false;
+            }
+        }
+
+        if let (Some(snapshot), Some(payload)) = (&self.snapshot, payload) {
+            if let Err(e) = snapshot.save(&payload) {
+                warn!(error = %e, "Couldn't write item snapshot");
+            }
+        }
+
+        true
+    }
+
+    /// Fetches all items and writes them to throwaway indexes
+    async fn dry_run(&mut self) -> Result<DryRunReport> {
+        if !self.origins.client().token_is_valid().await {
+            self.origins.client().refresh_token().await?;
+        }
+
+        if !self.acquire_call("items", true).await {
+            return Err(Error::ShuttingDown);
+        }
+
+        let start = Instant::now();
+        let result = self.origins.client().get_items_all().await;
+        record_upstream_call("items", start.elapsed(), None);
+        let items = result?;
+
+        info!(
+            target: SYNC_TARGET,
+            event = "dry_run",
+            items = items.len(),
+            "Items fetched for a dry run"
+        );
+
+        let on_disk = match &self.memory_limit {
+            Some(limit) => match limit.enforce() {
+                Some(resident) => {
+                    warn!(
+                        target: SYNC_TARGET,
+                        resident_bytes = resident,
+                        limit_bytes = limit.bytes,
+                        "Memory limit exceeded before dry run, building it on disk"
+                    );
+                    true
+                }
+                None => false,
+            },
+            None => false,
+        };
+
+        self.state
+            .dry_run(&items, &self.supplements, &self.smoke_queries, on_disk)
     }
 
     pub async fn run(mut self, mut shutdown: Receiver<()>) -> Result<()> {
+        self.shutdown = Some(shutdown.resubscribe());
+        self.restore_snapshot().await;
+
         let mut interval = tokio::time::interval(self.interval);
 
         tracing::debug!(
@@ -133,14 +864,45 @@ impl IndexStateHandler {
             "watching for changes",
         );
 
+        let mut next_optimize = self.optimize.map(|s| s.next_run(Utc::now()));
+
         loop {
-            tokio::select! {
+            let until_optimize = next_optimize
+                .map(|t| (t - Utc::now()).to_std().unwrap_or_default())
+                .unwrap_or_default();
+
+            let wakeup = tokio::select! {
                 biased;
                 _ = shutdown.recv() => break,
-                _ = interval.tick() => {},
+                Some(_) = self.trigger_rx.recv() => Wakeup::Update { force: true },
+                Some(reply) = self.dry_run_rx.recv() => Wakeup::DryRun(reply),
+                Some(clients) = self.clients_rx.recv() => Wakeup::Clients(clients),
+                _ = interval.tick() => Wakeup::Update { force: false },
+                _ = tokio::time::sleep(until_optimize), if next_optimize.is_some() => Wakeup::Optimize,
             };
 
-            self.update_state().await;
+            match (wakeup, self.optimize) {
+                (Wakeup::Update { force }, _) => self.update_state(force).await,
+                (Wakeup::DryRun(reply), _) => {
+                    let report = self.dry_run().await;
+                    if let Err(e) = &report {
+                        warn!(error = %e, "Reindex dry run failed");
+                    }
+                    let _ = reply.send(report);
+                }
+                (Wakeup::Clients(clients), _) => {
+                    if self.origins.replace_clients(clients) {
+                        info!("API clients replaced");
+                    }
+                }
+                (Wakeup::Optimize, Some(schedule)) => {
+                    if let Err(e) = self.state.optimize(schedule.target_segments) {
+                        error!(error = %e, "Scheduled index optimization failed");
+                    }
+                    next_optimize = Some(schedule.next_run(Utc::now()));
+                }
+                (Wakeup::Optimize, None) => {}
+            }
         }
 
         tracing::debug!("shutting down...");
@@ -153,17 +915,83 @@ impl IndexStateHandler {
 pub struct HandlerStatus {
     index_error: AtomicBool,
     client_error: AtomicBool,
+    sync_deferred: AtomicBool,
+    active_origin: AtomicUsize,
+    stale: AtomicBool,
+    schema_incompatible: AtomicBool,
+    skipped_documents: AtomicUsize,
+    /// Consecutive failed syncs
+    failed_syncs: AtomicUsize,
+    document_errors: Mutex<Vec<String>>,
+    history: Mutex<HealthHistory>,
 }
 
 impl HandlerStatus {
     pub fn set_index_error(&self, val: bool) {
-        tracing::debug!(value = ?val, "index error set");
-        self.index_error.store(val, Ordering::SeqCst);
+        self.set_error(HealthService::Index, val, None);
     }
 
     pub fn set_client_error(&self, val: bool) {
-        tracing::debug!(value = ?val, "client error set");
-        self.client_error.store(val, Ordering::SeqCst);
+        self.set_error(HealthService::Api, val, None);
+    }
+
+    /// Sets the index error and records the reason in the health history
+    pub fn index_failed(&self, reason: impl fmt::Display) {
+        self.set_error(HealthService::Index, true, Some(reason.to_string()));
+    }
+
+    /// Sets the client error and records the reason in the health history
+    pub fn client_failed(&self, reason: impl fmt::Display) {
+        self.set_error(HealthService::Api, true, Some(reason.to_string()));
+    }
+
+    fn set_error(&self, service: HealthService, val: bool, message: Option<String>) {
+        tracing::debug!(service = service.name(), value = ?val, "error set");
+
+        let flag = match service {
+            HealthService::Index => &self.index_error,
+            HealthService::Api => &self.client_error,
+        };
+
+        if flag.swap(val, Ordering::SeqCst) != val {
+            self.history.lock().unwrap().push(service, val, message);
+        }
+    }
+
+    /// Counts a failed sync, the count is reset by the next successful one
+    pub fn sync_failed(&self) {
+        self.failed_syncs.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn sync_succeeded(&self) {
+        self.failed_syncs.store(0, Ordering::SeqCst);
+    }
+
+    pub fn set_sync_deferred(&self, val: bool) {
+        tracing::debug!(value = ?val, "sync deferred set");
+        self.sync_deferred.store(val, Ordering::SeqCst);
+    }
+
+    pub fn set_stale(&self, val: bool) {
+        tracing::debug!(value = ?val, "stale set");
+        self.stale.store(val, Ordering::SeqCst);
+    }
+
+    pub fn set_schema_incompatible(&self, val: bool) {
+        tracing::debug!(value = ?val, "schema incompatible set");
+        self.schema_incompatible.store(val, Ordering::SeqCst);
+    }
+
+    /// Sets the documents skipped by the last index write
+    pub fn set_batch(&self, summary: &BatchSummary) {
+        self.skipped_documents
+            .store(summary.skipped, Ordering::SeqCst);
+        *self.document_errors.lock().unwrap() =
+            summary.errors.iter().map(|e| e.to_string()).collect();
+    }
+
+    pub fn set_active_origin(&self, val: usize) {
+        self.active_origin.store(val, Ordering::SeqCst);
     }
 
     pub fn is_index_error(&self) -> bool {
@@ -173,4 +1001,54 @@ impl HandlerStatus {
     pub fn is_client_error(&self) -> bool {
         self.client_error.load(Ordering::SeqCst)
     }
+
+    pub fn is_sync_deferred(&self) -> bool {
+        self.sync_deferred.load(Ordering::SeqCst)
+    }
+
+    /// Returns true if the indexes were restored from a snapshot not yet
+    /// confirmed by the API
+    pub fn is_stale(&self) -> bool {
+        self.stale.load(Ordering::SeqCst)
+    }
+
+    /// Returns true if API responses persistently fail to decode
+    pub fn is_schema_incompatible(&self) -> bool {
+        self.schema_incompatible.load(Ordering::SeqCst)
+    }
+
+    /// Number of consecutive failed syncs
+    pub fn failed_syncs(&self) -> usize {
+        self.failed_syncs.load(Ordering::SeqCst)
+    }
+
+    /// Number of documents skipped by the last index write
+    pub fn skipped_documents(&self) -> usize {
+        self.skipped_documents.load(Ordering::SeqCst)
+    }
+
+    /// Errors of the first documents skipped by the last index write
+    pub fn document_errors(&self) -> Vec<String> {
+        self.document_errors.lock().unwrap().clone()
+    }
+
+    /// Index of the API origin in use, 0 is the primary one
+    pub fn active_origin(&self) -> usize {
+        self.active_origin.load(Ordering::SeqCst)
+    }
+
+    /// Last health transitions, newest first
+    pub fn health_history(&self) -> Vec<HealthTransition> {
+        self.history.lock().unwrap().transitions()
+    }
+
+    /// Time the handler was started
+    pub fn started(&self) -> DateTime<Utc> {
+        self.history.lock().unwrap().started()
+    }
+
+    /// Time since which the service is in its current state
+    pub fn state_since(&self, service: HealthService) -> DateTime<Utc> {
+        self.history.lock().unwrap().since(service)
+    }
 }
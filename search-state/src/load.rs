@@ -0,0 +1,137 @@
+use std::{
+    collections::VecDeque,
+    fmt, fs,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+const SAMPLE_CAPACITY: usize = 1024;
+
+/// Tracks recent search latencies to judge the current load
+#[derive(Debug)]
+pub struct LoadMonitor {
+    samples: Mutex<VecDeque<(Instant, Duration)>>,
+    window: Duration,
+}
+
+impl LoadMonitor {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            samples: Mutex::new(VecDeque::with_capacity(SAMPLE_CAPACITY)),
+            window,
+        }
+    }
+
+    pub fn record(&self, latency: Duration) {
+        let mut samples = self.samples.lock().unwrap();
+
+        if samples.len() == SAMPLE_CAPACITY {
+            samples.pop_front();
+        }
+
+        samples.push_back((Instant::now(), latency));
+    }
+
+    /// Returns the 95th percentile of the latencies recorded within the window
+    pub fn p95_latency(&self) -> Option<Duration> {
+        let mut latencies = {
+            let samples = self.samples.lock().unwrap();
+            samples
+                .iter()
+                .filter(|(t, _)| t.elapsed() <= self.window)
+                .map(|(_, l)| *l)
+                .collect::<Vec<_>>()
+        };
+
+        if latencies.is_empty() {
+            return None;
+        }
+
+        latencies.sort_unstable();
+        let i = (latencies.len() * 95 + 99) / 100 - 1;
+
+        Some(latencies[i])
+    }
+}
+
+impl Default for LoadMonitor {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(60))
+    }
+}
+
+/// Thresholds above which non-urgent index updates are deferred
+#[derive(Debug, Clone, Default)]
+pub struct LoadThresholds {
+    /// 95th percentile search latency
+    pub latency: Option<Duration>,
+    /// One minute load average per CPU
+    pub cpu_load: Option<f64>,
+    /// Maximum time an update can be deferred
+    pub max_deferral: Option<Duration>,
+}
+
+impl LoadThresholds {
+    pub(crate) fn is_exceeded(&self, monitor: &LoadMonitor) -> bool {
+        if let Some(threshold) = self.latency {
+            if monitor.p95_latency().map_or(false, |l| l > threshold) {
+                return true;
+            }
+        }
+
+        if let Some(threshold) = self.cpu_load {
+            if cpu_load().map_or(false, |l| l > threshold) {
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+/// Memory ceiling of the search subsystem
+#[derive(Clone)]
+pub struct MemoryLimit {
+    pub bytes: u64,
+    /// Returns the current resident memory, if available
+    pub usage: fn() -> Option<u64>,
+    /// Frees memory held outside of the indexes, e.g. by caches
+    pub shrink: Option<Arc<dyn Fn() + Send + Sync>>,
+}
+
+impl fmt::Debug for MemoryLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryLimit")
+            .field("bytes", &self.bytes)
+            .field("shrink", &self.shrink.is_some())
+            .finish()
+    }
+}
+
+impl MemoryLimit {
+    /// Returns the resident memory if it exceeds the limit
+    pub(crate) fn exceeded(&self) -> Option<u64> {
+        (self.usage)().filter(|u| *u > self.bytes)
+    }
+
+    /// Shrinks the caches if the limit is exceeded, returns the resident memory
+    /// in that case
+    pub(crate) fn enforce(&self) -> Option<u64> {
+        let resident = self.exceeded()?;
+        if let Some(shrink) = &self.shrink {
+            shrink();
+        }
+
+        Some(resident)
+    }
+}
+
+fn cpu_load() -> Option<f64> {
+    let loadavg = fs::read_to_string("/proc/loadavg").ok()?;
+    let load = loadavg.split_whitespace().next()?.parse::<f64>().ok()?;
+    let cpus = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+
+    Some(load / cpus as f64)
+}
@@ -0,0 +1,22 @@
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Utc};
+
+/// Daily merge of the index segments
+#[derive(Debug, Clone, Copy)]
+pub struct OptimizeSchedule {
+    /// Time of the day in UTC
+    pub at: NaiveTime,
+    pub target_segments: usize,
+}
+
+impl OptimizeSchedule {
+    /// Returns the next scheduled run after `now`
+    pub fn next_run(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let today = Utc.from_utc_datetime(&now.date_naive().and_time(self.at));
+
+        if today > now {
+            today
+        } else {
+            today + Duration::days(1)
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::time::{Duration, Instant};
+
+use metrics::increment_counter;
+use tarkov_database_rs::client::Client;
+use tracing::{info, warn};
+
+/// When to switch between upstream API origins
+#[derive(Debug, Clone, Copy)]
+pub struct FailoverPolicy {
+    /// Consecutive failures after which the next origin is used
+    pub max_failures: u32,
+    /// Time after which the primary origin is used again
+    pub failback_after: Duration,
+}
+
+impl Default for FailoverPolicy {
+    fn default() -> Self {
+        Self {
+            max_failures: 3,
+            failback_after: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+/// Upstream API clients in order of preference, the first one is the primary
+pub(crate) struct Origins {
+    clients: Vec<Client>,
+    policy: FailoverPolicy,
+    active: usize,
+    failures: u32,
+    switched: Option<Instant>,
+}
+
+impl Origins {
+    pub(crate) fn new(primary: Client) -> Self {
+        Self {
+            clients: vec![primary],
+            policy: FailoverPolicy::default(),
+            active: 0,
+            failures: 0,
+            switched: None,
+        }
+    }
+
+    pub(crate) fn with_fallbacks(mut self, clients: Vec<Client>, policy: FailoverPolicy) -> Self {
+        self.clients.extend(clients);
+        self.policy = policy;
+        self
+    }
+
+    /// Replaces the clients of the origins, given in the same order.
+    ///
+    /// Returns false if the number of clients differs.
+    pub(crate) fn replace_clients(&mut self, clients: Vec<Client>) -> bool {
+        if clients.len() != self.clients.len() {
+            warn!(
+                expected = self.clients.len(),
+                got = clients.len(),
+                "API clients not replaced, the number of origins differs"
+            );
+            return false;
+        }
+
+        self.clients = clients;
+
+        true
+    }
+
+    /// Index of the origin in use
+    pub(crate) fn active(&self) -> usize {
+        self.active
+    }
+
+    pub(crate) fn client(&mut self) -> &mut Client {
+        &mut self.clients[self.active]
+    }
+
+    /// Switches back to the primary origin once the fail-back time has passed
+    pub(crate) fn check_failback(&mut self) {
+        if self.active == 0 {
+            return;
+        }
+
+        if self
+            .switched
+            .is_some_and(|t| t.elapsed() >= self.policy.failback_after)
+        {
+            info!(from = self.active, "Failing back to primary API origin");
+            increment_counter!("search_upstream_failbacks_total");
+
+            self.active = 0;
+            self.failures = 0;
+            self.switched = None;
+        }
+    }
+
+    pub(crate) fn record_success(&mut self) {
+        self.failures = 0;
+    }
+
+    /// Counts a failed call, returns true if it caused a failover
+    pub(crate) fn record_failure(&mut self) -> bool {
+        self.failures += 1;
+
+        if self.clients.len() < 2 || self.failures < self.policy.max_failures {
+            return false;
+        }
+
+        let next = (self.active + 1) % self.clients.len();
+        warn!(
+            from = self.active,
+            to = next,
+            failures = self.failures,
+            "Failing over to next API origin"
+        );
+        increment_counter!("search_upstream_failovers_total");
+
+        self.active = next;
+        self.failures = 0;
+        self.switched = Some(Instant::now());
+
+        true
+    }
+}
@@ -0,0 +1,48 @@
+use std::time::{Duration, Instant};
+
+/// Rate of the calls to the upstream API
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub per_second: f64,
+    pub burst: u32,
+}
+
+/// Token bucket refilled at a constant rate up to the burst size
+#[derive(Debug)]
+pub(crate) struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            tokens: f64::from(limit.burst),
+            last: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+
+        self.tokens =
+            (self.tokens + elapsed * self.limit.per_second).min(f64::from(self.limit.burst));
+        self.last = now;
+    }
+
+    /// Takes a token, otherwise returns the time until one is available
+    pub(crate) fn try_acquire(&mut self) -> Result<(), Duration> {
+        self.refill();
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+
+        let missing = 1.0 - self.tokens;
+        Err(Duration::from_secs_f64(missing / self.limit.per_second))
+    }
+}
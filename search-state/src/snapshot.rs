@@ -0,0 +1,43 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+
+use chrono::{DateTime, Utc};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use tarkov_database_rs::model::item::common::Item;
+
+/// Gzip compressed JSON of the last successfully indexed items
+#[derive(Debug, Clone)]
+pub(crate) struct ItemSnapshot {
+    path: PathBuf,
+}
+
+impl ItemSnapshot {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Replaces the snapshot with the JSON encoded items
+    pub(crate) fn save(&self, json: &[u8]) -> io::Result<()> {
+        let tmp = self.path.with_extension("tmp");
+
+        let mut encoder =
+            GzEncoder::new(BufWriter::new(File::create(&tmp)?), Compression::default());
+        encoder.write_all(json)?;
+        encoder.finish()?.flush()?;
+
+        // Renaming keeps the previous snapshot intact if writing fails
+        fs::rename(tmp, &self.path)
+    }
+
+    /// Returns the items and the time the snapshot was written
+    pub(crate) fn load(&self) -> io::Result<(Vec<Item>, DateTime<Utc>)> {
+        let file = File::open(&self.path)?;
+        let written = file.metadata()?.modified()?;
+        let items = serde_json::from_reader(GzDecoder::new(BufReader::new(file)))?;
+
+        Ok((items, written.into()))
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use metrics::histogram;
+
+/// Records the duration and the payload size of an upstream API call
+pub fn record_upstream_call(
+    endpoint: &'static str,
+    duration: Duration,
+    payload_bytes: Option<u64>,
+) {
+    histogram!(
+        "search_upstream_duration_seconds",
+        duration.as_secs_f64(),
+        "endpoint" => endpoint
+    );
+
+    if let Some(bytes) = payload_bytes {
+        histogram!(
+            "search_upstream_payload_bytes",
+            bytes as f64,
+            "endpoint" => endpoint
+        );
+    }
+}
+
+/// Cached item index stats of the upstream API.
+///
+/// The API doesn't support conditional requests, so changes are detected by
+/// comparing the modification time locally.
+#[derive(Debug, Default)]
+pub(crate) struct StatsCache {
+    ttl: Duration,
+    modified: Option<(DateTime<Utc>, Instant)>,
+    payload_bytes: u64,
+}
+
+impl StatsCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the cached modification time if it's younger than the TTL
+    pub(crate) fn fresh(&self) -> Option<DateTime<Utc>> {
+        self.modified
+            .filter(|(_, fetched)| fetched.elapsed() < self.ttl)
+            .map(|(m, _)| m)
+    }
+
+    pub(crate) fn store(&mut self, modified: DateTime<Utc>) {
+        self.modified = Some((modified, Instant::now()));
+    }
+
+    /// Size of the last fetched items payload
+    pub(crate) fn payload_bytes(&self) -> u64 {
+        self.payload_bytes
+    }
+
+    pub(crate) fn set_payload_bytes(&mut self, bytes: u64) {
+        self.payload_bytes = bytes;
+    }
+}
+
+/// Consecutive failures to decode API responses after which the upstream
+/// schema is considered incompatible
+const SCHEMA_DRIFT_FAILURES: u32 = 3;
+
+/// Detects persistent decoding failures of API responses
+#[derive(Debug, Default)]
+pub(crate) struct SchemaDrift {
+    failures: u32,
+}
+
+impl SchemaDrift {
+    /// Returns true if the error was caused by a response not matching the
+    /// data model
+    pub(crate) fn is_schema_error(err: &tarkov_database_rs::Error) -> bool {
+        let mut source: Option<&(dyn Error + 'static)> = Some(err);
+
+        while let Some(e) = source {
+            if e.downcast_ref::<serde_json::Error>()
+                .is_some_and(|e| e.is_data())
+            {
+                return true;
+            }
+            source = e.source();
+        }
+
+        false
+    }
+
+    /// Counts a decoding failure, returns true if the schema is incompatible
+    pub(crate) fn record_failure(&mut self) -> bool {
+        self.failures += 1;
+        self.failures >= SCHEMA_DRIFT_FAILURES
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.failures = 0;
+    }
+}
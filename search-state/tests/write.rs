@@ -0,0 +1,103 @@
+//! Tests of the index writes of the state
+
+use std::{fs, path::Path};
+
+use search_index::{Index, Language, NgramRange, Preset, Supplements, MIN_WRITE_BUFFER};
+use search_state::IndexState;
+
+/// Supplements with a single preset, which is enough for a healthy index
+fn supplements(name: &str) -> Supplements {
+    Supplements {
+        presets: vec![Preset {
+            id: "5a8ae43686f774377b73cfb3".to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            weapon: "5447a9cd4bdc2dbd208b4567".to_string(),
+            mods: Vec::new(),
+        }],
+        ..Default::default()
+    }
+}
+
+fn stored_index(path: &Path, lang: Language) -> Index {
+    Index::open_or_create(path, lang, MIN_WRITE_BUFFER, NgramRange::default()).unwrap()
+}
+
+#[tokio::test]
+async fn failed_health_check_keeps_modified() {
+    let state = IndexState::new(Index::new().unwrap());
+    let lang = state.default_language();
+    let modified = state.get_modified().await;
+
+    // An empty write leaves the index without searchable segments, which
+    // fails the health check after the commit
+    let result = state
+        .update_items(Vec::new(), &Supplements::default())
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(state.get_modified().await, modified);
+    assert_eq!(state.is_healthy_by_lang(lang), Some(false));
+    assert!(!state.is_populated());
+}
+
+#[tokio::test]
+async fn failed_commit_keeps_modified() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("en");
+    let state = IndexState::new(stored_index(&path, Language::English));
+    let lang = state.default_language();
+
+    state
+        .update_items(Vec::new(), &supplements("M4A1 Default"))
+        .await
+        .unwrap();
+    let modified = state.get_modified().await;
+    let generation = state.get_generation_by_lang(lang);
+    let docs = state.get_index().num_docs();
+
+    // Without its directory the changed document can't be committed
+    fs::remove_dir_all(&path).unwrap();
+    let result = state
+        .update_items(Vec::new(), &supplements("M4A1 Assault"))
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(state.get_modified().await, modified);
+    assert_eq!(state.get_generation_by_lang(lang), generation);
+    assert_eq!(state.get_index().num_docs(), docs);
+}
+
+#[tokio::test]
+async fn failed_language_keeps_other_indexes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("de");
+    let state = IndexState::with_indexes(vec![
+        stored_index(&path, Language::German),
+        Index::with_lang(Language::English).unwrap(),
+    ]);
+
+    state
+        .update_items(Vec::new(), &supplements("M4A1 Default"))
+        .await
+        .unwrap();
+    let modified = state.get_modified().await;
+    let generations = state
+        .languages()
+        .into_iter()
+        .map(|l| state.get_generation_by_lang(l))
+        .collect::<Vec<_>>();
+
+    // The failing index is written first, the write stops before the others
+    fs::remove_dir_all(&path).unwrap();
+    let result = state
+        .update_items(Vec::new(), &supplements("M4A1 Assault"))
+        .await;
+
+    assert!(result.is_err());
+    assert_eq!(state.get_modified().await, modified);
+    for (lang, generation) in state.languages().into_iter().zip(generations) {
+        assert_eq!(state.get_modified_by_lang(lang).await, Some(modified));
+        assert_eq!(state.get_generation_by_lang(lang), generation);
+    }
+}